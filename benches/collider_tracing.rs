@@ -0,0 +1,145 @@
+//! Times the collider pass on a default sized sandbox, both for a quiet frame and for a frame
+//! where every region changed at once, like after a TNT blast. A region is rebuilt like in
+//! `generate_sandbox_colliders`: its outlines are traced, simplified, split into convex pieces and
+//! turned into a compound collider.
+//!
+//! Run with `cargo bench --bench collider_tracing`
+
+#[path = "../src/sandbox/collider/contour.rs"]
+mod contour;
+#[path = "../src/sandbox/collider/convex.rs"]
+mod convex;
+#[path = "../src/sandbox/collider/simplify.rs"]
+mod simplify;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bevy::math::{IVec2, Vec2};
+use bevy_rapier2d::prelude::Collider;
+use rand::prelude::*;
+
+use contour::trace_contours;
+use convex::convex_pieces;
+use simplify::simplify_loop;
+
+const WIDTH: i32 = 240;
+const HEIGHT: i32 = 136;
+const CHUNK_SIZE: i32 = 8;
+const ITERATIONS: u32 = 100;
+/// The collider pass should stay well below this, it runs in the same frame as the simulation
+const FRAME_BUDGET: Duration = Duration::from_micros(16_667);
+
+fn main() {
+    let flat = flat_terrain();
+    let blasted = blasted_terrain(&flat);
+
+    for (name, grid) in [("flat", &flat), ("blasted", &blasted)] {
+        // Regions as built with `ColliderSettings::merge_chunks` on and off
+        bench(&format!("{name}, one chunk changed"), grid, CHUNK_SIZE, 1);
+        bench(
+            &format!("{name}, every row region"),
+            grid,
+            WIDTH,
+            HEIGHT / CHUNK_SIZE,
+        );
+        bench(
+            &format!("{name}, every chunk region"),
+            grid,
+            CHUNK_SIZE,
+            (WIDTH / CHUNK_SIZE) * (HEIGHT / CHUNK_SIZE),
+        );
+    }
+}
+
+fn bench(name: &str, grid: &[Vec<bool>], region_width: i32, regions: i32) {
+    let x_regions = WIDTH / region_width;
+    let filled = |x: i32, y: i32| grid[x as usize][y as usize];
+
+    let start = Instant::now();
+    let mut pieces = 0;
+    for _ in 0..ITERATIONS {
+        for region in 0..regions {
+            let low = IVec2::new(
+                (region % x_regions) * region_width,
+                (region / x_regions) * CHUNK_SIZE,
+            );
+            let high = low + IVec2::new(region_width, CHUNK_SIZE);
+            pieces += rebuild_region(low, high, filled);
+        }
+    }
+    let pass = start.elapsed() / ITERATIONS;
+
+    println!(
+        "{name:<32} {:>9.3} ms per pass, {:>5.1}% of a frame, {} convex pieces",
+        pass.as_secs_f64() * 1000.0,
+        pass.as_secs_f64() / FRAME_BUDGET.as_secs_f64() * 100.0,
+        pieces / ITERATIONS as usize,
+    );
+}
+
+/// Builds the solid collider of a region, returns how many convex pieces it has
+fn rebuild_region(low: IVec2, high: IVec2, filled: impl Fn(i32, i32) -> bool) -> usize {
+    let half_size = Vec2::new(WIDTH as f32, HEIGHT as f32) / 2.0;
+    let loops = trace_contours(low, high, filled)
+        .iter()
+        .map(|block| simplify_loop(block, 0.5))
+        .filter(|block| block.len() >= 3)
+        .map(|block| {
+            block
+                .into_iter()
+                .map(|pos| (pos - half_size + Vec2::splat(0.5)) * 8.0)
+                .collect()
+        })
+        .collect::<Vec<_>>();
+
+    let pieces = convex_pieces(&loops)
+        .into_iter()
+        .filter_map(Collider::convex_polyline)
+        .map(|piece| (Vec2::ZERO, 0.0, piece))
+        .collect::<Vec<_>>();
+    let count = pieces.len();
+    if count > 0 {
+        black_box(Collider::compound(pieces));
+    }
+    count
+}
+
+/// Ground filling the lower half with some hills, indexed by `[x][y]`
+fn flat_terrain() -> Vec<Vec<bool>> {
+    (0..WIDTH)
+        .map(|x| {
+            let ground = HEIGHT / 2 + ((x as f32 / 12.0).sin() * 6.0) as i32;
+            (0..HEIGHT).map(|y| y < ground).collect()
+        })
+        .collect()
+}
+
+/// The terrain after a row of explosions, with craters and debris scattered above them
+fn blasted_terrain(terrain: &[Vec<bool>]) -> Vec<Vec<bool>> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut grid = terrain.to_vec();
+
+    for _ in 0..12 {
+        let center = IVec2::new(
+            rng.gen_range(0..WIDTH),
+            rng.gen_range(HEIGHT / 4..HEIGHT / 2),
+        );
+        let radius = rng.gen_range(6..16);
+        for x in (center.x - radius).max(0)..(center.x + radius).min(WIDTH) {
+            for y in (center.y - radius).max(0)..(center.y + radius).min(HEIGHT) {
+                if IVec2::new(x, y).distance_squared(center) <= radius * radius {
+                    grid[x as usize][y as usize] = false;
+                }
+            }
+        }
+    }
+
+    for _ in 0..2000 {
+        let x = rng.gen_range(0..WIDTH) as usize;
+        let y = rng.gen_range(HEIGHT / 2..HEIGHT) as usize;
+        grid[x][y] = true;
+    }
+
+    grid
+}
@@ -0,0 +1,200 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    damage::{DamageEvent, DamageKind},
+    player::{
+        components::PlayerHealth,
+        grab::{Held, ParentObject, Rock},
+    },
+    sandbox::{
+        particle::{CollisionType, MovementType, Particle},
+        raycast::ParticleFilter,
+        sandbox::Sandbox,
+    },
+    schedule::GameplaySet,
+};
+
+/// Rocks compared to liquid `Density`, they sink in water but float on lava
+const ROCK_DENSITY: f32 = 4.5;
+/// How much liquids slow bodies down for every unit of `Density`
+const LIQUID_DRAG: f32 = 0.6;
+
+pub struct BuoyancyPlugin;
+
+impl Plugin for BuoyancyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (float_bodies, float_rocks)
+                .chain()
+                .in_set(GameplaySet::Terrain),
+        );
+    }
+}
+
+/// Floats a body in the sandbox liquids overlapping the circle around it. The density is
+/// compared to the liquid's `Density`, bodies lighter than the liquid float.
+#[derive(Component)]
+pub struct Buoyancy {
+    pub radius: f32,
+    pub density: f32,
+}
+
+/// How deep a body with [`Buoyancy`] is in liquids
+#[derive(Component, Default)]
+pub struct Submerged {
+    /// Part of the body that is covered in liquid, from 0 to 1
+    pub fraction: f32,
+    /// Average `Density` of the liquids around the body
+    pub density: f32,
+}
+
+fn is_liquid(particle: &Particle) -> bool {
+    particle.movement_type == MovementType::Liquid
+}
+
+fn float_bodies(
+    mut query: Query<(
+        Entity,
+        &GlobalTransform,
+        &Buoyancy,
+        &mut Submerged,
+        &mut Velocity,
+        Has<PlayerHealth>,
+    )>,
+    sandbox_query: Query<&Sandbox>,
+    mut damage_events: EventWriter<DamageEvent>,
+    config: Res<RapierConfiguration>,
+    time: Res<Time>,
+) {
+    let Ok(sandbox) = sandbox_query.get_single() else {
+        return;
+    };
+
+    for (entity, transform, buoyancy, mut submerged, mut velocity, is_player) in query.iter_mut() {
+        let center = sandbox.world_to_grid(transform.translation().truncate());
+        let radius = buoyancy.radius / 8.0;
+        let liquids =
+            sandbox.particles_in_circle(center, radius, ParticleFilter::Custom(is_liquid));
+
+        let cells = (PI * radius * radius).max(1.0);
+        submerged.fraction = (liquids.len() as f32 / cells).min(1.0);
+        submerged.density = if liquids.is_empty() {
+            0.0
+        } else {
+            liquids
+                .iter()
+                .map(|(_, particle)| particle.density.0 as f32)
+                .sum::<f32>()
+                / liquids.len() as f32
+        };
+
+        // Molten liquids like lava kill anyone who falls in
+        let molten = liquids
+            .iter()
+            .any(|(_, particle)| particle.collision_type == CollisionType::Fire);
+        if molten && is_player {
+            damage_events.send(DamageEvent {
+                target: entity,
+                source: None,
+                amount: f32::INFINITY,
+                knockback: Vec2::ZERO,
+                kind: DamageKind::Lava,
+            });
+        }
+
+        apply_liquid_forces(
+            &mut velocity,
+            submerged.fraction,
+            submerged.density,
+            buoyancy.density,
+            config.gravity,
+            time.delta_seconds(),
+        );
+    }
+}
+
+/// Rocks are made of cells, so every cell checks whether it is in a liquid
+fn float_rocks(
+    mut parent_query: Query<(Entity, &mut Velocity), (With<ParentObject>, Without<Held>)>,
+    mut loose_query: Query<
+        (&GlobalTransform, &mut Velocity),
+        (With<Rock>, Without<Parent>, Without<ParentObject>),
+    >,
+    children_query: Query<&Children>,
+    rock_query: Query<&GlobalTransform, With<Rock>>,
+    sandbox_query: Query<&Sandbox>,
+    config: Res<RapierConfiguration>,
+    time: Res<Time>,
+) {
+    let Ok(sandbox) = sandbox_query.get_single() else {
+        return;
+    };
+
+    let liquid_at = |position: Vec3| {
+        let IVec2 { x, y } = sandbox.world_to_cell(position.truncate());
+        sandbox
+            .checked_get_i32(x, y)
+            .filter(|particle| is_liquid(particle))
+            .map(|particle| particle.density.0 as f32)
+    };
+
+    for (entity, mut velocity) in parent_query.iter_mut() {
+        let mut cells = 0;
+        let mut densities = vec![];
+        for child in children_query.iter_descendants(entity) {
+            let Ok(transform) = rock_query.get(child) else {
+                continue;
+            };
+            cells += 1;
+            densities.extend(liquid_at(transform.translation()));
+        }
+        if densities.is_empty() {
+            continue;
+        }
+
+        apply_liquid_forces(
+            &mut velocity,
+            densities.len() as f32 / cells as f32,
+            densities.iter().sum::<f32>() / densities.len() as f32,
+            ROCK_DENSITY,
+            config.gravity,
+            time.delta_seconds(),
+        );
+    }
+
+    for (transform, mut velocity) in loose_query.iter_mut() {
+        let Some(density) = liquid_at(transform.translation()) else {
+            continue;
+        };
+
+        apply_liquid_forces(
+            &mut velocity,
+            1.0,
+            density,
+            ROCK_DENSITY,
+            config.gravity,
+            time.delta_seconds(),
+        );
+    }
+}
+
+/// Pushes the body up by the weight of the liquid it displaces and slows it down
+fn apply_liquid_forces(
+    velocity: &mut Velocity,
+    fraction: f32,
+    liquid_density: f32,
+    body_density: f32,
+    gravity: Vec2,
+    delta: f32,
+) {
+    if fraction <= 0.0 {
+        return;
+    }
+
+    velocity.linvel -= gravity * fraction * liquid_density / body_density * delta;
+    velocity.linvel /= 1.0 + LIQUID_DRAG * fraction * liquid_density * delta;
+}
@@ -0,0 +1,135 @@
+use bevy::{prelude::*, render::camera::ScalingMode};
+use bevy_rapier2d::dynamics::Velocity;
+
+use crate::{
+    player::{
+        components::PlayerHealth,
+        grab::{Held, ParentObject},
+    },
+    sandbox::sandbox::Sandbox,
+};
+
+const VIEW_WIDTH: f32 = 1920.0;
+const VIEW_HEIGHT: f32 = 1080.0;
+const ROCK_IN_FLIGHT_VELOCITY: f32 = 100.0;
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup).add_systems(
+            PostUpdate,
+            frame_players.before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+#[derive(Component)]
+pub struct CameraController {
+    /// Extra space in world units kept around the framed entities
+    pub padding: f32,
+    /// How quickly the camera catches up to its target, higher is snappier
+    pub damping: f32,
+    /// Smallest projection scale, i.e. how far the camera can zoom in
+    pub min_zoom: f32,
+    /// Largest projection scale, i.e. how far the camera can zoom out
+    pub max_zoom: f32,
+    pub follow_rocks: bool,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        Self {
+            padding: 200.0,
+            damping: 4.0,
+            min_zoom: 0.5,
+            max_zoom: 2.0,
+            follow_rocks: true,
+        }
+    }
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        Camera2dBundle {
+            projection: OrthographicProjection {
+                scaling_mode: ScalingMode::Fixed {
+                    width: VIEW_WIDTH,
+                    height: VIEW_HEIGHT,
+                },
+                near: -1000.0,
+                ..default()
+            },
+            ..default()
+        },
+        CameraController::default(),
+    ));
+}
+
+fn frame_players(
+    mut camera_query: Query<(
+        &mut Transform,
+        &mut OrthographicProjection,
+        &CameraController,
+    )>,
+    player_query: Query<&GlobalTransform, With<PlayerHealth>>,
+    rock_query: Query<(&GlobalTransform, &Velocity), (With<ParentObject>, Without<Held>)>,
+    sandbox_query: Query<&Sandbox>,
+    time: Res<Time>,
+) {
+    let Ok(sandbox) = sandbox_query.get_single() else {
+        return;
+    };
+    // Centers of cells a whole grid apart are as far apart as the edges of the grid
+    let half_bounds = (sandbox.cell_to_world(sandbox.width() as i32, sandbox.height() as i32)
+        - sandbox.cell_to_world(0, 0))
+        / 2.0;
+
+    for (mut transform, mut projection, controller) in camera_query.iter_mut() {
+        let mut points: Vec<Vec2> = player_query
+            .iter()
+            .map(|transform| transform.translation().truncate())
+            .collect();
+        if controller.follow_rocks && !points.is_empty() {
+            points.extend(
+                rock_query
+                    .iter()
+                    .filter(|(_, velocity)| velocity.linvel.length() >= ROCK_IN_FLIGHT_VELOCITY)
+                    .map(|(transform, _)| transform.translation().truncate()),
+            );
+        }
+
+        // Frame the whole sandbox while nobody is playing
+        let (low, high) = if points.is_empty() {
+            (-half_bounds, half_bounds)
+        } else {
+            let low = points.iter().fold(Vec2::MAX, |low, point| low.min(*point));
+            let high = points
+                .iter()
+                .fold(Vec2::MIN, |high, point| high.max(*point));
+            (
+                low - Vec2::splat(controller.padding),
+                high + Vec2::splat(controller.padding),
+            )
+        };
+
+        let size = high - low;
+        let fit_bounds = (half_bounds.x * 2.0 / VIEW_WIDTH).min(half_bounds.y * 2.0 / VIEW_HEIGHT);
+        let target_scale = (size.x / VIEW_WIDTH)
+            .max(size.y / VIEW_HEIGHT)
+            .clamp(controller.min_zoom, controller.max_zoom)
+            .min(fit_bounds);
+
+        let blend = 1.0 - (-controller.damping * time.delta_seconds()).exp();
+        projection.scale += (target_scale - projection.scale) * blend;
+
+        // Keep the view inside the sandbox
+        let half_view = Vec2::new(VIEW_WIDTH, VIEW_HEIGHT) * projection.scale / 2.0;
+        let max_center = (half_bounds - half_view).max(Vec2::ZERO);
+        let target_center = ((low + high) / 2.0).clamp(-max_center, max_center);
+
+        let center = transform.translation.truncate();
+        let center = (center + (target_center - center) * blend).clamp(-max_center, max_center);
+        transform.translation = center.extend(transform.translation.z);
+    }
+}
@@ -3,10 +3,12 @@ use bevy_rapier2d::prelude::*;
 
 use crate::{
     player::{
-        components::PlayerHealth,
-        grab::{ActualVelocity, Owner, ParentObject, PutBackIntoSandbox, Rock},
+        components::{DamagePercent, PlayerHealth},
+        grab::{ActualVelocity, Owner, ParentObject, PutBackIntoSandbox, Rock, RockParticle},
     },
+    rules::{CombatMode, MatchRules},
     sandbox::{collider::Ground, sandbox::Sandbox},
+    schedule::GameplaySet,
 };
 
 const OVERPOWERDIFFERENCE: f32 = 20000.0;
@@ -16,94 +18,248 @@ const BREAKGROUNDVELOCITYTHRESHOLD: f32 = 10.0;
 const BREAKRADIUS: i32 = 1;
 const VELOCITYVSEXTERNALDIFFERENCE: f32 = 200.0;
 const DESPAWNFALLENY: f32 = -1000.0;
+const ROCKKNOCKBACK: f32 = 5000.0;
+/// Seconds a player can't be hurt after spawning, so they aren't KO'd again right away
+pub const SPAWNINVULNERABILITY: f32 = 2.0;
+/// Damage at the center of an explosion for every cell of its radius
+const EXPLOSIONDAMAGE: f32 = 1000.0;
+const EXPLOSIONKNOCKBACK: f32 = 2000.0;
+/// Percent added for every point of damage in `CombatMode::Percentage`
+const PERCENTPERDAMAGE: f32 = 0.005;
+const MAXPERCENT: f32 = 999.0;
+/// Knockback multiplier at 0%, grows by `PERCENTKNOCKBACKGROWTH` for every 100%
+const PERCENTKNOCKBACKBASE: f32 = 0.5;
+const PERCENTKNOCKBACKGROWTH: f32 = 1.5;
+/// How far players can leave the sandbox before they are KO'd
+const BLASTZONEMARGIN: f32 = 400.0;
 
 pub struct DamagePlugin;
 
 impl Plugin for DamagePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (
-                damage_player,
-                despawn_player,
-                damage_rock,
-                break_from_ground,
-                despawn_fallen_rocks,
-            ),
-        );
+        app.add_event::<DamageEvent>()
+            .add_event::<KnockedOut>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    damage_player,
+                    damage_from_explosions,
+                    apply_damage,
+                    knock_out_of_blast_zones,
+                    despawn_player,
+                    log_damage,
+                    tick_invulnerability,
+                    damage_rock,
+                    break_from_ground,
+                    despawn_fallen_rocks,
+                )
+                    .chain()
+                    .in_set(GameplaySet::Damage),
+            );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DamageKind {
+    Rock,
+    Explosion,
+    Burning,
+    Corrosion,
+    Lava,
+}
+
+/// Damage to a player. Every damage source sends one and `apply_damage` applies them, so other
+/// systems can read them too. They are sent before invulnerability is checked.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct DamageEvent {
+    pub target: Entity,
+    /// Whoever caused the damage, like the player that threw a rock
+    pub source: Option<Entity>,
+    pub amount: f32,
+    /// Impulse applied to the target
+    pub knockback: Vec2,
+    pub kind: DamageKind,
+}
+
+/// A player was KO'd and despawned
+#[derive(Event, Clone, Copy, Debug)]
+pub struct KnockedOut(pub Entity);
+
+/// Players ignore all damage while this has time remaining
+#[derive(Component, Default, Clone)]
+pub struct Invulnerable {
+    pub remaining: f32,
+}
+
+impl Invulnerable {
+    pub fn is_active(&self) -> bool {
+        self.remaining > 0.0
     }
 }
 
+/// Damage of a rock hitting a player
+fn impact_damage(speed: f32, mass: f32) -> f32 {
+    (speed * mass).sqrt()
+}
+
 fn damage_player(
-    mut player_query: Query<(&Transform, &mut ExternalImpulse, &mut PlayerHealth)>,
+    player_query: Query<&Transform, With<PlayerHealth>>,
     collider_query: Query<(&GlobalTransform, &Parent), With<Rock>>,
     rock_query: Query<(&Owner, &Velocity, &ReadMassProperties), With<ParentObject>>,
     mut events: EventReader<CollisionEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
 ) {
     for event in events.read() {
         let CollisionEvent::Started(e1, e2, _) = event else {
             continue;
         };
 
-        if let Ok((transform, mut external, mut health)) = player_query.get_mut(*e2) {
-            if let Ok((colldier_transform, parent)) = collider_query.get(*e1) {
-                let Ok((owner, velocity, mass)) = rock_query.get(parent.get()) else {
-                    continue;
-                };
-                if velocity.linvel.length() < VELOCITYTHRESHOLD || owner.0 == *e2 {
-                    continue;
-                }
-
-                health.0 -= (velocity.linvel.length() * mass.mass).sqrt();
+        for (player, collider) in [(*e1, *e2), (*e2, *e1)] {
+            let Ok(transform) = player_query.get(player) else {
+                continue;
+            };
+            let Ok((collider_transform, parent)) = collider_query.get(collider) else {
+                continue;
+            };
+            let Ok((owner, velocity, mass)) = rock_query.get(parent.get()) else {
+                continue;
+            };
+            let speed = velocity.linvel.length();
+            if speed < VELOCITYTHRESHOLD || owner.0 == player {
+                continue;
+            }
 
-                let direction = (colldier_transform.translation() - transform.translation)
-                    .truncate()
-                    .normalize_or_zero();
-                external.impulse =
-                    -direction * (velocity.linvel.length() * mass.mass).sqrt() * 5000.0;
+            let amount = impact_damage(speed, mass.mass);
+            let direction = (collider_transform.translation() - transform.translation)
+                .truncate()
+                .normalize_or_zero();
+            damage_events.send(DamageEvent {
+                target: player,
+                source: Some(owner.0),
+                amount,
+                knockback: -direction * amount * ROCKKNOCKBACK,
+                kind: DamageKind::Rock,
+            });
+        }
+    }
+}
 
-                println!("V: {}", velocity.linvel.length());
-                println!(
-                    "Damage {} by {}",
-                    *e2,
-                    (velocity.linvel.length() * mass.mass).sqrt()
-                );
+fn damage_from_explosions(
+    mut sandbox_query: Query<&mut Sandbox>,
+    player_query: Query<(Entity, &Transform), With<PlayerHealth>>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    let Ok(mut sandbox) = sandbox_query.get_single_mut() else {
+        return;
+    };
+
+    for (x, y, radius) in sandbox.take_explosions() {
+        let center = sandbox.cell_to_world(x as i32, y as i32);
+        // Explosions push particles up to twice their radius away
+        let reach = (radius * 2) as f32 * 8.0;
+
+        for (entity, transform) in player_query.iter() {
+            let offset = transform.translation.truncate() - center;
+            if offset.length() > reach {
                 continue;
             }
+
+            let amount = EXPLOSIONDAMAGE * radius as f32 * (1.0 - offset.length() / reach);
+            damage_events.send(DamageEvent {
+                target: entity,
+                source: None,
+                amount,
+                knockback: offset.normalize_or_zero() * amount * EXPLOSIONKNOCKBACK,
+                kind: DamageKind::Explosion,
+            });
         }
-        if let Ok((transform, mut external, mut health)) = player_query.get_mut(*e1) {
-            if let Ok((colldier_transform, parent)) = collider_query.get(*e2) {
-                let Ok((owner, velocity, mass)) = rock_query.get(parent.get()) else {
-                    continue;
-                };
-                if velocity.linvel.length() < VELOCITYTHRESHOLD || owner.0 == *e1 {
-                    continue;
-                }
+    }
+}
 
-                health.0 -= (velocity.linvel.length() * mass.mass).sqrt();
+fn apply_damage(
+    mut query: Query<(
+        &mut PlayerHealth,
+        &mut DamagePercent,
+        &mut ExternalImpulse,
+        &Invulnerable,
+    )>,
+    mut events: EventReader<DamageEvent>,
+    rules: Res<MatchRules>,
+) {
+    for event in events.read() {
+        let Ok((mut health, mut percent, mut external, invulnerable)) = query.get_mut(event.target)
+        else {
+            continue;
+        };
+        if invulnerable.is_active() {
+            continue;
+        }
 
-                let direction = (colldier_transform.translation() - transform.translation)
-                    .truncate()
-                    .normalize_or_zero();
-                external.impulse =
-                    -direction * (velocity.linvel.length() * mass.mass).sqrt() * 5000.0;
+        // Hazards dealing infinite damage KO in every mode
+        if !event.amount.is_finite() {
+            health.0 = 0.0;
+            continue;
+        }
 
-                println!(
-                    "Damage {} by {}",
-                    *e1,
-                    (velocity.linvel.length() * mass.mass).sqrt()
-                );
-                continue;
+        match rules.combat {
+            CombatMode::Health => {
+                health.0 -= event.amount;
+                external.impulse = event.knockback;
+            }
+            CombatMode::Percentage => {
+                percent.0 = (percent.0 + event.amount * PERCENTPERDAMAGE).min(MAXPERCENT);
+                external.impulse = event.knockback
+                    * (PERCENTKNOCKBACKBASE + percent.0 / 100.0 * PERCENTKNOCKBACKGROWTH);
             }
         }
     }
 }
 
-fn despawn_player(mut commands: Commands, query: Query<(Entity, &PlayerHealth)>) {
+/// Players that are launched or fall too far out of the sandbox are KO'd in every mode
+fn knock_out_of_blast_zones(
+    mut query: Query<(&Transform, &mut PlayerHealth)>,
+    sandbox_query: Query<&Sandbox>,
+) {
+    let Ok(sandbox) = sandbox_query.get_single() else {
+        return;
+    };
+
+    let half_size = Vec2::new(sandbox.width() as f32, sandbox.height() as f32) * 8.0 / 2.0;
+    let bounds = Rect::from_center_half_size(Vec2::ZERO, half_size + BLASTZONEMARGIN);
+    for (transform, mut health) in query.iter_mut() {
+        if !bounds.contains(transform.translation.truncate()) {
+            health.0 = 0.0;
+        }
+    }
+}
+
+fn log_damage(mut events: EventReader<DamageEvent>) {
+    for event in events.read() {
+        debug!(
+            "{:?} damage {} to {} by {:?}",
+            event.kind, event.amount, event.target, event.source
+        );
+    }
+}
+
+fn tick_invulnerability(mut query: Query<&mut Invulnerable>, time: Res<Time>) {
+    for mut invulnerable in query.iter_mut() {
+        if invulnerable.is_active() {
+            invulnerable.remaining -= time.delta_seconds();
+        }
+    }
+}
+
+fn despawn_player(
+    mut commands: Commands,
+    query: Query<(Entity, &PlayerHealth)>,
+    mut events: EventWriter<KnockedOut>,
+) {
     for (entity, health) in query.iter() {
         if health.0 <= 0.0 {
             warn!("Player {} has died", entity);
             commands.entity(entity).despawn();
+            events.send(KnockedOut(entity));
         }
     }
 }
@@ -124,7 +280,7 @@ fn damage_rock(
     collider_query: Query<&Parent, With<Rock>>,
     rock_query: Query<(&Velocity, &ReadMassProperties), With<ParentObject>>,
     children_query: Query<&Children>,
-    transform_query: Query<&GlobalTransform>,
+    rock_particle_query: Query<(&GlobalTransform, &RockParticle)>,
     mut events: EventReader<CollisionEvent>,
 ) {
     let mut broke_both: HashSet<Entity> = HashSet::new();
@@ -162,18 +318,18 @@ fn damage_rock(
 
         if difference.is_sign_positive() && difference.abs() >= OVERPOWERDIFFERENCE {
             for child in children_query.iter_descendants(e2) {
-                let Ok(translation) = transform_query.get(child).map(|x| x.translation()) else {
+                let Ok((transform, rock)) = rock_particle_query.get(child) else {
                     continue;
                 };
                 commands.spawn((
                     Name::new("Rock"),
                     SpriteBundle {
                         sprite: Sprite {
-                            color: Color::srgb_u8(89, 39, 39),
+                            color: rock.color(),
                             custom_size: Some(Vec2::new(8.0, 8.0)),
                             ..default()
                         },
-                        transform: Transform::from_translation(translation),
+                        transform: Transform::from_translation(transform.translation()),
                         ..default()
                     },
                     Collider::cuboid(3.0, 3.0),
@@ -182,6 +338,7 @@ fn damage_rock(
                     *v1,
                     CollisionGroups::new(Group::GROUP_1, Group::GROUP_1),
                     PutBackIntoSandbox,
+                    *rock,
                     ActiveEvents::COLLISION_EVENTS,
                     Rock,
                 ));
@@ -192,18 +349,18 @@ fn damage_rock(
         }
         if difference.is_sign_negative() && difference.abs() >= OVERPOWERDIFFERENCE {
             for child in children_query.iter_descendants(e1) {
-                let Ok(translation) = transform_query.get(child).map(|x| x.translation()) else {
+                let Ok((transform, rock)) = rock_particle_query.get(child) else {
                     continue;
                 };
                 commands.spawn((
                     Name::new("Rock"),
                     SpriteBundle {
                         sprite: Sprite {
-                            color: Color::srgb_u8(89, 39, 39),
+                            color: rock.color(),
                             custom_size: Some(Vec2::new(8.0, 8.0)),
                             ..default()
                         },
-                        transform: Transform::from_translation(translation),
+                        transform: Transform::from_translation(transform.translation()),
                         ..default()
                     },
                     Collider::cuboid(3.0, 3.0),
@@ -211,6 +368,7 @@ fn damage_rock(
                     RigidBody::Dynamic,
                     *v1,
                     PutBackIntoSandbox,
+                    *rock,
                     CollisionGroups::new(Group::GROUP_1, Group::GROUP_1),
                     ActiveEvents::COLLISION_EVENTS,
                     Rock,
@@ -224,18 +382,18 @@ fn damage_rock(
         if !broke_both.contains(&e2) {
             commands.entity(e2).despawn_recursive();
             for child in children_query.iter_descendants(e2) {
-                let Ok(translation) = transform_query.get(child).map(|x| x.translation()) else {
+                let Ok((transform, rock)) = rock_particle_query.get(child) else {
                     continue;
                 };
                 commands.spawn((
                     Name::new("Rock"),
                     SpriteBundle {
                         sprite: Sprite {
-                            color: Color::srgb_u8(89, 39, 39),
+                            color: rock.color(),
                             custom_size: Some(Vec2::new(8.0, 8.0)),
                             ..default()
                         },
-                        transform: Transform::from_translation(translation),
+                        transform: Transform::from_translation(transform.translation()),
                         ..default()
                     },
                     Collider::cuboid(3.0, 3.0),
@@ -244,6 +402,7 @@ fn damage_rock(
                     *v2,
                     CollisionGroups::new(Group::GROUP_1, Group::GROUP_1),
                     PutBackIntoSandbox,
+                    *rock,
                     ActiveEvents::COLLISION_EVENTS,
                     Rock,
                 ));
@@ -254,18 +413,18 @@ fn damage_rock(
         if !broke_both.contains(&e1) {
             commands.entity(e1).despawn_recursive();
             for child in children_query.iter_descendants(e1) {
-                let Ok(translation) = transform_query.get(child).map(|x| x.translation()) else {
+                let Ok((transform, rock)) = rock_particle_query.get(child) else {
                     continue;
                 };
                 commands.spawn((
                     Name::new("Rock"),
                     SpriteBundle {
                         sprite: Sprite {
-                            color: Color::srgb_u8(89, 39, 39),
+                            color: rock.color(),
                             custom_size: Some(Vec2::new(8.0, 8.0)),
                             ..default()
                         },
-                        transform: Transform::from_translation(translation),
+                        transform: Transform::from_translation(transform.translation()),
                         ..default()
                     },
                     Collider::cuboid(3.0, 3.0),
@@ -274,6 +433,7 @@ fn damage_rock(
                     *v1,
                     CollisionGroups::new(Group::GROUP_1, Group::GROUP_1),
                     PutBackIntoSandbox,
+                    *rock,
                     ActiveEvents::COLLISION_EVENTS,
                     Rock,
                 ));
@@ -288,7 +448,7 @@ fn damage_rock(
 fn break_from_ground(
     mut commands: Commands,
     mut sandbox_query: Query<&mut Sandbox>,
-    collider_query: Query<(Entity, &GlobalTransform, &Parent), With<Rock>>,
+    collider_query: Query<(Entity, &GlobalTransform, &Parent, &RockParticle), With<Rock>>,
     parent_query: Query<(&ActualVelocity, &Velocity), With<ParentObject>>,
     ground_query: Query<(), With<Ground>>,
     mut events: EventReader<CollisionEvent>,
@@ -300,7 +460,7 @@ fn break_from_ground(
             continue;
         };
 
-        if let Ok((entity, transform, parent)) = collider_query.get(*e1) {
+        if let Ok((entity, transform, parent, rock)) = collider_query.get(*e1) {
             if !ground_query.contains(*e2) {
                 continue;
             }
@@ -337,7 +497,7 @@ fn break_from_ground(
                 Name::new("Rock"),
                 SpriteBundle {
                     sprite: Sprite {
-                        color: Color::srgb_u8(89, 39, 39),
+                        color: rock.color(),
                         custom_size: Some(Vec2::new(8.0, 8.0)),
                         ..default()
                     },
@@ -349,19 +509,23 @@ fn break_from_ground(
                 RigidBody::Dynamic,
                 *v,
                 PutBackIntoSandbox,
+                *rock,
                 ActiveEvents::COLLISION_EVENTS,
                 CollisionGroups::new(Group::GROUP_1, Group::GROUP_1),
                 Rock,
             ));
 
             if v.linvel.length() >= BREAKGROUNDVELOCITYTHRESHOLD {
-                let (grid_x, grid_y) = (
-                    (transform.translation().x / 8.0).floor() as i32 + sandbox.width() as i32 / 2,
-                    (transform.translation().y / 8.0).floor() as i32 + sandbox.height() as i32 / 2,
-                );
+                let IVec2 {
+                    x: grid_x,
+                    y: grid_y,
+                } = sandbox.world_to_cell(transform.translation().truncate());
                 for x in -BREAKRADIUS..=BREAKRADIUS {
                     for y in -BREAKRADIUS..=BREAKRADIUS {
-                        if !sandbox.out_of_bounds_i32(grid_x + x, grid_y + y) {
+                        let Some(particle) = sandbox.checked_get_i32(grid_x + x, grid_y + y) else {
+                            continue;
+                        };
+                        if !particle.unbreakable {
                             sandbox.set((grid_x + x) as usize, (grid_y + y) as usize, None);
                         }
                     }
@@ -370,7 +534,7 @@ fn break_from_ground(
 
             commands.entity(entity).despawn();
         }
-        if let Ok((entity, transform, parent)) = collider_query.get(*e2) {
+        if let Ok((entity, transform, parent, rock)) = collider_query.get(*e2) {
             if !ground_query.contains(*e1) {
                 continue;
             }
@@ -394,7 +558,7 @@ fn break_from_ground(
                 Name::new("Rock"),
                 SpriteBundle {
                     sprite: Sprite {
-                        color: Color::srgb_u8(89, 39, 39),
+                        color: rock.color(),
                         custom_size: Some(Vec2::new(8.0, 8.0)),
                         ..default()
                     },
@@ -407,18 +571,22 @@ fn break_from_ground(
                 *velocity,
                 CollisionGroups::new(Group::GROUP_1, Group::GROUP_1),
                 PutBackIntoSandbox,
+                *rock,
                 ActiveEvents::COLLISION_EVENTS,
                 Rock,
             ));
 
             if velocity.linvel.length() >= BREAKGROUNDVELOCITYTHRESHOLD {
-                let (grid_x, grid_y) = (
-                    (transform.translation().x / 8.0).floor() as i32 + sandbox.width() as i32 / 2,
-                    (transform.translation().y / 8.0).floor() as i32 + sandbox.height() as i32 / 2,
-                );
+                let IVec2 {
+                    x: grid_x,
+                    y: grid_y,
+                } = sandbox.world_to_cell(transform.translation().truncate());
                 for x in -BREAKRADIUS..=BREAKRADIUS {
                     for y in -BREAKRADIUS..=BREAKRADIUS {
-                        if !sandbox.out_of_bounds_i32(grid_x + x, grid_y + y) {
+                        let Some(particle) = sandbox.checked_get_i32(grid_x + x, grid_y + y) else {
+                            continue;
+                        };
+                        if !particle.unbreakable {
                             sandbox.set((grid_x + x) as usize, (grid_y + y) as usize, None);
                         }
                     }
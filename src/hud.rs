@@ -0,0 +1,218 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::ReadMassProperties;
+
+use crate::{
+    match_flow::{MatchScore, PlayerRecord},
+    player::{
+        bending::Bender,
+        components::{DamagePercent, ExtraJumps, HeldObject, PlayerHealth, PlayerSlot},
+        parry::Parry,
+    },
+    rules::{CombatMode, MatchRules},
+    state::GameState,
+};
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Playing), spawn_hud)
+            .add_systems(
+                Update,
+                (sync_panels, update_panels)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+/// Row along the top of the screen holding the panels
+#[derive(Component)]
+struct Hud;
+
+/// Shows the state of the player in one slot
+#[derive(Component)]
+struct HudPanel {
+    slot: usize,
+    text: Entity,
+    parry_fill: Entity,
+}
+
+fn spawn_hud(mut commands: Commands) {
+    commands.spawn((
+        Name::new("HUD"),
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                top: Val::Px(8.0),
+                justify_content: JustifyContent::SpaceEvenly,
+                ..default()
+            },
+            ..default()
+        },
+        Hud,
+        StateScoped(GameState::Playing),
+    ));
+}
+
+/// Adds a panel for every player who joined and removes panels of a previous match
+fn sync_panels(
+    mut commands: Commands,
+    hud_query: Query<Entity, With<Hud>>,
+    panel_query: Query<(Entity, &HudPanel)>,
+    score: Res<MatchScore>,
+) {
+    let Ok(hud) = hud_query.get_single() else {
+        return;
+    };
+
+    let mut shown = vec![false; score.players.len()];
+    for (entity, panel) in panel_query.iter() {
+        match shown.get_mut(panel.slot) {
+            Some(is_shown) => *is_shown = true,
+            None => commands.entity(entity).despawn_recursive(),
+        }
+    }
+
+    for slot in (0..score.players.len()).filter(|slot| !shown[*slot]) {
+        let panel = spawn_panel(&mut commands, slot);
+        commands.entity(hud).add_child(panel);
+    }
+}
+
+fn spawn_panel(commands: &mut Commands, slot: usize) -> Entity {
+    let color = PlayerSlot(slot).color();
+
+    let text = commands
+        .spawn(TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 18.0,
+                ..default()
+            },
+        ))
+        .id();
+    let parry_fill = commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            background_color: Color::WHITE.into(),
+            ..default()
+        })
+        .id();
+    let parry_bar = commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Px(6.0),
+                margin: UiRect::top(Val::Px(4.0)),
+                ..default()
+            },
+            background_color: Color::srgba(0.0, 0.0, 0.0, 0.5).into(),
+            ..default()
+        })
+        .add_child(parry_fill)
+        .id();
+
+    commands
+        .spawn((
+            Name::new("HUD Panel"),
+            NodeBundle {
+                style: Style {
+                    width: Val::Px(160.0),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(6.0)),
+                    border: UiRect::all(Val::Px(2.0)),
+                    ..default()
+                },
+                background_color: color.with_alpha(0.3).into(),
+                border_color: color.into(),
+                ..default()
+            },
+            HudPanel {
+                slot,
+                text,
+                parry_fill,
+            },
+        ))
+        .push_children(&[text, parry_bar])
+        .id()
+}
+
+fn update_panels(
+    panel_query: Query<&HudPanel>,
+    player_query: Query<(
+        &PlayerSlot,
+        &PlayerHealth,
+        &DamagePercent,
+        // Players mirrored from a network server only have their health
+        Option<&Parry>,
+        Option<&ExtraJumps>,
+        Option<&HeldObject>,
+        Option<&Bender>,
+    )>,
+    mass_query: Query<&ReadMassProperties>,
+    mut text_query: Query<&mut Text>,
+    mut style_query: Query<&mut Style>,
+    score: Res<MatchScore>,
+    rules: Res<MatchRules>,
+) {
+    for panel in panel_query.iter() {
+        let Some(record) = score.players.get(panel.slot) else {
+            continue;
+        };
+        let player = player_query.iter().find(|(slot, ..)| slot.0 == panel.slot);
+
+        let mut lines = vec![format!("P{}{}", panel.slot + 1, stocks_label(record))];
+        let mut charge = 0.0;
+        match player {
+            Some((_, health, percent, parry, jumps, held, bender)) => {
+                lines.push(match rules.combat {
+                    CombatMode::Health => format!("Health {:.0}", health.0.max(0.0)),
+                    CombatMode::Percentage => format!("{:.0}%", percent.0),
+                });
+                if let Some(jumps) = jumps {
+                    lines.push(format!("Jumps {}/{}", jumps.current, jumps.max));
+                }
+                if let Some(held) = held {
+                    let mass = held
+                        .0
+                        .and_then(|entity| mass_query.get(entity).ok())
+                        .map_or(0.0, |mass| mass.mass);
+                    lines.push(format!("Holding {mass:.0}"));
+                }
+                if let Some(bender) = bender {
+                    lines.push(if bender.cooldown > 0.0 {
+                        format!("{:?} {:.1}", bender.element, bender.cooldown)
+                    } else {
+                        format!("{:?} ready", bender.element)
+                    });
+                }
+                charge = parry.map_or(0.0, Parry::charge);
+            }
+            None if record.is_eliminated() => lines.push("Out".to_string()),
+            None => match record.respawn_in {
+                Some(respawn_in) => lines.push(format!("Respawning {respawn_in:.1}")),
+                None => lines.push("Press any button".to_string()),
+            },
+        }
+
+        if let Ok(mut text) = text_query.get_mut(panel.text) {
+            text.sections[0].value = lines.join("\n");
+        }
+        if let Ok(mut style) = style_query.get_mut(panel.parry_fill) {
+            style.width = Val::Percent(charge * 100.0);
+        }
+    }
+}
+
+fn stocks_label(record: &PlayerRecord) -> String {
+    match record.stocks {
+        Some(stocks) => format!(" - {stocks} stocks"),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashSet};
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    player::grab::{ActualVelocity, ParentObject, Rock, RockParticle},
+    sandbox::sandbox::{is_solid, Sandbox},
+    schedule::GameplaySet,
+    state::GameState,
+};
+
+/// Cells a flood fill visits before it gives up for the tick. Islands it couldn't finish are
+/// searched again on the next tick with twice the limit.
+const MAX_ISLAND_SIZE: usize = 600;
+
+pub struct IslandPlugin;
+
+impl Plugin for IslandPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PendingIslands::default())
+            .add_systems(FixedUpdate, detach_islands.in_set(GameplaySet::Islands));
+    }
+}
+
+/// Cells of islands that were too large to flood fill within the limit, so it isn't known yet
+/// whether they are anchored
+#[derive(Resource, Default, Clone)]
+pub struct PendingIslands {
+    seeds: Vec<(usize, usize)>,
+    /// Limit of the next search, grows every time an island doesn't fit
+    limit: usize,
+}
+
+struct Island {
+    seed: (usize, usize),
+    cells: Vec<(usize, usize)>,
+    anchored: bool,
+    /// The flood fill stopped at the limit before finding out whether the island is anchored
+    unknown: bool,
+}
+
+/// Looks for solid regions that were cut off from the rest of the terrain by removed particles
+/// and turns them into falling rigid bodies. Of several loose pieces, the largest one stays
+/// in the sandbox so authored floating platforms don't fall when they are dug into.
+fn detach_islands(
+    mut commands: Commands,
+    mut sandbox_query: Query<&mut Sandbox>,
+    mut pending: ResMut<PendingIslands>,
+    state: Res<State<GameState>>,
+) {
+    let Ok(mut sandbox) = sandbox_query.get_single_mut() else {
+        return;
+    };
+
+    let removed = sandbox.take_removed_solids();
+    if *state.get() != GameState::Playing {
+        *pending = PendingIslands::default();
+        return;
+    }
+    if removed.is_empty() && pending.seeds.is_empty() {
+        return;
+    }
+
+    let limit = if pending.seeds.is_empty() {
+        MAX_ISLAND_SIZE
+    } else {
+        pending.limit
+    };
+    let mut seeds = std::mem::take(&mut pending.seeds);
+    for (x, y) in removed {
+        seeds.extend([
+            (x.overflowing_sub(1).0, y),
+            (x + 1, y),
+            (x, y.overflowing_sub(1).0),
+            (x, y + 1),
+        ]);
+    }
+
+    let mut visited: HashSet<(usize, usize)> = HashSet::new();
+    let mut islands: Vec<Island> = vec![];
+    for (x, y) in seeds {
+        if visited.contains(&(x, y)) || !is_solid(sandbox.checked_get(x, y)) {
+            continue;
+        }
+
+        islands.push(flood_island(&sandbox, x, y, limit, &mut visited));
+    }
+
+    // Islands that didn't fit are searched again as a whole once the search gets through them
+    if islands.iter().any(|island| island.unknown) {
+        pending.seeds = islands.iter().map(|island| island.seed).collect();
+        pending.limit = (limit * 2).min(sandbox.width() * sandbox.height());
+        return;
+    }
+
+    if islands.iter().all(|island| !island.anchored) {
+        let largest = islands
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, island)| island.cells.len())
+            .map(|(i, _)| i);
+        if let Some(largest) = largest {
+            islands.swap_remove(largest);
+        }
+    }
+
+    for island in islands.iter().filter(|island| !island.anchored) {
+        spawn_island(&mut commands, &mut sandbox, island);
+    }
+
+    // Cutting out the islands doesn't split anything else
+    sandbox.take_removed_solids();
+}
+
+/// Stops as soon as the island is found to be anchored, the rest of it is never split off
+fn flood_island(
+    sandbox: &Sandbox,
+    x: usize,
+    y: usize,
+    limit: usize,
+    visited: &mut HashSet<(usize, usize)>,
+) -> Island {
+    let mut island = Island {
+        seed: (x, y),
+        cells: vec![],
+        anchored: false,
+        unknown: false,
+    };
+    let mut queue = VecDeque::from([(x, y)]);
+    visited.insert((x, y));
+
+    while let Some((x, y)) = queue.pop_front() {
+        island.cells.push((x, y));
+
+        // Bedrock never moves, so neither does anything resting on it
+        if sandbox
+            .get(x, y)
+            .is_some_and(|particle| particle.unbreakable)
+        {
+            island.anchored = true;
+            return island;
+        }
+
+        if island.cells.len() > limit {
+            island.unknown = true;
+            return island;
+        }
+
+        for (neighbor_x, neighbor_y) in [
+            (x.overflowing_sub(1).0, y),
+            (x + 1, y),
+            (x, y.overflowing_sub(1).0),
+            (x, y + 1),
+        ] {
+            // Touching the edge of the world or an unloaded chunk holds the island in place
+            if sandbox.out_of_bounds_usize(neighbor_x, neighbor_y) {
+                island.anchored = true;
+                return island;
+            }
+            if visited.contains(&(neighbor_x, neighbor_y))
+                || !is_solid(sandbox.get(neighbor_x, neighbor_y))
+            {
+                continue;
+            }
+
+            visited.insert((neighbor_x, neighbor_y));
+            queue.push_back((neighbor_x, neighbor_y));
+        }
+    }
+
+    island
+}
+
+fn spawn_island(commands: &mut Commands, sandbox: &mut Sandbox, island: &Island) {
+    let center = island
+        .cells
+        .iter()
+        .map(|(x, y)| sandbox.cell_to_world(*x as i32, *y as i32))
+        .sum::<Vec2>()
+        / island.cells.len() as f32;
+
+    let mut rocks = vec![];
+    for (x, y) in island.cells.iter().copied() {
+        let Some(particle) = sandbox.get(x, y).copied() else {
+            continue;
+        };
+        sandbox.set(x, y, None);
+
+        let offset = sandbox.cell_to_world(x as i32, y as i32) - center;
+        let rock = RockParticle(particle);
+        rocks.push(
+            commands
+                .spawn((
+                    Name::new("Rock"),
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: rock.color(),
+                            custom_size: Some(Vec2::new(8.0, 8.0)),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(offset.extend(0.1)),
+                        ..default()
+                    },
+                    Collider::cuboid(3.0, 3.0),
+                    Friction::coefficient(0.5),
+                    ActiveEvents::COLLISION_EVENTS,
+                    CollisionGroups::new(Group::all(), Group::all().difference(Group::GROUP_1)),
+                    Velocity::default(),
+                    ActualVelocity::default(),
+                    Rock,
+                    rock,
+                ))
+                .id(),
+        );
+    }
+
+    commands
+        .spawn((
+            Name::new("Island"),
+            SpatialBundle::from_transform(Transform::from_translation(center.extend(0.0))),
+            RigidBody::Dynamic,
+            ReadMassProperties::default(),
+            Velocity::default(),
+            ActualVelocity::default(),
+            Ccd::enabled(),
+            ParentObject,
+        ))
+        .push_children(&rocks);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sandbox::{
+        particle_types::{get_particle, ParticleTypes},
+        rng::SimulationRng,
+    };
+
+    fn sandbox_with_block(low: (usize, usize), high: (usize, usize)) -> Sandbox {
+        let mut sandbox = Sandbox::new(6, 6, 8, 8);
+        let mut rng = SimulationRng::seeded(0);
+        for x in low.0..high.0 {
+            for y in low.1..high.1 {
+                sandbox.set(x, y, Some(get_particle(ParticleTypes::Stone, &mut rng)));
+            }
+        }
+        sandbox
+    }
+
+    #[test]
+    fn large_island_is_unknown_until_limit_fits() {
+        let sandbox = sandbox_with_block((5, 5), (35, 35));
+
+        let island = flood_island(&sandbox, 5, 5, MAX_ISLAND_SIZE, &mut HashSet::new());
+        assert!(island.unknown);
+        assert!(!island.anchored);
+
+        let island = flood_island(&sandbox, 5, 5, MAX_ISLAND_SIZE * 2, &mut HashSet::new());
+        assert!(!island.unknown);
+        assert!(!island.anchored);
+        assert_eq!(island.cells.len(), 900);
+    }
+
+    #[test]
+    fn island_touching_edge_is_anchored() {
+        let sandbox = sandbox_with_block((0, 0), (30, 30));
+
+        let island = flood_island(&sandbox, 20, 20, MAX_ISLAND_SIZE, &mut HashSet::new());
+        assert!(island.anchored);
+        assert!(!island.unknown);
+    }
+}
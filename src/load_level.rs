@@ -1,8 +1,14 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, render::render_resource::Extent3d};
 
-use crate::sandbox::{
-    particle_types::{get_particle, ParticleTypes},
-    sandbox::Sandbox,
+use crate::{
+    sandbox::{
+        editor::EditedLayout,
+        history::SandboxHistory,
+        particle_types::{get_particle, ParticleTypes},
+        rng::SimulationRng,
+        sandbox::Sandbox,
+    },
+    spawn_points::{SpawnPoints, SPAWN_POINT_COLOR},
 };
 
 pub struct LoadLevelPlugin;
@@ -32,15 +38,36 @@ fn load_level(mut commands: Commands, asset_server: Res<AssetServer>) {
 }
 
 fn draw_level(
-    mut query: Query<&mut Sandbox>,
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Sandbox, &Handle<Image>)>,
     dirt_image_handle: Res<DirtImageHandle>,
-    images: Res<Assets<Image>>,
+    mut images: ResMut<Assets<Image>>,
+    mut layout: ResMut<EditedLayout>,
+    mut history: ResMut<SandboxHistory>,
+    mut rng: ResMut<SimulationRng>,
 ) {
-    let mut sandbox = query.single_mut();
+    let (entity, mut sandbox, sandbox_image) = query.single_mut();
 
-    let image = images.get(&dirt_image_handle.0).unwrap();
+    let image = images.get(&dirt_image_handle.0).unwrap().clone();
     let size = image.size().as_ivec2();
 
+    // Grow the sandbox when the level doesn't fit into it
+    let x_chunks = (size.x as usize)
+        .div_ceil(sandbox.chunk_width())
+        .max(sandbox.x_chunks());
+    let y_chunks = (size.y as usize)
+        .div_ceil(sandbox.chunk_height())
+        .max(sandbox.y_chunks());
+    if x_chunks != sandbox.x_chunks() || y_chunks != sandbox.y_chunks() {
+        sandbox.resize(x_chunks, y_chunks);
+        images.get_mut(sandbox_image).unwrap().resize(Extent3d {
+            width: sandbox.width() as u32,
+            height: sandbox.height() as u32,
+            depth_or_array_layers: 1,
+        });
+    }
+
+    let mut spawn_points = vec![];
     for x in 0..size.x {
         for y in 0..size.y {
             let bytes_per_pixel = 4;
@@ -54,17 +81,23 @@ fn draw_level(
             let r = image.data[index];
             let g = image.data[index + 1];
             let b = image.data[index + 2];
+            let sandbox_y = (y - size.y + 1).unsigned_abs() as usize;
+
+            if (r, g, b, alpha) == SPAWN_POINT_COLOR {
+                spawn_points.push(IVec2::new(x, sandbox_y as i32));
+                continue;
+            }
 
-            let mut particle = get_particle(ParticleTypes::Dirt);
+            let mut particle = get_particle(ParticleTypes::Dirt, &mut rng);
             particle.color = (r, g, b, alpha);
 
-            sandbox.set(
-                x as usize,
-                (y - size.y + 1).unsigned_abs() as usize,
-                Some(particle),
-            );
+            sandbox.set(x as usize, sandbox_y, Some(particle));
         }
     }
+    commands.entity(entity).insert(SpawnPoints(spawn_points));
+
+    layout.save(&sandbox);
+    history.clear();
 }
 
 fn to_index(x: i32, y: i32, width: i32) -> usize {
@@ -1,4 +1,4 @@
-use bevy::{prelude::*, render::camera::ScalingMode, window::PresentMode};
+use bevy::{prelude::*, window::PresentMode};
 use bevy_rapier2d::prelude::*;
 
 #[cfg(feature = "dev")]
@@ -8,19 +8,45 @@ use bevy::window::WindowMode;
 #[cfg(feature = "dev")]
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
+mod buoyancy;
+mod camera;
 mod damage;
+mod hud;
+mod islands;
 mod load_level;
+mod match_flow;
+mod net;
 mod player;
+mod replay;
+mod rollback;
+mod rules;
 mod sandbox;
+mod schedule;
+mod spawn_points;
+mod state;
 mod vector;
 use bevy_tnua::controller::TnuaControllerPlugin;
 use bevy_tnua_rapier2d::TnuaRapier2dPlugin;
+use buoyancy::BuoyancyPlugin;
+use camera::CameraPlugin;
 use damage::DamagePlugin;
+use hud::HudPlugin;
+use islands::IslandPlugin;
 use load_level::LoadLevelPlugin;
+use match_flow::MatchPlugin;
+use net::NetPlugin;
 use player::PlayerPlugin;
+use replay::ReplayPlugin;
+use rules::RulesPlugin;
 use sandbox::SandboxPlugin;
+use schedule::SchedulePlugin;
+use state::GameStatePlugin;
+
+fn main() -> AppExit {
+    if std::env::args().any(|arg| arg == "--verify-rollback") {
+        return rollback::verify_rollback();
+    }
 
-fn main() {
     let mut app = App::new();
     app.add_plugins(
         DefaultPlugins
@@ -38,36 +64,31 @@ fn main() {
             .set(ImagePlugin::default_nearest()),
     )
     .insert_resource(Msaa::Off)
-    .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
+    .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0).in_fixed_schedule())
     //.add_plugins(RapierDebugRenderPlugin::default())
     .add_plugins((
-        TnuaControllerPlugin::default(),
-        TnuaRapier2dPlugin::default(),
+        TnuaControllerPlugin::new(FixedUpdate),
+        TnuaRapier2dPlugin::new(FixedUpdate),
+        SchedulePlugin,
+        GameStatePlugin,
+        RulesPlugin,
+        MatchPlugin,
+        ReplayPlugin,
+        HudPlugin,
+        CameraPlugin,
         LoadLevelPlugin,
         DamagePlugin,
+        IslandPlugin,
+        BuoyancyPlugin,
         SandboxPlugin,
         PlayerPlugin,
     ))
-    .add_systems(Startup, setup);
+    .add_plugins(NetPlugin);
 
     #[cfg(feature = "dev")]
     app.add_plugins(
         WorldInspectorPlugin::default().run_if(input_toggle_active(true, KeyCode::Escape)),
     );
 
-    app.run();
-}
-
-fn setup(mut commands: Commands) {
-    commands.spawn(Camera2dBundle {
-        projection: OrthographicProjection {
-            scaling_mode: ScalingMode::Fixed {
-                width: 1920.0,
-                height: 1080.0,
-            },
-            near: -1000.0,
-            ..default()
-        },
-        ..default()
-    });
+    app.run()
 }
@@ -0,0 +1,339 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    damage::KnockedOut,
+    net::is_client,
+    player::{
+        bending::Element, components::PlayerHealth, devices::InputDevice,
+        spawn_player::spawn_device_player, UsedDevices,
+    },
+    rules::MatchRules,
+    sandbox::sandbox::Sandbox,
+    schedule::GameplaySet,
+    spawn_points::SpawnPoints,
+    state::{GameState, ResetArena},
+};
+
+/// Seconds before a KO'd player with stocks left comes back
+const RESPAWN_DELAY: f32 = 2.0;
+
+pub struct MatchPlugin;
+
+impl Plugin for MatchPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_sub_state::<MatchPhase>()
+            .enable_state_scoped_entities::<MatchPhase>()
+            .insert_resource(MatchScore::default())
+            .add_systems(OnEnter(GameState::Playing), start_match)
+            .add_systems(
+                FixedUpdate,
+                (
+                    count_knockouts,
+                    respawn_players,
+                    tick_match_clock,
+                    detect_winner,
+                )
+                    .chain()
+                    .run_if(in_state(MatchPhase::InProgress))
+                    .in_set(GameplaySet::Match),
+            )
+            .add_systems(OnEnter(MatchPhase::Finished), show_outcome)
+            .add_systems(
+                Update,
+                // The server starts the next round of its clients
+                next_round.run_if(in_state(MatchPhase::Finished).and_then(not(is_client))),
+            )
+            .add_systems(OnExit(MatchPhase::Finished), clear_players);
+    }
+}
+
+#[derive(SubStates, Debug, Clone, Copy, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[source(GameState = GameState::Playing)]
+pub enum MatchPhase {
+    #[default]
+    InProgress,
+    /// Shows the outcome until the next round is started
+    Finished,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchOutcome {
+    /// Index of the winner in `MatchScore::players`
+    Winner(usize),
+    Draw,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlayerRecord {
+    pub device: InputDevice,
+    /// Binding profile the player picked, kept when they respawn
+    pub profile: usize,
+    /// Element the player bends, kept when they respawn
+    pub element: Element,
+    /// `None` with unlimited stocks
+    pub stocks: Option<u32>,
+    pub falls: u32,
+    /// Seconds until the player is spawned again
+    pub respawn_in: Option<f32>,
+}
+
+impl PlayerRecord {
+    pub fn is_eliminated(&self) -> bool {
+        self.stocks == Some(0)
+    }
+}
+
+/// Everyone who joined the current match in the order they joined
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+pub struct MatchScore {
+    pub players: Vec<PlayerRecord>,
+    /// Seconds since the round started
+    pub elapsed: f32,
+    pub outcome: Option<MatchOutcome>,
+}
+
+impl MatchScore {
+    /// Players can join once and again after disconnecting, but KO'd players wait for their
+    /// respawn or the next round. Devices sharing keys with a device in the match can't join.
+    pub fn can_join(&self, device: InputDevice) -> bool {
+        let conflicting = self
+            .players
+            .iter()
+            .any(|record| record.device.conflicts_with(&device));
+        !conflicting
+            && self
+                .record(device)
+                .is_none_or(|record| record.respawn_in.is_none() && !record.is_eliminated())
+    }
+
+    /// Returns the slot of the player, which stays the same when they rejoin with the profile
+    /// they had before
+    pub fn join(&mut self, device: InputDevice, profile: usize, rules: &MatchRules) -> usize {
+        if let Some(slot) = self.slot(device) {
+            return slot;
+        }
+
+        self.players.push(PlayerRecord {
+            device,
+            profile,
+            element: Element::default(),
+            stocks: rules.stocks,
+            falls: 0,
+            respawn_in: None,
+        });
+        self.players.len() - 1
+    }
+
+    pub fn slot(&self, device: InputDevice) -> Option<usize> {
+        self.players
+            .iter()
+            .position(|record| record.device == device)
+    }
+
+    pub fn record(&self, device: InputDevice) -> Option<&PlayerRecord> {
+        self.players.iter().find(|record| record.device == device)
+    }
+
+    fn record_mut(&mut self, device: InputDevice) -> Option<&mut PlayerRecord> {
+        self.players
+            .iter_mut()
+            .find(|record| record.device == device)
+    }
+
+    /// Starts a new round with everyone who joined, spawning them right away
+    fn restart(&mut self, rules: &MatchRules) {
+        for record in self.players.iter_mut() {
+            record.stocks = rules.stocks;
+            record.falls = 0;
+            record.respawn_in = Some(0.0);
+        }
+        self.elapsed = 0.0;
+        self.outcome = None;
+    }
+}
+
+fn start_match(mut score: ResMut<MatchScore>) {
+    *score = MatchScore::default();
+}
+
+fn count_knockouts(
+    mut score: ResMut<MatchScore>,
+    mut used_devices: ResMut<UsedDevices>,
+    mut events: EventReader<KnockedOut>,
+) {
+    for event in events.read() {
+        // Frees the device, so the player can be spawned again
+        let Some(device) = used_devices.remove_player(event.0) else {
+            continue;
+        };
+        let Some(record) = score.record_mut(device) else {
+            continue;
+        };
+
+        record.falls += 1;
+        if let Some(stocks) = &mut record.stocks {
+            *stocks = stocks.saturating_sub(1);
+        }
+        if !record.is_eliminated() {
+            record.respawn_in = Some(RESPAWN_DELAY);
+        }
+    }
+}
+
+fn respawn_players(
+    mut commands: Commands,
+    mut score: ResMut<MatchScore>,
+    mut used_devices: ResMut<UsedDevices>,
+    sandbox_query: Query<(&Sandbox, &SpawnPoints)>,
+    player_query: Query<&Transform, With<PlayerHealth>>,
+    time: Res<Time>,
+) {
+    let Ok((sandbox, spawn_points)) = sandbox_query.get_single() else {
+        return;
+    };
+    let mut players: Vec<Vec2> = player_query
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .collect();
+
+    for (slot, record) in score.players.iter_mut().enumerate() {
+        let Some(respawn_in) = &mut record.respawn_in else {
+            continue;
+        };
+
+        *respawn_in -= time.delta_seconds();
+        if *respawn_in <= 0.0 {
+            record.respawn_in = None;
+            let position = spawn_points.pick(sandbox, &players);
+            players.push(position);
+            let entity = spawn_device_player(
+                &mut commands,
+                record.device,
+                record.profile,
+                record.element,
+                slot,
+                position,
+            );
+            used_devices.insert(record.device, entity);
+        }
+    }
+}
+
+fn tick_match_clock(mut score: ResMut<MatchScore>, time: Res<Time>) {
+    score.elapsed += time.delta_seconds();
+}
+
+fn detect_winner(
+    mut score: ResMut<MatchScore>,
+    rules: Res<MatchRules>,
+    mut next_phase: ResMut<NextState<MatchPhase>>,
+) {
+    let standing: Vec<usize> = (0..score.players.len())
+        .filter(|i| !score.players[*i].is_eliminated())
+        .collect();
+
+    let outcome = if score.players.len() >= 2 && standing.len() <= 1 {
+        // Last one standing
+        Some(
+            standing
+                .first()
+                .map_or(MatchOutcome::Draw, |i| MatchOutcome::Winner(*i)),
+        )
+    } else if rules
+        .time_limit
+        .is_some_and(|time_limit| score.elapsed >= time_limit)
+    {
+        // Whoever fell the least, unless that's a tie
+        let least_falls = standing.iter().map(|i| score.players[*i].falls).min();
+        let leaders: Vec<usize> = standing
+            .into_iter()
+            .filter(|i| Some(score.players[*i].falls) == least_falls)
+            .collect();
+        match leaders[..] {
+            [winner] => Some(MatchOutcome::Winner(winner)),
+            _ => Some(MatchOutcome::Draw),
+        }
+    } else {
+        None
+    };
+
+    if outcome.is_some() {
+        score.outcome = outcome;
+        next_phase.set(MatchPhase::Finished);
+    }
+}
+
+fn show_outcome(mut commands: Commands, score: Res<MatchScore>) {
+    let title = match score.outcome {
+        Some(MatchOutcome::Winner(i)) => format!("Player {} wins!", i + 1),
+        _ => "Draw!".to_string(),
+    };
+
+    commands
+        .spawn((
+            Name::new("Match Outcome"),
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.6).into(),
+                ..default()
+            },
+            StateScoped(MatchPhase::Finished),
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                title,
+                TextStyle {
+                    font_size: 96.0,
+                    ..default()
+                },
+            ));
+            parent.spawn(TextBundle::from_section(
+                "Press Start or Enter for the next round",
+                TextStyle {
+                    font_size: 32.0,
+                    ..default()
+                },
+            ));
+        });
+}
+
+/// Resets the arena to the edited layout, clears all rocks and respawns everyone
+fn next_round(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepad_input: Res<ButtonInput<GamepadButton>>,
+    mut score: ResMut<MatchScore>,
+    rules: Res<MatchRules>,
+    mut next_phase: ResMut<NextState<MatchPhase>>,
+    mut events: EventWriter<ResetArena>,
+) {
+    let start_pressed = gamepad_input
+        .get_just_pressed()
+        .any(|button| button.button_type == GamepadButtonType::Start);
+    if !start_pressed && !keyboard_input.just_pressed(KeyCode::Enter) {
+        return;
+    }
+
+    score.restart(&rules);
+    events.send(ResetArena);
+    next_phase.set(MatchPhase::InProgress);
+}
+
+/// The winner is still standing when the round ends
+pub fn clear_players(
+    mut commands: Commands,
+    player_query: Query<Entity, With<PlayerHealth>>,
+    mut used_devices: ResMut<UsedDevices>,
+) {
+    for entity in player_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    used_devices.clear();
+}
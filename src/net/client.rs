@@ -0,0 +1,383 @@
+use std::{io, net::UdpSocket};
+
+use bevy::{prelude::*, utils::HashMap};
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{
+    damage::Invulnerable,
+    match_flow::{MatchPhase, MatchScore},
+    player::{
+        bindings::{ControlProfile, GrabControl},
+        components::{AimDirection, DamagePercent, PlayerHealth, PlayerSlot, Radius, Range},
+        devices::{InputDevice, JoinRequest, LeaveRequest, PlayerDevice},
+        Action, PlayerInput,
+    },
+    sandbox::{render::render_particles, rng::SimulationRng, sandbox::Sandbox},
+};
+
+use super::protocol::{
+    decode, encode, ClientPacket, EntityUpdate, NetColor, RemoteInput, ServerPacket,
+    MAX_PACKET_SIZE,
+};
+
+pub struct NetClientPlugin;
+
+impl Plugin for NetClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                join_locally,
+                (receive_state, render_particles).chain(),
+                (
+                    mirror_match,
+                    mirror_players,
+                    mirror_rocks,
+                    follow_own_players,
+                )
+                    .chain()
+                    .after(receive_state),
+            )
+                .run_if(resource_exists::<NetClient>),
+        )
+        .add_systems(
+            FixedUpdate,
+            send_inputs.run_if(resource_exists::<NetClient>),
+        );
+    }
+}
+
+/// Sends the actions of the players on this instance to the server and shows the match the
+/// server runs
+#[derive(Resource)]
+pub struct NetClient {
+    socket: UdpSocket,
+    /// Number the server gave this client, known once the server answered
+    id: Option<u32>,
+    /// Step of the newest entity update
+    step: u64,
+    latest: Option<EntityUpdate>,
+    /// Revision of every chunk of the sandbox
+    revisions: Vec<u32>,
+    /// Chunks received since the last packet to the server
+    acks: Vec<(u32, u32)>,
+    joined: u32,
+}
+
+impl NetClient {
+    pub fn new(socket: UdpSocket) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            id: None,
+            step: 0,
+            latest: None,
+            revisions: vec![],
+            acks: vec![],
+            joined: 0,
+        })
+    }
+}
+
+/// A player of this instance, numbered in the order they joined. The server spawns them, this
+/// entity only reads their device.
+#[derive(Component)]
+struct LocalPlayer(u32);
+
+/// A player or rock of the server, by its entity there
+#[derive(Component)]
+struct Mirror(u64);
+
+/// Players join by pressing a button like they would locally, bots only join on the server
+fn join_locally(
+    mut commands: Commands,
+    mut client: ResMut<NetClient>,
+    mut join_requests: EventReader<JoinRequest>,
+    mut leave_requests: EventReader<LeaveRequest>,
+    local_query: Query<(Entity, &PlayerDevice), With<LocalPlayer>>,
+) {
+    for JoinRequest { device, profile } in join_requests.read() {
+        let joined = local_query.iter().any(|(_, local)| local.0 == *device);
+        if joined || matches!(device, InputDevice::Bot { .. }) {
+            continue;
+        }
+
+        println!("Join with {:?}", device);
+        commands.spawn((
+            Name::new("Local Player"),
+            LocalPlayer(client.joined),
+            PlayerDevice(*device),
+            ControlProfile(*profile),
+            ActionState::<Action>::default(),
+            GrabControl::default(),
+            // Follows the player on the server so the mouse can aim from it
+            TransformBundle::default(),
+        ));
+        client.joined += 1;
+    }
+
+    for LeaveRequest { device } in leave_requests.read() {
+        for (entity, local) in local_query.iter() {
+            if local.0 == *device {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+fn send_inputs(
+    mut client: ResMut<NetClient>,
+    local_query: Query<(
+        &LocalPlayer,
+        &ControlProfile,
+        &ActionState<Action>,
+        &GrabControl,
+    )>,
+) {
+    let packet = ClientPacket {
+        players: local_query
+            .iter()
+            .map(|(local, profile, action, grab_control)| RemoteInput {
+                local: local.0,
+                profile: profile.0,
+                input: PlayerInput::read(action, grab_control),
+            })
+            .collect(),
+        acks: std::mem::take(&mut client.acks),
+    };
+    if let Some(bytes) = encode(&packet) {
+        // The server runs the match without this client until its packets arrive
+        let _ = client.socket.send(&bytes);
+    }
+}
+
+/// Writes received chunks into the sandbox and keeps the newest entity update
+fn receive_state(
+    mut client: ResMut<NetClient>,
+    mut sandbox_query: Query<&mut Sandbox>,
+    mut rng: ResMut<SimulationRng>,
+) {
+    let Ok(mut sandbox) = sandbox_query.get_single_mut() else {
+        return;
+    };
+    // Only changed chunks are drawn
+    sandbox.reset_ticked_chunks();
+    let chunk_count = sandbox.get_all_chunks().len();
+    if client.revisions.len() != chunk_count {
+        client.revisions = vec![0; chunk_count];
+    }
+
+    let mut buffer = vec![0; MAX_PACKET_SIZE];
+    while let Ok(length) = client.socket.recv(&mut buffer) {
+        match decode::<ServerPacket>(&buffer[..length]) {
+            Some(ServerPacket::Entities(update)) => {
+                if client.id.is_none() {
+                    println!("Joined as client {}", update.client);
+                    client.id = Some(update.client);
+                }
+                if update.step > client.step {
+                    client.step = update.step;
+                    client.latest = Some(update);
+                }
+            }
+            Some(ServerPacket::Chunks(updates)) => {
+                for update in updates {
+                    client.acks.push((update.index, update.revision));
+                    let index = update.index as usize;
+                    // Resent chunks can arrive after newer ones
+                    if client
+                        .revisions
+                        .get(index)
+                        .is_some_and(|revision| *revision < update.revision)
+                    {
+                        client.revisions[index] = update.revision;
+                        sandbox.replace_chunk(index, update.particles(&mut rng));
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+/// The HUD and the outcome screen show the match of the server
+fn mirror_match(
+    client: Res<NetClient>,
+    mut score: ResMut<MatchScore>,
+    phase: Option<Res<State<MatchPhase>>>,
+    mut next_phase: ResMut<NextState<MatchPhase>>,
+) {
+    let Some(update) = &client.latest else {
+        return;
+    };
+
+    *score = update.score.clone();
+    if let (Some(phase), Some(server_phase)) = (phase, update.phase) {
+        if *phase.get() != server_phase {
+            next_phase.set(server_phase);
+        }
+    }
+}
+
+fn mirror_players(
+    mut commands: Commands,
+    client: Res<NetClient>,
+    mut player_query: Query<(
+        Entity,
+        &Mirror,
+        &mut Transform,
+        &mut AimDirection,
+        &mut Radius,
+        (&mut PlayerHealth, &mut DamagePercent, &mut Invulnerable),
+    )>,
+) {
+    let Some(update) = &client.latest else {
+        return;
+    };
+    let mut mirrors: HashMap<u64, Entity> = HashMap::new();
+
+    for (
+        entity,
+        mirror,
+        mut transform,
+        mut aim,
+        mut radius,
+        (mut health, mut percent, mut invulnerable),
+    ) in player_query.iter_mut()
+    {
+        let Some(player) = update.players.iter().find(|player| player.id == mirror.0) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+
+        mirrors.insert(mirror.0, entity);
+        transform.translation = player.position.extend(transform.translation.z);
+        aim.0 = player.aim;
+        radius.current = player.radius;
+        health.0 = player.health;
+        percent.0 = player.percent;
+        invulnerable.remaining = player.invulnerable;
+    }
+
+    for player in update.players.iter() {
+        if mirrors.contains_key(&player.id) {
+            continue;
+        }
+
+        commands.spawn((
+            Name::new("Remote Player"),
+            SpriteBundle {
+                sprite: Sprite {
+                    color: PlayerSlot(player.slot).color().into(),
+                    custom_size: Some(Vec2::new(30.0, 30.0)),
+                    ..default()
+                },
+                transform: Transform::from_translation(player.position.extend(0.1)),
+                ..default()
+            },
+            Mirror(player.id),
+            PlayerSlot(player.slot),
+            AimDirection(player.aim),
+            Range::default(),
+            Radius {
+                current: player.radius,
+                ..default()
+            },
+            PlayerHealth(player.health),
+            DamagePercent(player.percent),
+            Invulnerable {
+                remaining: player.invulnerable,
+            },
+        ));
+    }
+}
+
+fn mirror_rocks(
+    mut commands: Commands,
+    client: Res<NetClient>,
+    mut rock_query: Query<
+        (Entity, &Mirror, &mut Transform, Option<&Children>),
+        Without<PlayerSlot>,
+    >,
+    sandbox_query: Query<&Sandbox>,
+) {
+    let (Some(update), Ok(sandbox)) = (&client.latest, sandbox_query.get_single()) else {
+        return;
+    };
+    let mut mirrors: HashMap<u64, Entity> = HashMap::new();
+
+    for (entity, mirror, mut transform, children) in rock_query.iter_mut() {
+        let Some(rock) = update.rocks.iter().find(|rock| rock.id == mirror.0) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+
+        mirrors.insert(mirror.0, entity);
+        transform.translation = rock.position.extend(transform.translation.z);
+        transform.rotation = Quat::from_rotation_z(rock.rotation);
+
+        // Rocks lose cells when they hit something
+        if children.map_or(0, |children| children.len()) != rock.cells.len() {
+            commands
+                .entity(entity)
+                .despawn_descendants()
+                .with_children(|parent| spawn_rock_cells(parent, sandbox, &rock.cells));
+        }
+    }
+
+    for rock in update.rocks.iter() {
+        if mirrors.contains_key(&rock.id) {
+            continue;
+        }
+
+        commands
+            .spawn((
+                Name::new("Remote Rock"),
+                SpatialBundle::from_transform(
+                    Transform::from_translation(rock.position.extend(0.0))
+                        .with_rotation(Quat::from_rotation_z(rock.rotation)),
+                ),
+                Mirror(rock.id),
+            ))
+            .with_children(|parent| spawn_rock_cells(parent, sandbox, &rock.cells));
+    }
+}
+
+/// Cells are offset in whole cells from the middle of their rock
+fn spawn_rock_cells(parent: &mut ChildBuilder, sandbox: &Sandbox, cells: &[(IVec2, NetColor)]) {
+    let origin = sandbox.cell_to_world(0, 0);
+    let size = sandbox.cell_to_world(1, 1) - origin;
+    for (offset, color) in cells.iter() {
+        parent.spawn(SpriteBundle {
+            sprite: Sprite {
+                color: Color::srgba_u8(color.0, color.1, color.2, color.3),
+                custom_size: Some(size),
+                ..default()
+            },
+            transform: Transform::from_translation(
+                (sandbox.cell_to_world(offset.x, offset.y) - origin).extend(0.1),
+            ),
+            ..default()
+        });
+    }
+}
+
+/// Moves local players to where the server has them, so they aim from there
+fn follow_own_players(
+    client: Res<NetClient>,
+    mut local_query: Query<(&LocalPlayer, &mut Transform), Without<Mirror>>,
+) {
+    let (Some(update), Some(id)) = (&client.latest, client.id) else {
+        return;
+    };
+
+    for (local, mut transform) in local_query.iter_mut() {
+        let own = InputDevice::Remote {
+            client: id,
+            local: local.0,
+        };
+        if let Some(player) = update.players.iter().find(|player| player.device == own) {
+            transform.translation = player.position.extend(0.0);
+        }
+    }
+}
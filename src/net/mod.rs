@@ -0,0 +1,64 @@
+use std::net::UdpSocket;
+
+use bevy::prelude::*;
+
+use self::{
+    client::{NetClient, NetClientPlugin},
+    server::{NetServer, NetServerPlugin},
+};
+
+pub mod client;
+mod protocol;
+pub mod server;
+
+/// Port the server listens on when none is given
+const DEFAULT_PORT: u16 = 5000;
+
+/// Starts a network match when the game is run with `--host [port]` or
+/// `--join [address:port]`, otherwise everyone plays on this instance
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((NetServerPlugin, NetClientPlugin));
+
+        let args: Vec<String> = std::env::args().collect();
+        let value_after = |flag: &str| {
+            let position = args.iter().position(|arg| arg == flag)?;
+            Some(
+                args.get(position + 1)
+                    .filter(|value| !value.starts_with("--"))
+                    .cloned(),
+            )
+        };
+
+        if let Some(port) = value_after("--host") {
+            let port = port
+                .and_then(|port| port.parse().ok())
+                .unwrap_or(DEFAULT_PORT);
+            match UdpSocket::bind(("0.0.0.0", port)).and_then(NetServer::new) {
+                Ok(server) => {
+                    println!("Hosting on port {port}");
+                    app.insert_resource(server);
+                }
+                Err(error) => println!("Couldn't host on port {port}: {error}"),
+            }
+        } else if let Some(address) = value_after("--join") {
+            let address = address.unwrap_or_else(|| format!("127.0.0.1:{DEFAULT_PORT}"));
+            let socket = UdpSocket::bind(("0.0.0.0", 0))
+                .and_then(|socket| socket.connect(&address).map(|_| socket));
+            match socket.and_then(NetClient::new) {
+                Ok(client) => {
+                    println!("Joining {address}");
+                    app.insert_resource(client);
+                }
+                Err(error) => println!("Couldn't join {address}: {error}"),
+            }
+        }
+    }
+}
+
+/// Clients only show what the server sends, they don't run the match themselves
+pub fn is_client(client: Option<Res<NetClient>>) -> bool {
+    client.is_some()
+}
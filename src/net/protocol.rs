@@ -0,0 +1,186 @@
+use bevy::prelude::*;
+use bincode::Options;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    match_flow::{MatchPhase, MatchScore},
+    player::{devices::InputDevice, PlayerInput},
+    sandbox::{
+        particle::Particle,
+        particle_types::{get_particle, ParticleTypes},
+        rng::SimulationRng,
+    },
+};
+
+/// Largest UDP payload, big enough for every packet the server sends
+pub const MAX_PACKET_SIZE: usize = 65_507;
+
+/// Sent by clients every fixed step
+#[derive(Serialize, Deserialize)]
+pub struct ClientPacket {
+    pub players: Vec<RemoteInput>,
+    /// Index and revision of every chunk received since the last packet
+    pub acks: Vec<(u32, u32)>,
+}
+
+/// The actions of a player on the client
+#[derive(Serialize, Deserialize)]
+pub struct RemoteInput {
+    /// Players are numbered by the client in the order they joined there
+    pub local: u32,
+    pub profile: usize,
+    pub input: PlayerInput,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum ServerPacket {
+    /// Sent every fixed step, only the newest one counts
+    Entities(EntityUpdate),
+    /// Chunks that changed since the client acknowledged them
+    Chunks(Vec<ChunkUpdate>),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EntityUpdate {
+    /// Number the server gave the receiving client
+    pub client: u32,
+    pub step: u64,
+    pub players: Vec<NetPlayer>,
+    pub rocks: Vec<NetRock>,
+    /// Everyone who joined, their stocks and respawns, the match clock and the outcome
+    pub score: MatchScore,
+    /// `None` while the server is editing the arena
+    pub phase: Option<MatchPhase>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NetPlayer {
+    /// Entity of the player on the server
+    pub id: u64,
+    pub slot: usize,
+    pub device: InputDevice,
+    pub position: Vec2,
+    pub aim: Vec2,
+    pub radius: f32,
+    pub health: f32,
+    pub percent: f32,
+    pub invulnerable: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NetRock {
+    /// Entity of the rock on the server
+    pub id: u64,
+    pub position: Vec2,
+    pub rotation: f32,
+    /// Cell offsets from the center of the rock and their colors
+    pub cells: Vec<(IVec2, NetColor)>,
+}
+
+pub type NetColor = (u8, u8, u8, u8);
+
+/// A particle as far as clients need it, they don't simulate the sandbox
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct NetCell {
+    material: ParticleTypes,
+    color: NetColor,
+}
+
+/// All particles of a chunk, run-length encoded like unloaded chunks
+#[derive(Serialize, Deserialize)]
+pub struct ChunkUpdate {
+    pub index: u32,
+    /// Goes up every time the particles of the chunk change on the server
+    pub revision: u32,
+    runs: Vec<(u16, Option<NetCell>)>,
+}
+
+impl ChunkUpdate {
+    pub fn new(index: u32, revision: u32, particles: &[Option<Particle>]) -> Self {
+        let mut runs: Vec<(u16, Option<NetCell>)> = vec![];
+        for particle in particles {
+            let cell = particle.map(|particle| NetCell {
+                material: particle.material,
+                color: particle.color,
+            });
+            match runs.last_mut() {
+                Some((count, last)) if *last == cell && *count < u16::MAX => *count += 1,
+                _ => runs.push((1, cell)),
+            }
+        }
+
+        Self {
+            index,
+            revision,
+            runs,
+        }
+    }
+
+    pub fn particles(&self, rng: &mut SimulationRng) -> Vec<Option<Particle>> {
+        let mut particles = vec![];
+        for (count, cell) in self.runs.iter() {
+            let particle = cell.map(|cell| Particle {
+                color: cell.color,
+                ..get_particle(cell.material, rng)
+            });
+            particles.extend(std::iter::repeat_n(particle, *count as usize));
+        }
+        particles
+    }
+}
+
+/// Largest packet after decompression. Packets claiming more are dropped before anything is
+/// allocated for them, anyone can send packets to the server.
+const MAX_DECODED_SIZE: usize = 4 * MAX_PACKET_SIZE;
+
+/// Same encoding as `bincode::serialize`, but never reads or writes more than a decoded packet
+fn bincode_options() -> impl bincode::Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(MAX_DECODED_SIZE as u64)
+}
+
+/// Packets are compressed, most of the grid is made of long runs of the same few particles
+pub fn encode(message: &impl Serialize) -> Option<Vec<u8>> {
+    let bytes = bincode_options().serialize(message).ok()?;
+    Some(lz4_flex::compress_prepend_size(&bytes))
+}
+
+/// `None` for packets that aren't from this game, of a different version or too large
+pub fn decode<T: DeserializeOwned>(packet: &[u8]) -> Option<T> {
+    let (size, compressed) = lz4_flex::block::uncompressed_size(packet).ok()?;
+    if size > MAX_DECODED_SIZE {
+        return None;
+    }
+    let bytes = lz4_flex::decompress(compressed, size).ok()?;
+    bincode_options().deserialize(&bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_what_was_encoded() {
+        let packet = ClientPacket {
+            players: vec![],
+            acks: vec![(3, 7)],
+        };
+
+        let decoded = decode::<ClientPacket>(&encode(&packet).unwrap()).unwrap();
+        assert_eq!(decoded.acks, packet.acks);
+    }
+
+    #[test]
+    fn drops_packets_claiming_to_be_huge() {
+        let mut packet = encode(&ClientPacket {
+            players: vec![],
+            acks: vec![],
+        })
+        .unwrap();
+        packet[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        assert!(decode::<ClientPacket>(&packet).is_none());
+    }
+}
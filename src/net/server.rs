@@ -0,0 +1,365 @@
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+    sync::Arc,
+};
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{
+    damage::Invulnerable,
+    match_flow::{MatchPhase, MatchScore},
+    player::{
+        bindings::GrabControl,
+        components::{AimDirection, DamagePercent, PlayerHealth, PlayerSlot, Radius},
+        devices::{InputDevice, JoinRequest, LeaveRequest, PlayerDevice},
+        grab::{ParentObject, RockParticle},
+        Action, UsedDevices,
+    },
+    replay::is_replaying,
+    sandbox::{chunk::SandboxChunk, sandbox::Sandbox},
+    schedule::{GameplaySet, FIXED_HZ},
+};
+
+use super::protocol::{
+    decode, encode, ChunkUpdate, ClientPacket, EntityUpdate, NetPlayer, NetRock, RemoteInput,
+    ServerPacket, MAX_PACKET_SIZE,
+};
+
+/// Fixed steps without a packet before the players of a client leave
+const TIMEOUT_STEPS: u64 = 5 * FIXED_HZ as u64;
+/// Fixed steps before a chunk the client didn't acknowledge is sent again
+const RESEND_STEPS: u64 = 10;
+const CHUNKS_PER_PACKET: usize = 32;
+/// Larger changes, like the first grid sent to a client, are spread over several steps
+const CHUNK_PACKETS_PER_STEP: usize = 8;
+
+pub struct NetServerPlugin;
+
+impl Plugin for NetServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (
+                receive_inputs,
+                // Replays play back the joins, leaves and actions they recorded
+                (request_joins_and_leaves, apply_remote_inputs).run_if(not(is_replaying)),
+            )
+                .chain()
+                .in_set(GameplaySet::Input)
+                .run_if(resource_exists::<NetServer>),
+        )
+        // Physics steps in `FixedPostUpdate`, the state is sent once the whole step is done
+        .add_systems(
+            FixedPostUpdate,
+            (track_chunks, send_state)
+                .chain()
+                .after(PhysicsSet::Writeback)
+                .run_if(resource_exists::<NetServer>),
+        );
+    }
+}
+
+/// Runs the match for every client, which only send their inputs
+#[derive(Resource)]
+pub struct NetServer {
+    socket: UdpSocket,
+    clients: Vec<RemoteClient>,
+    next_client: u32,
+    step: u64,
+    /// Goes up for a chunk every time its particles change
+    revisions: Vec<u32>,
+    /// Players of clients that went away, they leave with the next step that isn't replayed
+    left: Vec<InputDevice>,
+    /// Chunks as of the last step, sharing their particles with the sandbox until they change
+    chunks: Vec<Arc<SandboxChunk>>,
+}
+
+struct RemoteClient {
+    id: u32,
+    address: SocketAddr,
+    last_heard: u64,
+    inputs: Vec<RemoteInput>,
+    /// Revision of every chunk the client acknowledged
+    acked: Vec<u32>,
+    /// Revision and step every chunk was last sent with
+    sent: Vec<(u32, u64)>,
+}
+
+impl RemoteClient {
+    fn device(&self, local: u32) -> InputDevice {
+        InputDevice::Remote {
+            client: self.id,
+            local,
+        }
+    }
+}
+
+impl NetServer {
+    pub fn new(socket: UdpSocket) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            clients: vec![],
+            left: vec![],
+            next_client: 1,
+            step: 0,
+            revisions: vec![],
+            chunks: vec![],
+        })
+    }
+}
+
+fn send_packet(socket: &UdpSocket, address: SocketAddr, packet: &ServerPacket) {
+    let Some(bytes) = encode(packet) else {
+        return;
+    };
+    if bytes.len() > MAX_PACKET_SIZE {
+        println!("Dropped a packet of {} bytes", bytes.len());
+        return;
+    }
+    // Lost packets are fine, entities are sent every step and chunks until acknowledged
+    let _ = socket.send_to(&bytes, address);
+}
+
+fn receive_inputs(mut server: ResMut<NetServer>) {
+    server.step += 1;
+    let step = server.step;
+    let chunk_count = server.revisions.len();
+
+    let mut buffer = vec![0; MAX_PACKET_SIZE];
+    while let Ok((length, address)) = server.socket.recv_from(&mut buffer) {
+        let Some(packet) = decode::<ClientPacket>(&buffer[..length]) else {
+            continue;
+        };
+
+        let index = match server
+            .clients
+            .iter()
+            .position(|client| client.address == address)
+        {
+            Some(index) => index,
+            None => {
+                let id = server.next_client;
+                server.next_client += 1;
+                println!("Client {id} connected from {address}");
+                server.clients.push(RemoteClient {
+                    id,
+                    address,
+                    last_heard: step,
+                    inputs: vec![],
+                    acked: vec![0; chunk_count],
+                    sent: vec![(0, 0); chunk_count],
+                });
+                server.clients.len() - 1
+            }
+        };
+        let server = &mut *server;
+        let client = &mut server.clients[index];
+        for input in client.inputs.iter() {
+            if !packet
+                .players
+                .iter()
+                .any(|other| other.local == input.local)
+            {
+                server.left.push(client.device(input.local));
+            }
+        }
+        for (chunk, revision) in packet.acks {
+            if let Some(acked) = client.acked.get_mut(chunk as usize) {
+                *acked = (*acked).max(revision);
+            }
+        }
+        client.inputs = packet.players;
+        client.last_heard = step;
+    }
+
+    let server = &mut *server;
+    server.clients.retain(|client| {
+        let timed_out = step - client.last_heard > TIMEOUT_STEPS;
+        if timed_out {
+            println!("Client {} timed out", client.id);
+            server
+                .left
+                .extend(client.inputs.iter().map(|input| client.device(input.local)));
+        }
+        !timed_out
+    });
+}
+
+/// Players of a client join when their inputs first arrive and leave when they stop arriving
+fn request_joins_and_leaves(
+    mut server: ResMut<NetServer>,
+    used_devices: Res<UsedDevices>,
+    mut join_requests: EventWriter<JoinRequest>,
+    mut leave_requests: EventWriter<LeaveRequest>,
+) {
+    leave_requests.send_batch(server.left.drain(..).map(|device| LeaveRequest { device }));
+
+    for client in server.clients.iter() {
+        for input in client.inputs.iter() {
+            let device = client.device(input.local);
+            if !used_devices.contains(&device) {
+                join_requests.send(JoinRequest {
+                    device,
+                    profile: input.profile,
+                });
+            }
+        }
+    }
+}
+
+/// Remote players have no input map, so only their client presses their actions
+fn apply_remote_inputs(
+    server: Res<NetServer>,
+    mut player_query: Query<(&PlayerDevice, &mut ActionState<Action>, &mut GrabControl)>,
+) {
+    for (device, mut action, mut grab_control) in player_query.iter_mut() {
+        let InputDevice::Remote { client, local } = device.0 else {
+            continue;
+        };
+        let input = server
+            .clients
+            .iter()
+            .filter(|remote| remote.id == client)
+            .flat_map(|remote| remote.inputs.iter())
+            .find(|input| input.local == local);
+        if let Some(input) = input {
+            input.input.apply(&mut action, &mut grab_control);
+        }
+    }
+}
+
+/// Bumps the revision of every chunk whose particles changed in this step. Unloaded chunks are
+/// tracked too, clients have no chunk loaders and show the whole grid.
+fn track_chunks(mut server: ResMut<NetServer>, sandbox_query: Query<&Sandbox>) {
+    let Ok(sandbox) = sandbox_query.get_single() else {
+        return;
+    };
+    let chunks = sandbox.get_all_chunks();
+
+    // A resized grid is sent again as a whole. Revisions never go down, so clients can tell
+    // the new chunks from the ones they have.
+    if server.chunks.len() != chunks.len() {
+        let revision = server.revisions.iter().max().map_or(1, |max| max + 1);
+        server.chunks = chunks.to_vec();
+        server.revisions = vec![revision; chunks.len()];
+        for client in server.clients.iter_mut() {
+            client.acked = vec![0; chunks.len()];
+            client.sent = vec![(0, 0); chunks.len()];
+        }
+        return;
+    }
+
+    let server = &mut *server;
+    for (index, chunk) in chunks.iter().enumerate() {
+        let last = &mut server.chunks[index];
+        if Arc::ptr_eq(chunk, last) {
+            continue;
+        }
+
+        // Loading or unloading a chunk doesn't change its particles
+        if chunk.particles() != last.particles() {
+            server.revisions[index] += 1;
+        }
+        *last = chunk.clone();
+    }
+}
+
+fn send_state(
+    mut server: ResMut<NetServer>,
+    player_query: Query<(
+        Entity,
+        &PlayerSlot,
+        &PlayerDevice,
+        &Transform,
+        &AimDirection,
+        &Radius,
+        (&PlayerHealth, &DamagePercent, &Invulnerable),
+    )>,
+    rock_query: Query<(Entity, &Transform, &Children), With<ParentObject>>,
+    cell_query: Query<(&Transform, &RockParticle)>,
+    sandbox_query: Query<&Sandbox>,
+    score: Res<MatchScore>,
+    phase: Option<Res<State<MatchPhase>>>,
+) {
+    let Ok(sandbox) = sandbox_query.get_single() else {
+        return;
+    };
+    let players: Vec<NetPlayer> = player_query
+        .iter()
+        .map(
+            |(entity, slot, device, transform, aim, radius, (health, percent, invulnerable))| {
+                NetPlayer {
+                    id: entity.to_bits(),
+                    slot: slot.0,
+                    device: device.0,
+                    position: transform.translation.truncate(),
+                    aim: aim.0,
+                    radius: radius.current,
+                    health: health.0,
+                    percent: percent.0,
+                    invulnerable: invulnerable.remaining,
+                }
+            },
+        )
+        .collect();
+    let rocks: Vec<NetRock> = rock_query
+        .iter()
+        .map(|(entity, transform, children)| NetRock {
+            id: entity.to_bits(),
+            position: transform.translation.truncate(),
+            rotation: transform.rotation.to_euler(EulerRot::XYZ).2,
+            cells: cell_query
+                .iter_many(children)
+                .map(|(transform, rock)| {
+                    // Cells sit on whole cells from the middle of their rock
+                    let offset = sandbox.world_to_cell(transform.translation.truncate())
+                        - sandbox.world_to_cell(Vec2::ZERO);
+                    (offset, rock.0.color)
+                })
+                .collect(),
+        })
+        .collect();
+
+    let server = &mut *server;
+    let step = server.step;
+    for client in server.clients.iter_mut() {
+        let update = ServerPacket::Entities(EntityUpdate {
+            client: client.id,
+            step,
+            players: players.clone(),
+            rocks: rocks.clone(),
+            score: score.clone(),
+            phase: phase.as_ref().map(|phase| *phase.get()),
+        });
+        send_packet(&server.socket, client.address, &update);
+
+        let stale: Vec<usize> = (0..server.revisions.len())
+            .filter(|index| {
+                let revision = server.revisions[*index];
+                let (sent_revision, sent_at) = client.sent[*index];
+                client.acked[*index] != revision
+                    && (sent_revision != revision || step >= sent_at + RESEND_STEPS)
+            })
+            .take(CHUNKS_PER_PACKET * CHUNK_PACKETS_PER_STEP)
+            .collect();
+        for batch in stale.chunks(CHUNKS_PER_PACKET) {
+            let updates = batch
+                .iter()
+                .map(|index| {
+                    let revision = server.revisions[*index];
+                    client.sent[*index] = (revision, step);
+                    ChunkUpdate::new(*index as u32, revision, &server.chunks[*index].particles())
+                })
+                .collect();
+            send_packet(
+                &server.socket,
+                client.address,
+                &ServerPacket::Chunks(updates),
+            );
+        }
+    }
+}
@@ -1,13 +1,52 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, window::PrimaryWindow};
 use leafwing_input_manager::prelude::ActionState;
 
-use crate::player::*;
+use crate::{
+    net::is_client,
+    player::{devices::MouseAim, *},
+    replay::is_replaying,
+    schedule::GameplaySet,
+};
 
 pub struct PlayerAimPlugin;
 
 impl Plugin for PlayerAimPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (update_aim, draw_circle).chain());
+        app.add_systems(
+            FixedUpdate,
+            (
+                aim_with_mouse
+                    .run_if(not(is_replaying))
+                    .in_set(GameplaySet::Input),
+                update_aim.in_set(GameplaySet::Movement),
+            ),
+        )
+        // Clients send their aim with the rest of their actions
+        .add_systems(Update, (draw_circle, aim_with_mouse.run_if(is_client)));
+    }
+}
+
+/// Points `Action::Aim` from the player towards the cursor
+fn aim_with_mouse(
+    mut player_query: Query<(&Transform, &mut ActionState<Action>), With<MouseAim>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+) {
+    let (Ok(window), Ok((camera, camera_transform))) =
+        (window_query.get_single(), camera_query.get_single())
+    else {
+        return;
+    };
+    let Some(cursor) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    else {
+        return;
+    };
+
+    for (transform, mut input) in player_query.iter_mut() {
+        let direction = (cursor - transform.translation.xy()).normalize_or_zero();
+        input.set_axis_pair(&Action::Aim, direction);
     }
 }
 
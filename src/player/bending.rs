@@ -0,0 +1,235 @@
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    match_flow::MatchScore,
+    sandbox::{
+        particle::{MovementType, Particle},
+        particle_types::{get_particle, ParticleTypes},
+        raycast::ParticleFilter,
+        rng::SimulationRng,
+        sandbox::Sandbox,
+    },
+    schedule::GameplaySet,
+};
+
+use super::{Action, AimDirection, PlayerSlot, Radius, Range};
+
+/// Seconds before an ability can be used again
+const ABILITY_COOLDOWN: f32 = 3.0;
+/// Cells a raised wall is high
+const WALL_HEIGHT: i32 = 10;
+/// Cells to either side of the middle of a raised wall
+const WALL_HALF_WIDTH: i32 = 1;
+/// Cells below the grab circle a wall looks for ground to rise from
+const WALL_GROUND_SEARCH: i32 = 16;
+
+pub struct BendingPlugin;
+
+impl Plugin for BendingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (switch_element, use_ability)
+                .chain()
+                .in_set(GameplaySet::Bending),
+        );
+    }
+}
+
+/// The element a player bends, which decides what they can grab and their ability
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Element {
+    /// Grabs rock, soil and plants, raises walls out of the ground
+    #[default]
+    Earth,
+    /// Grabs water, ice, acid and steam, freezes water into ice
+    Water,
+    /// Grabs lava, ash, oil and explosives, sets burnable particles on fire
+    Fire,
+}
+
+impl Element {
+    pub fn next(self) -> Self {
+        match self {
+            Element::Earth => Element::Water,
+            Element::Water => Element::Fire,
+            Element::Fire => Element::Earth,
+        }
+    }
+
+    /// Whether the element can grab the material or blow it away
+    pub fn can_bend(self, material: ParticleTypes) -> bool {
+        use ParticleTypes::*;
+
+        match self {
+            Element::Earth => matches!(
+                material,
+                Sand | Stone | Dirt | Grass | Wood | Glass | Igneous | Ash
+            ),
+            Element::Water => matches!(material, Water | Ice | Acid | Steam),
+            Element::Fire => matches!(
+                material,
+                Lava | Igneous | Ash | Oil | Gunpowder | Tnt | Spark | Smoke
+            ),
+        }
+    }
+}
+
+#[derive(Component, Default, Clone)]
+pub struct Bender {
+    pub element: Element,
+    /// Seconds until the ability can be used again
+    pub cooldown: f32,
+}
+
+/// Elements can be switched at any time, respawned players keep theirs
+fn switch_element(
+    mut query: Query<(&ActionState<Action>, &PlayerSlot, &mut Bender)>,
+    mut score: ResMut<MatchScore>,
+) {
+    for (action, slot, mut bender) in query.iter_mut() {
+        if !action.just_pressed(&Action::SwitchElement) {
+            continue;
+        }
+
+        bender.element = bender.element.next();
+        if let Some(record) = score.players.get_mut(slot.0) {
+            record.element = bender.element;
+        }
+    }
+}
+
+/// Uses the ability of the element on the grab circle
+fn use_ability(
+    mut sandbox_query: Query<&mut Sandbox>,
+    mut query: Query<(
+        &Transform,
+        &ActionState<Action>,
+        &AimDirection,
+        &Range,
+        &Radius,
+        &mut Bender,
+    )>,
+    mut rng: ResMut<SimulationRng>,
+    time: Res<Time>,
+) {
+    let Ok(mut sandbox) = sandbox_query.get_single_mut() else {
+        return;
+    };
+
+    for (transform, action, aim, range, radius, mut bender) in query.iter_mut() {
+        bender.cooldown = (bender.cooldown - time.delta_seconds()).max(0.0);
+        if bender.cooldown > 0.0 || !action.just_pressed(&Action::Ability) {
+            continue;
+        }
+
+        let center = sandbox
+            .world_to_cell(transform.translation.truncate() + aim.0 * (range.0 + radius.current));
+        let cells = (radius.current / 8.0).round() as i32;
+        let used = match bender.element {
+            Element::Earth => raise_wall(&mut sandbox, center, &mut rng),
+            Element::Water => freeze_water(&mut sandbox, center, cells, &mut rng),
+            Element::Fire => ignite_burnables(&mut sandbox, center, cells),
+        };
+        if used {
+            bender.cooldown = ABILITY_COOLDOWN;
+        }
+    }
+}
+
+/// Raises a stone wall from the ground below the cell, returns false without ground to raise it
+/// from
+fn raise_wall(sandbox: &mut Sandbox, center: IVec2, rng: &mut SimulationRng) -> bool {
+    let is_ground = |particle: &Particle| {
+        matches!(
+            particle.movement_type,
+            MovementType::Solid | MovementType::Powder
+        )
+    };
+    let Some(ground) = (0..WALL_GROUND_SEARCH)
+        .map(|depth| center.y - depth)
+        .find(|y| sandbox.checked_get_i32(center.x, *y).is_some_and(is_ground))
+    else {
+        return false;
+    };
+
+    for x in center.x - WALL_HALF_WIDTH..=center.x + WALL_HALF_WIDTH {
+        for y in ground + 1..=ground + WALL_HEIGHT {
+            if sandbox.out_of_bounds_i32(x, y) {
+                continue;
+            }
+            // The wall only fills free cells and pushes gas out of the way
+            let free = sandbox
+                .get(x as usize, y as usize)
+                .is_none_or(|particle| particle.movement_type == MovementType::Gas);
+            if free {
+                sandbox.set(
+                    x as usize,
+                    y as usize,
+                    Some(get_particle(ParticleTypes::Stone, rng)),
+                );
+            }
+        }
+    }
+    true
+}
+
+/// Turns the water in the circle into ice, returns false when there is none
+fn freeze_water(
+    sandbox: &mut Sandbox,
+    center: IVec2,
+    radius: i32,
+    rng: &mut SimulationRng,
+) -> bool {
+    let water = sandbox
+        .particles_in_circle(
+            center.as_vec2() + 0.5,
+            radius as f32,
+            ParticleFilter::Material(ParticleTypes::Water),
+        )
+        .into_iter()
+        .map(|(cell, _)| cell)
+        .collect::<Vec<_>>();
+
+    for cell in water.iter() {
+        sandbox.set(
+            cell.x as usize,
+            cell.y as usize,
+            Some(get_particle(ParticleTypes::Ice, rng)),
+        );
+    }
+    !water.is_empty()
+}
+
+/// Heats the burnable particles in the circle until they catch fire, returns false when there
+/// are none
+fn ignite_burnables(sandbox: &mut Sandbox, center: IVec2, radius: i32) -> bool {
+    let mut ignited = false;
+    for cell in cells_in_circle(center, radius) {
+        let Some(mut particle) = sandbox.checked_get_i32(cell.x, cell.y).copied() else {
+            continue;
+        };
+        let (Some(burnable), Some(temperature)) = (particle.burnable, &mut particle.temperature)
+        else {
+            continue;
+        };
+        if burnable.burning {
+            continue;
+        }
+
+        // The simulation sets particles on fire once they are heated to 0
+        temperature.current_temperature = 0;
+        sandbox.set(cell.x as usize, cell.y as usize, Some(particle));
+        ignited = true;
+    }
+    ignited
+}
+
+fn cells_in_circle(center: IVec2, radius: i32) -> impl Iterator<Item = IVec2> {
+    (-radius..=radius)
+        .flat_map(move |x| (-radius..=radius).map(move |y| IVec2::new(x, y)))
+        .filter(move |offset| offset.length_squared() <= radius * radius)
+        .map(move |offset| center + offset)
+}
@@ -0,0 +1,456 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{match_flow::MatchScore, replay::is_replaying};
+
+use super::{
+    devices::{InputDevice, MouseAim, PlayerDevice},
+    Action, PlayerSlot,
+};
+
+/// Binding profiles are read from and written to this file in the user's config directory
+const SETTINGS_FILE: &str = "settings.ron";
+/// Directory of the game inside of the config directory
+const CONFIG_DIRECTORY: &str = "bending_brawler";
+
+pub struct BindingsPlugin;
+
+impl Plugin for BindingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ControlSettings::load()).add_systems(
+            Update,
+            (switch_profile, apply_profiles)
+                .chain()
+                .run_if(not(is_replaying)),
+        );
+    }
+}
+
+/// Which devices a profile can be used with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeviceKind {
+    Gamepad,
+    KeyboardMouse,
+    KeyboardLeft,
+    KeyboardRight,
+}
+
+/// A button of any device. Bevy names the gamepad bumpers `LeftTrigger` and `RightTrigger`, the
+/// triggers are `LeftTrigger2` and `RightTrigger2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+
+impl Binding {
+    fn insert(&self, map: &mut InputMap<Action>, action: Action) {
+        match *self {
+            Binding::Key(key) => map.insert(action, key),
+            Binding::Mouse(button) => map.insert(action, button),
+            Binding::Gamepad(button) => map.insert(action, button),
+        };
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{key:?}"),
+            Binding::Mouse(button) => format!("{button:?} Mouse"),
+            Binding::Gamepad(button) => format!("{button:?}"),
+        }
+    }
+}
+
+/// A pair of axes, like a stick or four keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AxisBinding {
+    LeftStick,
+    RightStick,
+    DPad,
+    Keys {
+        up: KeyCode,
+        down: KeyCode,
+        left: KeyCode,
+        right: KeyCode,
+    },
+    /// Towards the mouse cursor, only for aiming
+    Mouse,
+}
+
+impl AxisBinding {
+    const WASD: AxisBinding = AxisBinding::Keys {
+        up: KeyCode::KeyW,
+        down: KeyCode::KeyS,
+        left: KeyCode::KeyA,
+        right: KeyCode::KeyD,
+    };
+    const ARROWS: AxisBinding = AxisBinding::Keys {
+        up: KeyCode::ArrowUp,
+        down: KeyCode::ArrowDown,
+        left: KeyCode::ArrowLeft,
+        right: KeyCode::ArrowRight,
+    };
+
+    fn insert(&self, map: &mut InputMap<Action>, action: Action) {
+        match *self {
+            AxisBinding::LeftStick => map.insert_dual_axis(action, GamepadStick::LEFT),
+            AxisBinding::RightStick => map.insert_dual_axis(action, GamepadStick::RIGHT),
+            AxisBinding::DPad => map.insert_dual_axis(action, VirtualDPad::dpad()),
+            AxisBinding::Keys {
+                up,
+                down,
+                left,
+                right,
+            } => map.insert_dual_axis(action, VirtualDPad::new(up, down, left, right)),
+            AxisBinding::Mouse => map,
+        };
+    }
+
+    /// Axes the rebind screen cycles through for a device
+    pub fn presets(kind: DeviceKind) -> Vec<AxisBinding> {
+        match kind {
+            DeviceKind::Gamepad => vec![
+                AxisBinding::LeftStick,
+                AxisBinding::RightStick,
+                AxisBinding::DPad,
+            ],
+            _ => vec![AxisBinding::WASD, AxisBinding::ARROWS, AxisBinding::Mouse],
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            AxisBinding::Keys {
+                up,
+                down,
+                left,
+                right,
+            } => format!("{up:?} {left:?} {down:?} {right:?}"),
+            _ => format!("{self:?}"),
+        }
+    }
+}
+
+/// How the grab button holds on to a picked up block
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GrabMode {
+    /// Pressing grab again drops the block
+    #[default]
+    Toggle,
+    /// Pressing grab again readies the drop, letting go drops the block
+    Hold,
+}
+
+/// Bindings of all actions for one kind of device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    pub device: DeviceKind,
+    pub movement: AxisBinding,
+    pub aim: AxisBinding,
+    pub jump: Binding,
+    pub grab: Binding,
+    pub set: Binding,
+    pub parry: Binding,
+    /// Charges a throw of the held block while pressed and throws it when let go
+    pub throw: Binding,
+    /// Uses the ability of the player's element
+    pub ability: Binding,
+    pub switch_element: Binding,
+    /// Switches the player to the next profile for their device
+    pub switch_profile: Binding,
+    pub grab_mode: GrabMode,
+}
+
+impl Profile {
+    pub fn input_map(&self, device: InputDevice) -> InputMap<Action> {
+        let mut map = InputMap::default();
+        self.movement.insert(&mut map, Action::Move);
+        self.aim.insert(&mut map, Action::Aim);
+        self.jump.insert(&mut map, Action::Jump);
+        self.grab.insert(&mut map, Action::Grab);
+        self.set.insert(&mut map, Action::Set);
+        self.parry.insert(&mut map, Action::Parry);
+        self.throw.insert(&mut map, Action::Throw);
+        self.ability.insert(&mut map, Action::Ability);
+        self.switch_element.insert(&mut map, Action::SwitchElement);
+        if let InputDevice::Gamepad(gamepad) = device {
+            map.set_gamepad(gamepad);
+        }
+        map
+    }
+
+    /// Every key of the profile, used to tell which half of a shared keyboard someone pressed
+    pub fn keys(&self) -> Vec<KeyCode> {
+        let mut keys: Vec<KeyCode> = [
+            self.jump,
+            self.grab,
+            self.set,
+            self.parry,
+            self.throw,
+            self.ability,
+            self.switch_element,
+            self.switch_profile,
+        ]
+        .iter()
+        .filter_map(|binding| match binding {
+            Binding::Key(key) => Some(*key),
+            _ => None,
+        })
+        .collect();
+        for axis in [self.movement, self.aim] {
+            if let AxisBinding::Keys {
+                up,
+                down,
+                left,
+                right,
+            } = axis
+            {
+                keys.extend([up, down, left, right]);
+            }
+        }
+        keys
+    }
+}
+
+/// Every binding profile, persisted in [`settings_path`]
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct ControlSettings {
+    pub profiles: Vec<Profile>,
+}
+
+impl Default for ControlSettings {
+    fn default() -> Self {
+        let gamepad = Profile {
+            name: "Gamepad".to_string(),
+            device: DeviceKind::Gamepad,
+            movement: AxisBinding::LeftStick,
+            aim: AxisBinding::RightStick,
+            jump: Binding::Gamepad(GamepadButtonType::LeftTrigger2),
+            grab: Binding::Gamepad(GamepadButtonType::RightTrigger2),
+            set: Binding::Gamepad(GamepadButtonType::LeftTrigger),
+            parry: Binding::Gamepad(GamepadButtonType::RightTrigger),
+            throw: Binding::Gamepad(GamepadButtonType::West),
+            ability: Binding::Gamepad(GamepadButtonType::North),
+            switch_element: Binding::Gamepad(GamepadButtonType::DPadUp),
+            switch_profile: Binding::Gamepad(GamepadButtonType::Select),
+            grab_mode: GrabMode::Toggle,
+        };
+        let gamepad_hold = Profile {
+            name: "Gamepad Hold Grab".to_string(),
+            grab_mode: GrabMode::Hold,
+            ..gamepad.clone()
+        };
+
+        Self {
+            profiles: vec![
+                gamepad,
+                gamepad_hold,
+                Profile {
+                    name: "Keyboard & Mouse".to_string(),
+                    device: DeviceKind::KeyboardMouse,
+                    movement: AxisBinding::WASD,
+                    aim: AxisBinding::Mouse,
+                    jump: Binding::Key(KeyCode::Space),
+                    grab: Binding::Mouse(MouseButton::Left),
+                    set: Binding::Key(KeyCode::KeyE),
+                    parry: Binding::Mouse(MouseButton::Right),
+                    throw: Binding::Key(KeyCode::KeyQ),
+                    ability: Binding::Key(KeyCode::KeyF),
+                    switch_element: Binding::Key(KeyCode::KeyT),
+                    switch_profile: Binding::Key(KeyCode::KeyR),
+                    grab_mode: GrabMode::Toggle,
+                },
+                // The shared keyboard layouts aim with their movement keys
+                Profile {
+                    name: "Left Keyboard".to_string(),
+                    device: DeviceKind::KeyboardLeft,
+                    movement: AxisBinding::WASD,
+                    aim: AxisBinding::WASD,
+                    jump: Binding::Key(KeyCode::Space),
+                    grab: Binding::Key(KeyCode::KeyF),
+                    set: Binding::Key(KeyCode::KeyQ),
+                    parry: Binding::Key(KeyCode::KeyE),
+                    throw: Binding::Key(KeyCode::KeyG),
+                    ability: Binding::Key(KeyCode::KeyC),
+                    switch_element: Binding::Key(KeyCode::KeyT),
+                    switch_profile: Binding::Key(KeyCode::KeyR),
+                    grab_mode: GrabMode::Toggle,
+                },
+                Profile {
+                    name: "Right Keyboard".to_string(),
+                    device: DeviceKind::KeyboardRight,
+                    movement: AxisBinding::ARROWS,
+                    aim: AxisBinding::ARROWS,
+                    jump: Binding::Key(KeyCode::ShiftRight),
+                    grab: Binding::Key(KeyCode::ControlRight),
+                    set: Binding::Key(KeyCode::Period),
+                    parry: Binding::Key(KeyCode::Slash),
+                    throw: Binding::Key(KeyCode::Comma),
+                    ability: Binding::Key(KeyCode::KeyM),
+                    switch_element: Binding::Key(KeyCode::Quote),
+                    switch_profile: Binding::Key(KeyCode::Backslash),
+                    grab_mode: GrabMode::Toggle,
+                },
+            ],
+        }
+    }
+}
+
+/// The settings file in the user's config directory, or next to the executable on platforms
+/// without one, so it doesn't depend on where the game was started from
+fn settings_path() -> PathBuf {
+    let config_directory = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| Path::new(&home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    };
+
+    config_directory
+        .map(|directory| directory.join(CONFIG_DIRECTORY))
+        .or_else(|| Some(env::current_exe().ok()?.parent()?.to_path_buf()))
+        .unwrap_or_default()
+        .join(SETTINGS_FILE)
+}
+
+impl ControlSettings {
+    /// Reads the settings file, writing the defaults when there is none yet
+    fn load() -> Self {
+        let path = settings_path();
+        let Ok(contents) = fs::read_to_string(&path) else {
+            let settings = Self::default();
+            settings.save();
+            return settings;
+        };
+
+        match ron::from_str(&contents) {
+            Ok(settings) => settings,
+            Err(error) => {
+                println!(
+                    "Couldn't read {}, using the default controls: {error}",
+                    path.display()
+                );
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let path = settings_path();
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())
+            .and_then(|contents| {
+                if let Some(directory) = path.parent() {
+                    fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+                }
+                fs::write(&path, contents).map_err(|error| error.to_string())
+            });
+        if let Err(error) = result {
+            println!("Couldn't save {}: {error}", path.display());
+        }
+    }
+
+    /// The first profile for the device, which new players start with
+    pub fn default_profile(&self, kind: DeviceKind) -> Option<usize> {
+        self.profiles
+            .iter()
+            .position(|profile| profile.device == kind)
+    }
+
+    /// The profile after `current` for the same device, wrapping around
+    pub fn next_profile(&self, current: usize) -> usize {
+        let Some(kind) = self.profiles.get(current).map(|profile| profile.device) else {
+            return current;
+        };
+
+        (1..=self.profiles.len())
+            .map(|offset| (current + offset) % self.profiles.len())
+            .find(|index| self.profiles[*index].device == kind)
+            .unwrap_or(current)
+    }
+}
+
+/// Index of the profile in [`ControlSettings`] a player is controlled with
+#[derive(Component, Clone, Copy)]
+pub struct ControlProfile(pub usize);
+
+/// Blocks held with [`GrabMode::Hold`] are only dropped after grab was pressed again
+#[derive(Component, Default, Clone)]
+pub struct GrabControl {
+    pub mode: GrabMode,
+    pub drop_ready: bool,
+}
+
+/// Rebuilds the input map of players whose profile or bindings changed. Replayed players get
+/// no input map, their actions come from the replay. Players get their `ActionState` when they
+/// spawn, so they can act from the first fixed step on.
+fn apply_profiles(
+    mut commands: Commands,
+    mut query: Query<(Entity, &PlayerDevice, Ref<ControlProfile>, &mut GrabControl)>,
+    settings: Res<ControlSettings>,
+) {
+    for (entity, device, profile, mut grab_control) in query.iter_mut() {
+        if !profile.is_changed() && !settings.is_changed() {
+            continue;
+        }
+        let Some(profile) = settings.profiles.get(profile.0) else {
+            continue;
+        };
+
+        grab_control.mode = profile.grab_mode;
+        let mut player = commands.entity(entity);
+        player.insert(profile.input_map(device.0));
+        if profile.aim == AxisBinding::Mouse {
+            player.insert(MouseAim);
+        } else {
+            player.remove::<MouseAim>();
+        }
+    }
+}
+
+fn switch_profile(
+    mut query: Query<(&PlayerDevice, &PlayerSlot, &mut ControlProfile)>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    gamepad_input: Res<ButtonInput<GamepadButton>>,
+    mut score: ResMut<MatchScore>,
+    settings: Res<ControlSettings>,
+) {
+    for (device, slot, mut profile) in query.iter_mut() {
+        let Some(switch) = settings.profiles.get(profile.0).map(|p| p.switch_profile) else {
+            continue;
+        };
+        let pressed = match (switch, device.0) {
+            (Binding::Key(key), _) => keyboard_input.just_pressed(key),
+            (Binding::Mouse(button), _) => mouse_input.just_pressed(button),
+            (Binding::Gamepad(button), InputDevice::Gamepad(gamepad)) => {
+                gamepad_input.just_pressed(GamepadButton::new(gamepad, button))
+            }
+            _ => false,
+        };
+        if !pressed {
+            continue;
+        }
+
+        profile.0 = settings.next_profile(profile.0);
+        if let Some(record) = score.players.get_mut(slot.0) {
+            record.profile = profile.0;
+        }
+        println!(
+            "Player {} switched to {}",
+            slot.0 + 1,
+            settings.profiles[profile.0].name
+        );
+    }
+}
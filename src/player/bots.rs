@@ -0,0 +1,387 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_rapier2d::dynamics::Velocity;
+use leafwing_input_manager::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    match_flow::MatchPhase,
+    replay::is_replaying,
+    sandbox::{
+        particle::{CollisionType, MovementType, Particle},
+        raycast::ParticleFilter,
+        rng::SimulationRng,
+        sandbox::Sandbox,
+    },
+    schedule::GameplaySet,
+};
+
+use super::{
+    bending::{Bender, Element},
+    devices::{InputDevice, JoinRequest},
+    grab::{Owner, ParentObject},
+    parry::Parry,
+    Action, HeldObject, PlayerHealth, Radius, Range,
+};
+
+pub struct BotPlugin;
+
+impl Plugin for BotPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BotSettings::default())
+            .add_systems(
+                Update,
+                (
+                    add_bot.run_if(in_state(MatchPhase::InProgress).and_then(not(is_replaying))),
+                    select_bot_difficulty,
+                ),
+            )
+            // Bots press their actions before anything reads them, so every other system sees
+            // them like any other player. Replays press the actions of bots they recorded.
+            .add_systems(
+                FixedUpdate,
+                drive_bots
+                    .run_if(not(is_replaying))
+                    .in_set(GameplaySet::Input),
+            );
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// Seconds between decisions like picking a target or where to dig
+    fn reaction_time(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.6,
+            Difficulty::Normal => 0.3,
+            Difficulty::Hard => 0.1,
+        }
+    }
+
+    /// Largest angle in radians a throw misses by
+    fn aim_error(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.5,
+            Difficulty::Normal => 0.2,
+            Difficulty::Hard => 0.05,
+        }
+    }
+
+    /// Chance to parry rocks thrown at the bot
+    fn parry_chance(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.15,
+            Difficulty::Normal => 0.5,
+            Difficulty::Hard => 0.9,
+        }
+    }
+}
+
+/// Difficulty and numbering of the next bot
+#[derive(Resource, Default)]
+struct BotSettings {
+    difficulty: Difficulty,
+    added: u32,
+}
+
+/// Seconds a bot charges its grab radius before picking terrain up
+const GRAB_CHARGE: f32 = 0.4;
+/// Seconds the held block swings away from the target while the throw charges
+const WINDUP: f32 = 0.35;
+/// Seconds the held block swings towards the target before the throw is let go
+const SWING: f32 = 0.15;
+/// Bots only dig where at least this many cells can be picked up
+const MIN_GRAB_CELLS: usize = 4;
+/// Bots keep this far from their target while they have nothing to throw
+const APPROACH_DISTANCE: f32 = 150.0;
+/// Bots keep this far from their target while they hold something
+const THROW_DISTANCE: f32 = 300.0;
+/// Rocks slower than this aren't worth parrying
+const PARRY_SPEED: f32 = 200.0;
+/// Cells ahead a bot looks for walls and hazards
+const LOOKAHEAD: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Plan {
+    /// Charging the grab radius around `aim`
+    Dig { aim: Vec2, elapsed: f32 },
+    /// Pulling the held block back before throwing it
+    Windup { elapsed: f32 },
+    /// Swinging the held block at the target
+    Swing { elapsed: f32 },
+}
+
+/// Decides which actions a bot presses
+#[derive(Component)]
+pub struct BotBrain {
+    difficulty: Difficulty,
+    plan: Option<Plan>,
+    /// Seconds until the next decision
+    think_in: f32,
+    aim_error: f32,
+    will_parry: bool,
+}
+
+impl BotBrain {
+    pub fn new(difficulty: Difficulty) -> Self {
+        Self {
+            difficulty,
+            plan: None,
+            think_in: 0.0,
+            aim_error: 0.0,
+            will_parry: false,
+        }
+    }
+}
+
+fn add_bot(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<BotSettings>,
+    mut join_requests: EventWriter<JoinRequest>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F6) {
+        return;
+    }
+
+    settings.added += 1;
+    join_requests.send(JoinRequest {
+        device: InputDevice::Bot {
+            id: settings.added,
+            difficulty: settings.difficulty,
+        },
+        profile: 0,
+    });
+}
+
+fn select_bot_difficulty(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<BotSettings>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F7) {
+        return;
+    }
+
+    settings.difficulty = match settings.difficulty {
+        Difficulty::Easy => Difficulty::Normal,
+        Difficulty::Normal => Difficulty::Hard,
+        Difficulty::Hard => Difficulty::Easy,
+    };
+    println!("Chose {:?} bots", settings.difficulty);
+}
+
+fn is_hazard(particle: &Particle) -> bool {
+    matches!(
+        particle.collision_type,
+        CollisionType::Fire | CollisionType::Acid
+    )
+}
+
+/// Gases slip through the grab, powders and liquids are only grabbed as blobs
+fn is_grabbable(particle: &Particle, element: Element) -> bool {
+    !particle.unbreakable
+        && particle.movement_type != MovementType::Gas
+        && element.can_bend(particle.material)
+}
+
+/// Filters can't capture the element, so every element gets its own
+fn grabbable_filter(element: Element) -> ParticleFilter {
+    match element {
+        Element::Earth => ParticleFilter::Custom(|particle| is_grabbable(particle, Element::Earth)),
+        Element::Water => ParticleFilter::Custom(|particle| is_grabbable(particle, Element::Water)),
+        Element::Fire => ParticleFilter::Custom(|particle| is_grabbable(particle, Element::Fire)),
+    }
+}
+
+fn drive_bots(
+    mut bot_query: Query<(
+        Entity,
+        &Transform,
+        &mut BotBrain,
+        &mut ActionState<Action>,
+        (&HeldObject, &Parry, &Range, &Radius, &Bender),
+    )>,
+    player_query: Query<(Entity, &Transform), With<PlayerHealth>>,
+    rock_query: Query<(&Transform, &Velocity, &Owner), With<ParentObject>>,
+    sandbox_query: Query<&Sandbox>,
+    mut rng: ResMut<SimulationRng>,
+    time: Res<Time>,
+) {
+    let Ok(sandbox) = sandbox_query.get_single() else {
+        return;
+    };
+    let delta = time.delta_seconds();
+    let solid = ParticleFilter::CollisionType(CollisionType::Solid);
+
+    for (entity, transform, mut brain, mut action, (held, parry, range, radius, bender)) in
+        bot_query.iter_mut()
+    {
+        let position = transform.translation.truncate();
+        let grid = sandbox.world_to_grid(position);
+
+        brain.think_in -= delta;
+        if brain.think_in <= 0.0 {
+            brain.think_in = brain.difficulty.reaction_time();
+            brain.aim_error = (rng.gen::<f32>() * 2.0 - 1.0) * brain.difficulty.aim_error();
+            brain.will_parry = rng.gen::<f32>() < brain.difficulty.parry_chance();
+        }
+
+        let target = player_query
+            .iter()
+            .filter(|(other, _)| *other != entity)
+            .map(|(_, other)| other.translation.truncate())
+            .min_by(|a, b| a.distance(position).total_cmp(&b.distance(position)));
+
+        // Walk towards the target until in range, back off when too close to throw
+        let to_target = target.map_or(Vec2::ZERO, |target| target - position);
+        let holding = held.0.is_some();
+        let keep_distance = if holding {
+            THROW_DISTANCE
+        } else {
+            APPROACH_DISTANCE
+        };
+        let mut walk = if to_target.x.abs() > keep_distance {
+            to_target.x.signum()
+        } else if holding && to_target.x.abs() < keep_distance * 0.5 {
+            -to_target.x.signum()
+        } else {
+            0.0
+        };
+
+        // Turn around before walking into fire or acid
+        if walk != 0.0 {
+            let ahead = grid + Vec2::new(walk * LOOKAHEAD, -2.0);
+            if sandbox.count_in_circle(ahead, 3.0, ParticleFilter::Custom(is_hazard)) > 0 {
+                walk = -walk;
+            }
+        }
+        let standing_in_hazard =
+            sandbox.count_in_circle(grid, 3.0, ParticleFilter::Custom(is_hazard)) > 0;
+
+        // Jump over walls and up to targets above
+        let wall_ahead = walk != 0.0
+            && sandbox
+                .raycast(grid, Vec2::new(walk, 0.0), LOOKAHEAD, solid)
+                .is_some_and(|hit| hit.distance < LOOKAHEAD);
+        let jump = wall_ahead || standing_in_hazard || to_target.y > 100.0;
+        action.set_axis_pair(&Action::Move, Vec2::new(walk, 0.0));
+        press_if(&mut action, Action::Jump, jump);
+
+        // Parry rocks of others flying at the bot
+        let incoming = rock_query.iter().any(|(rock, velocity, owner)| {
+            let offset = position - rock.translation.truncate();
+            owner.0 != entity
+                && offset.length() < parry.radius() * 1.5
+                && velocity.linvel.length() > PARRY_SPEED
+                && velocity.linvel.dot(offset) > 0.0
+        });
+        let parry_now = incoming && brain.will_parry && !action.pressed(&Action::Parry);
+        press_if(&mut action, Action::Parry, parry_now);
+
+        let aim_at_target = Vec2::from_angle(brain.aim_error).rotate(to_target.normalize_or_zero());
+        let sees_target = target.is_some_and(|target| {
+            sandbox.line_of_sight(grid, sandbox.world_to_grid(target), solid)
+        });
+
+        let (aim, grab, throw) = match (brain.plan, holding) {
+            (Some(Plan::Dig { aim, elapsed }), false) => {
+                let done = elapsed >= GRAB_CHARGE;
+                brain.plan = (!done).then_some(Plan::Dig {
+                    aim,
+                    elapsed: elapsed + delta,
+                });
+                // Letting go of grab picks the terrain up
+                (aim, !done, false)
+            }
+            (Some(Plan::Windup { elapsed }), true) => {
+                brain.plan = Some(if elapsed >= WINDUP {
+                    Plan::Swing { elapsed: 0.0 }
+                } else {
+                    Plan::Windup {
+                        elapsed: elapsed + delta,
+                    }
+                });
+                (-aim_at_target, false, true)
+            }
+            (Some(Plan::Swing { elapsed }), true) => {
+                let done = elapsed >= SWING;
+                brain.plan = (!done).then_some(Plan::Swing {
+                    elapsed: elapsed + delta,
+                });
+                // Letting go of the charged throw throws
+                (aim_at_target, false, !done)
+            }
+            (_, true) => {
+                if sees_target && to_target.length() < THROW_DISTANCE * 1.5 {
+                    brain.plan = Some(Plan::Windup { elapsed: 0.0 });
+                } else {
+                    brain.plan = None;
+                }
+                (aim_at_target, false, false)
+            }
+            (_, false) => {
+                brain.plan =
+                    best_dig_aim(sandbox, position, to_target, range, radius, bender.element)
+                        .map(|aim| Plan::Dig { aim, elapsed: 0.0 });
+                (
+                    brain
+                        .plan
+                        .map_or(Vec2::ZERO, |_| to_target.normalize_or_zero()),
+                    false,
+                    false,
+                )
+            }
+        };
+        action.set_axis_pair(&Action::Aim, aim);
+        press_if(&mut action, Action::Grab, grab);
+        press_if(&mut action, Action::Throw, throw);
+    }
+}
+
+/// Finds the direction around the bot with the most terrain to pick up, preferring terrain
+/// towards the target
+fn best_dig_aim(
+    sandbox: &Sandbox,
+    position: Vec2,
+    to_target: Vec2,
+    range: &Range,
+    radius: &Radius,
+    element: Element,
+) -> Option<Vec2> {
+    const DIRECTIONS: usize = 8;
+
+    (0..DIRECTIONS)
+        .map(|i| Vec2::from_angle(i as f32 / DIRECTIONS as f32 * TAU))
+        .map(|aim| {
+            let center = position + aim * (range.0 + radius.current);
+            let cells = sandbox.count_in_circle(
+                sandbox.world_to_grid(center),
+                radius.current / 8.0,
+                grabbable_filter(element),
+            );
+            (aim, cells)
+        })
+        .filter(|(_, cells)| *cells >= MIN_GRAB_CELLS)
+        .max_by(|(a, a_cells), (b, b_cells)| {
+            let score =
+                |aim: &Vec2, cells: usize| cells as f32 + aim.dot(to_target.normalize_or_zero());
+            score(a, *a_cells).total_cmp(&score(b, *b_cells))
+        })
+        .map(|(aim, _)| aim)
+}
+
+fn press_if(action: &mut ActionState<Action>, button: Action, pressed: bool) {
+    if pressed {
+        action.press(&button);
+    } else {
+        action.release(&button);
+    }
+}
@@ -1,8 +1,10 @@
 use bevy::prelude::*;
 
-use super::parry::Parry;
+use crate::{buoyancy::Submerged, damage::Invulnerable, sandbox::streaming::ChunkLoader};
 
-#[derive(Component)]
+use super::{bending::Bender, parry::Parry, status::StatusEffects};
+
+#[derive(Component, Clone)]
 pub struct AimDirection(pub Vec2);
 
 impl Default for AimDirection {
@@ -20,7 +22,7 @@ impl Default for Range {
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct Radius {
     pub min: f32,
     pub current: f32,
@@ -48,13 +50,39 @@ impl Radius {
     }
 }
 
-#[derive(Component, Default)]
+#[derive(Component, Default, Clone)]
 pub struct HeldObject(pub Option<Entity>);
 
-#[derive(Default, Component)]
+/// How far a throw is charged, from 0 to 1
+#[derive(Component, Default, Clone)]
+pub struct ThrowCharge(pub f32);
+
+#[derive(Default, Component, Clone)]
 pub struct PlayerHealth(pub f32);
 
-#[derive(Component)]
+/// Damage taken in `CombatMode::Percentage`, higher percentages take more knockback
+#[derive(Default, Component, Clone)]
+pub struct DamagePercent(pub f32);
+
+/// Colors of the player slots in join order
+const SLOT_COLORS: [Srgba; 4] = [
+    bevy::color::palettes::basic::BLUE,
+    bevy::color::palettes::basic::LIME,
+    bevy::color::palettes::basic::YELLOW,
+    bevy::color::palettes::basic::FUCHSIA,
+];
+
+/// Index of the player in `MatchScore::players`, which picks their color
+#[derive(Default, Component, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerSlot(pub usize);
+
+impl PlayerSlot {
+    pub fn color(&self) -> Srgba {
+        SLOT_COLORS[self.0 % SLOT_COLORS.len()]
+    }
+}
+
+#[derive(Component, Clone)]
 pub struct ExtraJumps {
     pub max: i32,
     pub current: i32,
@@ -78,5 +106,13 @@ pub struct PlayerBundle {
     pub parry: Parry,
     pub jumps: ExtraJumps,
     pub held: HeldObject,
+    pub throw_charge: ThrowCharge,
     pub health: PlayerHealth,
+    pub percent: DamagePercent,
+    pub slot: PlayerSlot,
+    pub chunk_loader: ChunkLoader,
+    pub status_effects: StatusEffects,
+    pub submerged: Submerged,
+    pub invulnerable: Invulnerable,
+    pub bender: Bender,
 }
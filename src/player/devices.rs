@@ -0,0 +1,151 @@
+use bevy::{
+    input::gamepad::{GamepadButtonChangedEvent, GamepadConnectionEvent},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{match_flow::MatchPhase, replay::is_replaying};
+
+use super::{
+    bindings::{ControlSettings, DeviceKind},
+    bots::Difficulty,
+};
+
+pub struct InputDevicePlugin;
+
+impl Plugin for InputDevicePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<JoinRequest>()
+            .add_event::<LeaveRequest>()
+            .add_systems(
+                Update,
+                (
+                    (request_gamepad_join, request_keyboard_join)
+                        .run_if(in_state(MatchPhase::InProgress)),
+                    request_gamepad_leave,
+                )
+                    .run_if(not(is_replaying)),
+            );
+    }
+}
+
+/// What a player is controlled with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputDevice {
+    Gamepad(Gamepad),
+    /// A keyboard together with the mouse
+    KeyboardMouse,
+    /// Left half of a keyboard shared by two players
+    KeyboardLeft,
+    /// Right half of a keyboard shared by two players
+    KeyboardRight,
+    /// A computer player, numbered so any amount of them can join
+    Bot {
+        id: u32,
+        difficulty: Difficulty,
+    },
+    /// A player of another instance playing over the network, numbered by the client and by
+    /// the order the players joined there
+    Remote {
+        client: u32,
+        local: u32,
+    },
+}
+
+impl InputDevice {
+    /// The keyboard and mouse player uses the same keys as the left shared keyboard player, so
+    /// only one of them can play at a time
+    pub fn conflicts_with(&self, other: &InputDevice) -> bool {
+        matches!(
+            (self, other),
+            (InputDevice::KeyboardMouse, InputDevice::KeyboardLeft)
+                | (InputDevice::KeyboardLeft, InputDevice::KeyboardMouse)
+        )
+    }
+}
+
+/// The device controlling a player
+#[derive(Component, Clone, Copy)]
+pub struct PlayerDevice(pub InputDevice);
+
+/// Someone pressed a button on a device that isn't playing yet
+#[derive(Event, Clone, Copy, Serialize, Deserialize)]
+pub struct JoinRequest {
+    pub device: InputDevice,
+    /// Index of the binding profile in `ControlSettings`
+    pub profile: usize,
+}
+
+/// The device of a player went away, like an unplugged gamepad
+#[derive(Event, Clone, Copy, Serialize, Deserialize)]
+pub struct LeaveRequest {
+    pub device: InputDevice,
+}
+
+/// Players aiming at the mouse cursor instead of with an input of their map, see
+/// `AxisBinding::Mouse`
+#[derive(Component)]
+pub struct MouseAim;
+
+/// Gamepads join with the first gamepad profile
+fn request_gamepad_join(
+    mut gamepad_button_events: EventReader<GamepadButtonChangedEvent>,
+    mut join_requests: EventWriter<JoinRequest>,
+    settings: Res<ControlSettings>,
+) {
+    let Some(profile) = settings.default_profile(DeviceKind::Gamepad) else {
+        return;
+    };
+
+    for event in gamepad_button_events.read() {
+        join_requests.send(JoinRequest {
+            device: InputDevice::Gamepad(event.gamepad),
+            profile,
+        });
+    }
+}
+
+/// Clicking joins with keyboard and mouse. Pressing a key of a shared keyboard profile joins
+/// with that half of the keyboard and that profile.
+fn request_keyboard_join(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut join_requests: EventWriter<JoinRequest>,
+    settings: Res<ControlSettings>,
+) {
+    if mouse_input.get_just_pressed().next().is_some() {
+        if let Some(profile) = settings.default_profile(DeviceKind::KeyboardMouse) {
+            join_requests.send(JoinRequest {
+                device: InputDevice::KeyboardMouse,
+                profile,
+            });
+        }
+    }
+
+    for (index, profile) in settings.profiles.iter().enumerate() {
+        let device = match profile.device {
+            DeviceKind::KeyboardLeft => InputDevice::KeyboardLeft,
+            DeviceKind::KeyboardRight => InputDevice::KeyboardRight,
+            _ => continue,
+        };
+        if keyboard_input.any_just_pressed(profile.keys()) {
+            join_requests.send(JoinRequest {
+                device,
+                profile: index,
+            });
+        }
+    }
+}
+
+fn request_gamepad_leave(
+    mut gamepad_connection_events: EventReader<GamepadConnectionEvent>,
+    mut leave_requests: EventWriter<LeaveRequest>,
+) {
+    for event in gamepad_connection_events.read() {
+        if event.disconnected() {
+            leave_requests.send(LeaveRequest {
+                device: InputDevice::Gamepad(event.gamepad),
+            });
+        }
+    }
+}
@@ -1,34 +1,49 @@
 use bevy::prelude::*;
 use bevy_rapier2d::{
     dynamics::{Ccd, Damping, GravityScale, ReadMassProperties, RigidBody, Velocity},
-    geometry::{ActiveEvents, Collider, CollisionGroups, Friction, Group},
+    geometry::{ActiveEvents, Collider, CollisionGroups, Friction, Group, Sensor},
 };
 use leafwing_input_manager::prelude::ActionState;
 
-use crate::sandbox::{particle_types::get_particle, sandbox::Sandbox};
+use crate::{
+    sandbox::{
+        particle::{self, MovementType, Particle},
+        sandbox::Sandbox,
+    },
+    schedule::GameplaySet,
+    state::ResetArena,
+};
 
-use super::{Action, AimDirection, HeldObject, Radius, Range};
+use super::{
+    bending::Bender,
+    bindings::{GrabControl, GrabMode},
+    throw::{charge_throw, throw_speed},
+    Action, AimDirection, HeldObject, Radius, Range, ThrowCharge,
+};
 
 pub struct GrabPlugin;
 
 impl Plugin for GrabPlugin {
     fn build(&self, app: &mut App) {
-        app.init_state::<GrabState>().add_systems(
-            Update,
-            (
+        app.init_state::<GrabState>()
+            .add_systems(FixedUpdate, clear_rocks.in_set(GameplaySet::Reset))
+            .add_systems(
+                FixedUpdate,
                 (
                     increase_grab_radius,
-                    (grab_dirt, release_grab).chain(),
+                    grab_dirt,
+                    release_grab,
+                    charge_throw,
                     release_held,
+                    move_object,
+                    update_actual_velocity,
+                    place_back,
+                    place_back_mini,
+                    place_back_particle,
                 )
-                    .chain(),
-                move_object,
-                update_actual_velocity,
-                place_back,
-                place_back_mini,
-                place_back_particle,
-            ),
-        );
+                    .chain()
+                    .in_set(GameplaySet::Grab),
+            );
     }
 }
 
@@ -47,6 +62,58 @@ pub struct Rock;
 #[derive(Component)]
 pub struct Owner(pub Entity);
 
+/// A held object made of powder or liquid that spills back into the sandbox when released
+#[derive(Component)]
+pub struct Blob;
+
+/// The particle a rock was made from, written back into the sandbox when the rock is placed
+#[derive(Component, Clone, Copy)]
+pub struct RockParticle(pub Particle);
+
+impl RockParticle {
+    pub fn color(&self) -> Color {
+        let (r, g, b, a) = self.0.color;
+        Color::srgba_u8(r, g, b, a)
+    }
+}
+
+/// Writes the rock's particle into the cell at the world position
+pub fn place_rock(sandbox: &mut Sandbox, position: Vec3, rock: &RockParticle) {
+    let IVec2 { x, y } = sandbox.world_to_cell(position.truncate());
+    if sandbox.out_of_bounds_i32(x, y) {
+        return;
+    }
+
+    let mut particle = rock.0;
+    particle.velocity = default();
+    particle.updated = false;
+    sandbox.set(x as usize, y as usize, Some(particle));
+}
+
+/// How far up a spilled particle looks for a free cell before it is lost
+const SPILL_SEARCH_HEIGHT: i32 = 8;
+
+/// Drops the rock's particle into the first free cell at or above the world position as a free
+/// particle. `velocity` is in cells per simulation tick.
+pub fn spill_particle(sandbox: &mut Sandbox, position: Vec3, rock: &RockParticle, velocity: Vec2) {
+    let IVec2 { x, y } = sandbox.world_to_cell(position.truncate());
+
+    for y in y..y + SPILL_SEARCH_HEIGHT {
+        if sandbox.out_of_bounds_i32(x, y) {
+            return;
+        }
+        if sandbox.get(x as usize, y as usize).is_some() {
+            continue;
+        }
+
+        let mut particle = rock.0;
+        particle.velocity = particle::Velocity::new(velocity.x as i32, velocity.y as i32);
+        particle.updated = false;
+        sandbox.set(x as usize, y as usize, Some(particle));
+        return;
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
 enum GrabState {
     #[default]
@@ -77,29 +144,83 @@ fn release_grab(mut query: Query<(&ActionState<Action>, &mut Radius)>) {
     }
 }
 
+/// Drops the held object when grab is pressed again and throws it when a charged throw is let go
 fn release_held(
     mut commands: Commands,
-    mut query: Query<(&ActionState<Action>, &mut HeldObject)>,
+    mut sandbox_query: Query<&mut Sandbox>,
+    mut query: Query<(
+        &ActionState<Action>,
+        &AimDirection,
+        &mut HeldObject,
+        &mut GrabControl,
+        &mut ThrowCharge,
+    )>,
     mut parent_query: Query<
-        (Entity, &mut Velocity, &mut GravityScale),
+        (
+            Entity,
+            &mut Velocity,
+            &mut GravityScale,
+            &ReadMassProperties,
+        ),
         (With<ParentObject>, With<Held>),
     >,
+    blob_query: Query<&Children, With<Blob>>,
+    rock_query: Query<(&GlobalTransform, &RockParticle)>,
     mut state: ResMut<NextState<GrabState>>,
 ) {
-    for (action, mut held) in query.iter_mut() {
+    for (action, aim, mut held, mut control, mut charge) in query.iter_mut() {
         let Some(entity) = held.0 else {
             continue;
         };
-        if action.pressed(&Action::Grab) {
-            held.0 = None;
-            state.set(GrabState::Empty);
+        let throw = action.just_released(&Action::Throw);
+        if !throw && !wants_drop(action, &mut control) {
+            continue;
+        }
 
-            let Ok((entity, mut velocity, mut scale)) = parent_query.get_mut(entity) else {
-                continue;
-            };
-            commands.entity(entity).remove::<Held>();
-            scale.0 = 1.0;
-            velocity.linvel *= 2.0;
+        held.0 = None;
+        state.set(GrabState::Empty);
+        let charged = std::mem::take(&mut charge.0);
+
+        let Ok((entity, mut velocity, mut scale, properties)) = parent_query.get_mut(entity) else {
+            continue;
+        };
+        if throw {
+            velocity.linvel = aim.0 * throw_speed(charged, properties);
+        }
+
+        if let Ok(children) = blob_query.get(entity) {
+            let mut sandbox = sandbox_query.single_mut();
+            // Convert the speed of the blob to cells per tick
+            let spill_velocity = velocity.linvel / (8.0 * 24.0);
+            for child in children.iter() {
+                let Ok((transform, rock)) = rock_query.get(*child) else {
+                    continue;
+                };
+                spill_particle(&mut sandbox, transform.translation(), rock, spill_velocity);
+            }
+
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        commands.entity(entity).remove::<Held>();
+        scale.0 = 1.0;
+    }
+}
+
+fn wants_drop(action: &ActionState<Action>, control: &mut GrabControl) -> bool {
+    match control.mode {
+        GrabMode::Toggle => action.pressed(&Action::Grab),
+        // The release that picked the block up doesn't count
+        GrabMode::Hold => {
+            if action.just_pressed(&Action::Grab) {
+                control.drop_ready = true;
+            }
+            let drop = control.drop_ready && action.just_released(&Action::Grab);
+            if drop {
+                control.drop_ready = false;
+            }
+            drop
         }
     }
 }
@@ -114,13 +235,15 @@ fn grab_dirt(
         &AimDirection,
         &Radius,
         &Range,
+        &Bender,
         &mut HeldObject,
     )>,
     mut state: ResMut<NextState<GrabState>>,
 ) {
     let mut sandbox = sandbox_query.single_mut();
 
-    for (entity, transform, action, aim, radius, range, mut held) in player_query.iter_mut() {
+    for (entity, transform, action, aim, radius, range, bender, mut held) in player_query.iter_mut()
+    {
         if held.0.is_some() {
             continue;
         }
@@ -129,91 +252,138 @@ fn grab_dirt(
             let min = -(radius.current / 8.0).round() as i32;
             let max = (radius.current / 8.0).round() as i32;
 
-            let (grid_x, grid_y) = (
-                ((transform.translation.x + aim.0.x * (range.0 + radius.current)) / 8.0).floor()
-                    as i32
-                    + sandbox.width() as i32 / 2,
-                ((transform.translation.y + aim.0.y * (range.0 + radius.current)) / 8.0).floor()
-                    as i32
-                    + sandbox.height() as i32 / 2,
+            let IVec2 {
+                x: grid_x,
+                y: grid_y,
+            } = sandbox.world_to_cell(
+                transform.translation.truncate() + aim.0 * (range.0 + radius.current),
             );
 
-            let mut dirt = vec![];
+            let mut solids = vec![];
+            let mut fluids = vec![];
 
             for offset_x in min..=max {
                 for offset_y in min..=max {
                     let x = grid_x + offset_x;
                     let y = grid_y + offset_y;
 
-                    if !sandbox.out_of_bounds_i32(x, y) && sandbox.checked_get_i32(x, y).is_some() {
-                        sandbox.set(x as usize, y as usize, None);
-                        dirt.push(
-                            commands
-                                .spawn((
-                                    Name::new("Rock"),
-                                    SpriteBundle {
-                                        sprite: Sprite {
-                                            color: Color::srgb_u8(189, 139, 139),
-                                            custom_size: Some(Vec2::new(8.0, 8.0)),
-                                            ..default()
-                                        },
-                                        transform: Transform::from_translation(Vec3::new(
-                                            offset_x as f32 * 8.0,
-                                            offset_y as f32 * 8.0,
-                                            0.1,
-                                        )),
-                                        ..default()
-                                    },
-                                    Collider::cuboid(3.0, 3.0),
-                                    Ccd::enabled(),
-                                    Friction::coefficient(0.0),
-                                    ActiveEvents::COLLISION_EVENTS,
-                                    CollisionGroups::new(
-                                        Group::all(),
-                                        Group::all().difference(Group::GROUP_1),
-                                    ),
-                                    Velocity::default(),
-                                    ActualVelocity::default(),
-                                    Rock,
-                                ))
-                                .id(),
-                        );
+                    let Some(particle) = sandbox.checked_get_i32(x, y).copied() else {
+                        continue;
+                    };
+                    if particle.unbreakable || !bender.element.can_bend(particle.material) {
+                        continue;
+                    }
+
+                    let offset = IVec2::new(offset_x, offset_y);
+                    match particle.movement_type {
+                        MovementType::Solid => solids.push((offset, particle)),
+                        MovementType::Powder | MovementType::Liquid => {
+                            fluids.push((offset, particle))
+                        }
+                        // Gas slips through the grab and gets blown away from it
+                        MovementType::Gas => {
+                            if let Some(particle) = sandbox.get_mut(x as usize, y as usize) {
+                                particle.velocity = particle::Velocity::new(
+                                    offset_x.signum() * 3,
+                                    offset_y.signum() * 3,
+                                );
+                            }
+                        }
                     }
                 }
             }
 
-            if !dirt.is_empty() {
-                held.0 = Some(
-                    commands
-                        .spawn((
-                            Name::new("Rock Parent"),
-                            SpatialBundle::from_transform(Transform::from_translation(
-                                transform.translation
-                                    + (aim.0 * (range.0 + radius.current)).extend(0.0),
-                            )),
-                            RigidBody::Dynamic,
-                            GravityScale(0.0),
-                            ReadMassProperties::default(),
-                            Velocity::default(),
-                            ActualVelocity::default(),
-                            Ccd::enabled(),
-                            Damping {
-                                linear_damping: 0.0,
-                                angular_damping: 100.0,
-                            },
-                            Owner(entity),
-                            ParentObject,
-                            Held,
-                        ))
-                        .push_children(&dirt)
-                        .id(),
+            // Solids make up a rigid block, powders and liquids are only held when there is
+            // nothing solid to hold them together
+            let is_blob = solids.is_empty();
+            let cells = if is_blob { fluids } else { solids };
+
+            let mut dirt = vec![];
+            for (offset, particle) in cells {
+                sandbox.set(
+                    (grid_x + offset.x) as usize,
+                    (grid_y + offset.y) as usize,
+                    None,
                 );
+                dirt.push(spawn_grabbed(
+                    &mut commands,
+                    RockParticle(particle),
+                    offset,
+                    is_blob,
+                ));
+            }
+
+            if !dirt.is_empty() {
+                let mut parent = commands.spawn((
+                    Name::new("Rock Parent"),
+                    SpatialBundle::from_transform(Transform::from_translation(
+                        transform.translation + (aim.0 * (range.0 + radius.current)).extend(0.0),
+                    )),
+                    RigidBody::Dynamic,
+                    GravityScale(0.0),
+                    ReadMassProperties::default(),
+                    Velocity::default(),
+                    ActualVelocity::default(),
+                    Ccd::enabled(),
+                    Damping {
+                        linear_damping: 0.0,
+                        angular_damping: 100.0,
+                    },
+                    Owner(entity),
+                    ParentObject,
+                    Held,
+                ));
+                parent.push_children(&dirt);
+                if is_blob {
+                    parent.insert(Blob);
+                }
+                held.0 = Some(parent.id());
                 state.set(GrabState::Holding);
             }
         }
     }
 }
 
+fn spawn_grabbed(
+    commands: &mut Commands,
+    rock: RockParticle,
+    offset: IVec2,
+    is_blob: bool,
+) -> Entity {
+    let sprite = SpriteBundle {
+        sprite: Sprite {
+            color: rock.color(),
+            custom_size: Some(Vec2::new(8.0, 8.0)),
+            ..default()
+        },
+        transform: Transform::from_translation((offset.as_vec2() * 8.0).extend(0.1)),
+        ..default()
+    };
+
+    if is_blob {
+        // Blobs don't push anything around, they only give the held object its mass
+        return commands
+            .spawn((Name::new("Blob"), sprite, Collider::ball(4.0), Sensor, rock))
+            .id();
+    }
+
+    commands
+        .spawn((
+            Name::new("Rock"),
+            sprite,
+            Collider::cuboid(3.0, 3.0),
+            Ccd::enabled(),
+            Friction::coefficient(0.0),
+            ActiveEvents::COLLISION_EVENTS,
+            CollisionGroups::new(Group::all(), Group::all().difference(Group::GROUP_1)),
+            Velocity::default(),
+            ActualVelocity::default(),
+            Rock,
+            rock,
+        ))
+        .id()
+}
+
 pub fn move_object(
     player_query: Query<
         (&Transform, &AimDirection, &Range, &Radius, &HeldObject),
@@ -235,13 +405,34 @@ pub fn move_object(
         };
         let desired_pos = transform.translation.truncate() + (aim.0 * (range.0 + radius.current));
         let force = (desired_pos - parent_transform.translation.truncate()) * 400.0;
-        let mass = if properties.mass.is_nan() || properties.mass <= f32::EPSILON {
-            64.0
-        } else {
-            properties.mass.sqrt()
-        };
+        velocity.linvel = force / mass_factor(properties);
+    }
+}
 
-        velocity.linvel = force / mass;
+/// Heavier bodies are held and thrown slower, by the square root of their mass
+pub fn mass_factor(properties: &ReadMassProperties) -> f32 {
+    if properties.mass.is_nan() || properties.mass <= f32::EPSILON {
+        64.0
+    } else {
+        properties.mass.sqrt()
+    }
+}
+
+fn clear_rocks(
+    mut commands: Commands,
+    mut player_query: Query<&mut HeldObject>,
+    rock_query: Query<Entity, (Or<(With<ParentObject>, With<Rock>)>, Without<Parent>)>,
+    mut events: EventReader<ResetArena>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+
+    for entity in rock_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for mut held in player_query.iter_mut() {
+        held.0 = None;
     }
 }
 
@@ -258,7 +449,7 @@ fn place_back(
     mut sandbox_query: Query<&mut Sandbox>,
     parent_query: Query<(Entity, &Velocity), (With<ParentObject>, Without<Held>)>,
     children_query: Query<&Children>,
-    transform_query: Query<&GlobalTransform>,
+    rock_query: Query<(&GlobalTransform, &RockParticle)>,
 ) {
     let mut sandbox = sandbox_query.single_mut();
 
@@ -268,23 +459,11 @@ fn place_back(
         }
 
         for child in children_query.iter_descendants(entity) {
-            let Ok(position) = transform_query.get(child).map(|x| x.translation()) else {
+            let Ok((transform, rock)) = rock_query.get(child) else {
                 continue;
             };
-            let (grid_x, grid_y) = (
-                (position.x / 8.0).floor() as i32 + sandbox.width() as i32 / 2,
-                (position.y / 8.0).floor() as i32 + sandbox.height() as i32 / 2,
-            );
 
-            if !sandbox.out_of_bounds_i32(grid_x, grid_y) {
-                sandbox.set(
-                    grid_x as usize,
-                    grid_y as usize,
-                    Some(get_particle(
-                        crate::sandbox::particle_types::ParticleTypes::Dirt,
-                    )),
-                );
-            }
+            place_rock(&mut sandbox, transform.translation(), rock);
         }
 
         commands.entity(entity).despawn_recursive();
@@ -296,7 +475,7 @@ fn place_back_mini(
     mut sandbox_query: Query<&mut Sandbox>,
     parent_query: Query<(Entity, &Velocity, &Parent), (With<Rock>, With<PutBackIntoSandbox>)>,
     held_query: Query<(), With<Held>>,
-    transform_query: Query<&GlobalTransform>,
+    rock_query: Query<(&GlobalTransform, &RockParticle)>,
 ) {
     let mut sandbox = sandbox_query.single_mut();
 
@@ -305,23 +484,11 @@ fn place_back_mini(
             continue;
         }
 
-        let Ok(position) = transform_query.get(entity).map(|x| x.translation()) else {
+        let Ok((transform, rock)) = rock_query.get(entity) else {
             continue;
         };
-        let (grid_x, grid_y) = (
-            (position.x / 8.0).floor() as i32 + sandbox.width() as i32 / 2,
-            (position.y / 8.0).floor() as i32 + sandbox.height() as i32 / 2,
-        );
-
-        if !sandbox.out_of_bounds_i32(grid_x, grid_y) {
-            sandbox.set(
-                grid_x as usize,
-                grid_y as usize,
-                Some(get_particle(
-                    crate::sandbox::particle_types::ParticleTypes::Dirt,
-                )),
-            );
-        }
+
+        place_rock(&mut sandbox, transform.translation(), rock);
 
         commands.entity(entity).despawn_recursive();
     }
@@ -334,7 +501,7 @@ fn place_back_particle(
         (Entity, &Velocity),
         (With<Rock>, With<PutBackIntoSandbox>, Without<Parent>),
     >,
-    transform_query: Query<&GlobalTransform>,
+    rock_query: Query<(&GlobalTransform, &RockParticle)>,
 ) {
     let mut sandbox = sandbox_query.single_mut();
 
@@ -343,23 +510,11 @@ fn place_back_particle(
             continue;
         }
 
-        let Ok(position) = transform_query.get(entity).map(|x| x.translation()) else {
+        let Ok((transform, rock)) = rock_query.get(entity) else {
             continue;
         };
-        let (grid_x, grid_y) = (
-            (position.x / 8.0).floor() as i32 + sandbox.width() as i32 / 2,
-            (position.y / 8.0).floor() as i32 + sandbox.height() as i32 / 2,
-        );
-
-        if !sandbox.out_of_bounds_i32(grid_x, grid_y) {
-            sandbox.set(
-                grid_x as usize,
-                grid_y as usize,
-                Some(get_particle(
-                    crate::sandbox::particle_types::ParticleTypes::Dirt,
-                )),
-            );
-        }
+
+        place_rock(&mut sandbox, transform.translation(), rock);
 
         commands.entity(entity).despawn_recursive();
     }
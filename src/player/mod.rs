@@ -1,37 +1,75 @@
 use self::{
-    aim_direction::PlayerAimPlugin, components::*, grab::GrabPlugin, parry::ParryPlugin,
-    player_movement::PlayerMovementPlugin, set::SetPlugin, spawn_player::PlayerConnectionPlugin,
+    aim_direction::PlayerAimPlugin,
+    bending::BendingPlugin,
+    bindings::{BindingsPlugin, GrabControl, GrabMode},
+    bots::BotPlugin,
+    components::*,
+    devices::{InputDevice, InputDevicePlugin},
+    grab::GrabPlugin,
+    parry::ParryPlugin,
+    player_movement::PlayerMovementPlugin,
+    rebind::RebindPlugin,
+    set::SetPlugin,
+    spawn_player::PlayerConnectionPlugin,
+    status::StatusEffectPlugin,
+    throw::ThrowPlugin,
 };
 use bevy::{prelude::*, utils::HashMap};
 use leafwing_input_manager::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    damage::Invulnerable,
+    rules::{CombatMode, MatchRules},
+};
 
 mod aim_direction;
+pub mod bending;
+pub mod bindings;
+pub mod bots;
 pub mod components;
+pub mod devices;
 pub mod grab;
-mod parry;
+pub mod parry;
 mod player_movement;
+pub mod rebind;
 mod set;
-mod spawn_player;
+pub mod spawn_player;
+pub mod status;
+mod throw;
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(InputManagerPlugin::<Action>::default())
-            .insert_resource(UsedGamepads::default())
+            .insert_resource(UsedDevices::default())
             .add_plugins((
                 PlayerMovementPlugin,
                 PlayerAimPlugin,
                 PlayerConnectionPlugin,
+                InputDevicePlugin,
+                BindingsPlugin,
+                RebindPlugin,
+                BotPlugin,
                 GrabPlugin,
                 SetPlugin,
                 ParryPlugin,
+                StatusEffectPlugin,
+                ThrowPlugin,
+                BendingPlugin,
             ))
-            .add_systems(Update, change_color_on_health);
+            .add_systems(
+                Update,
+                (change_color_on_health, blink_while_invulnerable).chain(),
+            );
     }
 }
 
-#[derive(Reflect, PartialEq, Eq, Clone, Copy, Hash, Debug)]
+/// Blinks per second while a player can't be hurt
+const INVULNERABLE_BLINK_RATE: f32 = 8.0;
+
+#[derive(Reflect, PartialEq, Eq, Clone, Copy, Hash, Debug, Serialize, Deserialize)]
 pub enum Action {
     Move,
     Aim,
@@ -39,6 +77,9 @@ pub enum Action {
     Grab,
     Set,
     Parry,
+    Throw,
+    Ability,
+    SwitchElement,
 }
 
 impl Actionlike for Action {
@@ -50,19 +91,121 @@ impl Actionlike for Action {
             Action::Grab => InputControlKind::Button,
             Action::Set => InputControlKind::Button,
             Action::Parry => InputControlKind::Button,
+            Action::Throw => InputControlKind::Button,
+            Action::Ability => InputControlKind::Button,
+            Action::SwitchElement => InputControlKind::Button,
+        }
+    }
+}
+
+/// Buttons of [`PlayerInput`]
+const BUTTONS: [Action; 7] = [
+    Action::Jump,
+    Action::Grab,
+    Action::Set,
+    Action::Parry,
+    Action::Throw,
+    Action::Ability,
+    Action::SwitchElement,
+];
+
+/// The actions of one player in a fixed step, recorded by replays and sent by network clients
+#[derive(Serialize, Deserialize)]
+pub struct PlayerInput {
+    movement: Vec2,
+    aim: Vec2,
+    /// Buttons held down
+    pressed: Vec<Action>,
+    grab_mode: GrabMode,
+}
+
+impl PlayerInput {
+    pub fn read(action: &ActionState<Action>, grab_control: &GrabControl) -> Self {
+        Self {
+            movement: action.axis_pair(&Action::Move),
+            aim: action.axis_pair(&Action::Aim),
+            pressed: BUTTONS
+                .into_iter()
+                .filter(|button| action.pressed(button))
+                .collect(),
+            grab_mode: grab_control.mode,
+        }
+    }
+
+    pub fn apply(&self, action: &mut ActionState<Action>, grab_control: &mut GrabControl) {
+        action.set_axis_pair(&Action::Move, self.movement);
+        action.set_axis_pair(&Action::Aim, self.aim);
+        for button in BUTTONS {
+            if self.pressed.contains(&button) {
+                action.press(&button);
+            } else {
+                action.release(&button);
+            }
         }
+        grab_control.mode = self.grab_mode;
     }
 }
 
-fn change_color_on_health(mut query: Query<(&mut Sprite, &PlayerHealth), Changed<PlayerHealth>>) {
-    for (mut sprite, health) in query.iter_mut() {
+fn change_color_on_health(
+    mut query: Query<
+        (&mut Sprite, &PlayerHealth, &DamagePercent, &PlayerSlot),
+        Or<(Changed<PlayerHealth>, Changed<DamagePercent>)>,
+    >,
+    rules: Res<MatchRules>,
+) {
+    for (mut sprite, health, percent, slot) in query.iter_mut() {
+        let remaining = match rules.combat {
+            CombatMode::Health => health.0 / 50000.0,
+            CombatMode::Percentage => 1.0 - percent.0 / 200.0,
+        };
         sprite.color = bevy::color::palettes::basic::RED
-            .mix(&bevy::color::palettes::basic::BLUE, health.0 / 50000.0)
+            .mix(&slot.color(), remaining.clamp(0.0, 1.0))
             .into();
     }
 }
 
+fn blink_while_invulnerable(mut query: Query<(&mut Sprite, &Invulnerable)>) {
+    for (mut sprite, invulnerable) in query.iter_mut() {
+        let alpha = if invulnerable.is_active()
+            && (invulnerable.remaining * INVULNERABLE_BLINK_RATE).fract() < 0.5
+        {
+            0.3
+        } else {
+            1.0
+        };
+        if sprite.color.alpha() != alpha {
+            sprite.color.set_alpha(alpha);
+        }
+    }
+}
+
+/// Devices controlling a player in the arena
 #[derive(Default, Resource)]
-pub struct UsedGamepads {
-    gamepads: HashMap<Gamepad, Entity>,
+pub struct UsedDevices {
+    devices: HashMap<InputDevice, Entity>,
+}
+
+impl UsedDevices {
+    pub fn insert(&mut self, device: InputDevice, entity: Entity) {
+        self.devices.insert(device, entity);
+    }
+
+    pub fn contains(&self, device: &InputDevice) -> bool {
+        self.devices.contains_key(device)
+    }
+
+    /// Frees the device of a player that is no longer in the arena
+    pub fn remove_player(&mut self, entity: Entity) -> Option<InputDevice> {
+        let device = *self
+            .devices
+            .iter()
+            .find(|(_, player)| **player == entity)?
+            .0;
+        self.devices.remove(&device);
+        Some(device)
+    }
+
+    pub fn clear(&mut self) {
+        self.devices.clear();
+    }
 }
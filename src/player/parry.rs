@@ -4,7 +4,7 @@ use bevy_rapier2d::{
 };
 use leafwing_input_manager::action_state::ActionState;
 
-use crate::player::grab::Held;
+use crate::{player::grab::Held, schedule::GameplaySet};
 
 use super::{
     grab::{Owner, ParentObject, Rock},
@@ -16,18 +16,16 @@ pub struct ParryPlugin;
 impl Plugin for ParryPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
-            Update,
-            (
-                regen_parry,
-                initate_parry,
-                parry.before(regen_parry),
-                draw_parry,
-            ),
-        );
+            FixedUpdate,
+            (initate_parry, parry, regen_parry)
+                .chain()
+                .in_set(GameplaySet::Parry),
+        )
+        .add_systems(Update, draw_parry);
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct Parry {
     max_radius: f32,
     min_radius: f32,
@@ -52,6 +50,18 @@ impl Default for Parry {
     }
 }
 
+impl Parry {
+    /// How far the parry radius has recharged, from 0 to 1
+    pub fn charge(&self) -> f32 {
+        (self.current_radius / self.max_radius).min(1.0)
+    }
+
+    /// Radius of the next parry
+    pub fn radius(&self) -> f32 {
+        self.current_radius
+    }
+}
+
 fn regen_parry(mut query: Query<&mut Parry>, time: Res<Time>) {
     for mut parry in query.iter_mut() {
         let x = parry.current_radius / parry.max_radius;
@@ -1,4 +1,5 @@
 use bevy::prelude::*;
+use bevy_rapier2d::dynamics::Velocity;
 use bevy_tnua::{
     builtins::{TnuaBuiltinJump, TnuaBuiltinWalk},
     controller::TnuaController,
@@ -6,24 +7,49 @@ use bevy_tnua::{
 };
 use leafwing_input_manager::prelude::ActionState;
 
-use crate::player::*;
+use crate::{
+    buoyancy::Submerged,
+    player::{status::StatusEffects, *},
+    schedule::GameplaySet,
+};
+
+/// How deep in a liquid players have to be before they swim instead of walk
+const SWIM_DEPTH: f32 = 0.5;
+const SWIM_ACCELERATION: f32 = 1500.0;
+
 pub struct PlayerMovementPlugin;
 
 impl Plugin for PlayerMovementPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, apply_controls.in_set(TnuaUserControlsSystemSet));
+        app.add_systems(
+            FixedUpdate,
+            apply_controls
+                .in_set(TnuaUserControlsSystemSet)
+                .in_set(GameplaySet::Movement),
+        );
         //.add_systems(Update, apply_movement.after(jump));
     }
 }
 
-fn apply_controls(mut query: Query<(&ActionState<Action>, &mut TnuaController, &mut ExtraJumps)>) {
-    for (action, mut controller, mut jumps) in query.iter_mut() {
-        let direction = action.clamped_axis_pair(&Action::Move).x;
+fn apply_controls(
+    mut query: Query<(
+        &ActionState<Action>,
+        &mut TnuaController,
+        &mut ExtraJumps,
+        &StatusEffects,
+        &Submerged,
+        &mut Velocity,
+    )>,
+    time: Res<Time>,
+) {
+    for (action, mut controller, mut jumps, effects, submerged, mut velocity) in query.iter_mut() {
+        let direction = action.clamped_axis_pair(&Action::Move).x * effects.speed_multiplier();
+        let swimming = submerged.fraction >= SWIM_DEPTH;
 
         controller.basis(TnuaBuiltinWalk {
             desired_velocity: Vec3::new(direction * 800.0, 0.0, 0.0),
             float_height: 25.0,
-            free_fall_extra_gravity: 320.0,
+            free_fall_extra_gravity: if swimming { 0.0 } else { 320.0 },
             acceleration: 2600.0,
             air_acceleration: 2600.0,
             coyote_time: 500.0,
@@ -31,6 +57,18 @@ fn apply_controls(mut query: Query<(&ActionState<Action>, &mut TnuaController, &
             ..default()
         });
 
+        // Swimming players steer up and down with Move, Jump strokes upwards. Jumps come back
+        // so players can jump out of the liquid.
+        if swimming {
+            let mut stroke = action.clamped_axis_pair(&Action::Move).y;
+            if action.pressed(&Action::Jump) {
+                stroke = 1.0;
+            }
+            velocity.linvel.y += stroke * SWIM_ACCELERATION * time.delta_seconds();
+            jumps.current = jumps.max;
+            continue;
+        }
+
         if action.pressed(&Action::Jump) {
             let allow = if jumps.current > 0 { true } else { false };
 
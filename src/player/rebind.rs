@@ -0,0 +1,331 @@
+use bevy::prelude::*;
+
+use crate::state::GameState;
+
+use super::bindings::{AxisBinding, Binding, ControlSettings, DeviceKind, GrabMode, Profile};
+
+pub struct RebindPlugin;
+
+impl Plugin for RebindPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RebindScreen::default())
+            .add_systems(OnEnter(GameState::Editing), spawn_screen)
+            .add_systems(OnExit(GameState::Editing), close_screen)
+            .add_systems(
+                Update,
+                (toggle_screen, edit_bindings, show_screen)
+                    .chain()
+                    .run_if(in_state(GameState::Editing)),
+            );
+    }
+}
+
+/// Lines of the rebind screen, one for every binding of a profile
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Row {
+    Move,
+    Aim,
+    Jump,
+    Grab,
+    Set,
+    Parry,
+    Throw,
+    Ability,
+    SwitchElement,
+    SwitchProfile,
+    GrabMode,
+}
+
+const ROWS: [Row; 11] = [
+    Row::Move,
+    Row::Aim,
+    Row::Jump,
+    Row::Grab,
+    Row::Set,
+    Row::Parry,
+    Row::Throw,
+    Row::Ability,
+    Row::SwitchElement,
+    Row::SwitchProfile,
+    Row::GrabMode,
+];
+
+impl Row {
+    fn name(&self) -> &'static str {
+        match self {
+            Row::Move => "Move",
+            Row::Aim => "Aim",
+            Row::Jump => "Jump",
+            Row::Grab => "Grab",
+            Row::Set => "Set",
+            Row::Parry => "Parry",
+            Row::Throw => "Throw",
+            Row::Ability => "Ability",
+            Row::SwitchElement => "Switch Element",
+            Row::SwitchProfile => "Switch Profile",
+            Row::GrabMode => "Grab Mode",
+        }
+    }
+
+    fn button_mut<'a>(&self, profile: &'a mut Profile) -> Option<&'a mut Binding> {
+        match self {
+            Row::Jump => Some(&mut profile.jump),
+            Row::Grab => Some(&mut profile.grab),
+            Row::Set => Some(&mut profile.set),
+            Row::Parry => Some(&mut profile.parry),
+            Row::Throw => Some(&mut profile.throw),
+            Row::Ability => Some(&mut profile.ability),
+            Row::SwitchElement => Some(&mut profile.switch_element),
+            Row::SwitchProfile => Some(&mut profile.switch_profile),
+            _ => None,
+        }
+    }
+
+    fn value(&self, profile: &Profile) -> String {
+        match self {
+            Row::Move => profile.movement.label(),
+            Row::Aim => profile.aim.label(),
+            Row::Jump => profile.jump.label(),
+            Row::Grab => profile.grab.label(),
+            Row::Set => profile.set.label(),
+            Row::Parry => profile.parry.label(),
+            Row::Throw => profile.throw.label(),
+            Row::Ability => profile.ability.label(),
+            Row::SwitchElement => profile.switch_element.label(),
+            Row::SwitchProfile => profile.switch_profile.label(),
+            Row::GrabMode => format!("{:?}", profile.grab_mode),
+        }
+    }
+}
+
+/// Which binding of which profile is being edited
+#[derive(Resource, Default)]
+pub struct RebindScreen {
+    open: bool,
+    profile: usize,
+    row: usize,
+    /// Waiting for the button to bind to the selected row
+    waiting: bool,
+}
+
+#[derive(Component)]
+struct RebindText;
+
+/// The editor ignores its keys and the mouse while the rebind screen has them
+pub fn rebind_screen_open(screen: Res<RebindScreen>) -> bool {
+    screen.open
+}
+
+fn spawn_screen(mut commands: Commands) {
+    commands
+        .spawn((
+            Name::new("Rebind Screen"),
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(16.0),
+                    top: Val::Px(16.0),
+                    padding: UiRect::all(Val::Px(12.0)),
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.8).into(),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            RebindText,
+            StateScoped(GameState::Editing),
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 20.0,
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn close_screen(mut screen: ResMut<RebindScreen>) {
+    screen.open = false;
+    screen.waiting = false;
+}
+
+fn toggle_screen(keyboard_input: Res<ButtonInput<KeyCode>>, mut screen: ResMut<RebindScreen>) {
+    if keyboard_input.just_pressed(KeyCode::F1) && !screen.waiting {
+        screen.open = !screen.open;
+    }
+}
+
+fn edit_bindings(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    gamepad_input: Res<ButtonInput<GamepadButton>>,
+    mut screen: ResMut<RebindScreen>,
+    mut settings: ResMut<ControlSettings>,
+) {
+    if !screen.open || settings.profiles.is_empty() {
+        return;
+    }
+    if screen.profile >= settings.profiles.len() {
+        screen.profile = settings.profiles.len() - 1;
+    }
+    let row = ROWS[screen.row];
+
+    if screen.waiting {
+        if keyboard_input.just_pressed(KeyCode::Backspace) {
+            screen.waiting = false;
+            return;
+        }
+
+        // Only a new binding counts as a change, not waiting for it
+        let profile = &mut settings.bypass_change_detection().profiles[screen.profile];
+        let pressed = match profile.device {
+            DeviceKind::Gamepad => gamepad_input
+                .get_just_pressed()
+                .next()
+                .map(|button| Binding::Gamepad(button.button_type)),
+            _ => keyboard_input
+                .get_just_pressed()
+                .next()
+                .map(|key| Binding::Key(*key))
+                .or_else(|| {
+                    mouse_input
+                        .get_just_pressed()
+                        .next()
+                        .map(|button| Binding::Mouse(*button))
+                }),
+        };
+        if let (Some(pressed), Some(binding)) = (pressed, row.button_mut(profile)) {
+            *binding = pressed;
+            screen.waiting = false;
+            settings.set_changed();
+            settings.save();
+        }
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        screen.row = (screen.row + ROWS.len() - 1) % ROWS.len();
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        screen.row = (screen.row + 1) % ROWS.len();
+    }
+    let profiles = settings.profiles.len();
+    if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+        screen.profile = (screen.profile + profiles - 1) % profiles;
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+        screen.profile = (screen.profile + 1) % profiles;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        let profile = &mut settings.profiles[screen.profile];
+        match row {
+            Row::Move => profile.movement = next_axis(profile.device, profile.movement, false),
+            Row::Aim => profile.aim = next_axis(profile.device, profile.aim, true),
+            Row::GrabMode => {
+                profile.grab_mode = match profile.grab_mode {
+                    GrabMode::Toggle => GrabMode::Hold,
+                    GrabMode::Hold => GrabMode::Toggle,
+                }
+            }
+            _ => {
+                screen.waiting = true;
+                return;
+            }
+        }
+        settings.save();
+    }
+
+    // Players are only in the arena while playing, so profiles can be added and removed
+    // without changing the profile of anyone
+    if keyboard_input.just_pressed(KeyCode::KeyN) {
+        let mut copy = settings.profiles[screen.profile].clone();
+        copy.name = format!("{} Copy", copy.name);
+        settings.profiles.push(copy);
+        screen.profile = settings.profiles.len() - 1;
+        settings.save();
+    }
+    if keyboard_input.just_pressed(KeyCode::Delete) {
+        let device = settings.profiles[screen.profile].device;
+        let same_device = settings
+            .profiles
+            .iter()
+            .filter(|profile| profile.device == device)
+            .count();
+        // Every device keeps at least one profile to join with
+        if same_device > 1 {
+            settings.profiles.remove(screen.profile);
+            screen.profile = screen.profile.min(settings.profiles.len() - 1);
+            settings.save();
+        }
+    }
+}
+
+/// Cycles through the axis presets of the device, the mouse can only aim
+fn next_axis(device: DeviceKind, current: AxisBinding, is_aim: bool) -> AxisBinding {
+    let presets: Vec<AxisBinding> = AxisBinding::presets(device)
+        .into_iter()
+        .filter(|preset| is_aim || *preset != AxisBinding::Mouse)
+        .collect();
+    let index = presets
+        .iter()
+        .position(|preset| *preset == current)
+        .map_or(0, |i| i + 1);
+    presets[index % presets.len()]
+}
+
+fn show_screen(
+    mut root_query: Query<(&mut Visibility, &Children), With<RebindText>>,
+    mut text_query: Query<&mut Text>,
+    screen: Res<RebindScreen>,
+    settings: Res<ControlSettings>,
+) {
+    if !screen.is_changed() && !settings.is_changed() {
+        return;
+    }
+    let Ok((mut visibility, children)) = root_query.get_single_mut() else {
+        return;
+    };
+
+    *visibility = if screen.open {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    let Some(profile) = settings.profiles.get(screen.profile) else {
+        return;
+    };
+
+    let mut lines = vec![
+        "Controls (F1 to close)".to_string(),
+        format!(
+            "< {} - {:?} > {}/{}",
+            profile.name,
+            profile.device,
+            screen.profile + 1,
+            settings.profiles.len()
+        ),
+        String::new(),
+    ];
+    for (index, row) in ROWS.iter().enumerate() {
+        let cursor = if index == screen.row { ">" } else { " " };
+        lines.push(format!("{cursor} {}: {}", row.name(), row.value(profile)));
+    }
+    lines.push(String::new());
+    lines.push(if screen.waiting {
+        format!(
+            "Press a button for {}, Backspace to cancel",
+            ROWS[screen.row].name()
+        )
+    } else {
+        "Up/Down select, Left/Right profile, Enter change, N copy, Delete remove".to_string()
+    });
+
+    for child in children.iter() {
+        if let Ok(mut text) = text_query.get_mut(*child) {
+            text.sections[0].value = lines.join("\n");
+        }
+    }
+}
@@ -1,10 +1,10 @@
 use bevy::prelude::*;
 use leafwing_input_manager::action_state::ActionState;
 
-use crate::sandbox::{particle_types::get_particle, sandbox::Sandbox};
+use crate::{sandbox::sandbox::Sandbox, schedule::GameplaySet};
 
 use super::{
-    grab::{Held, ParentObject},
+    grab::{place_rock, spill_particle, Blob, Held, ParentObject, RockParticle},
     Action, HeldObject,
 };
 
@@ -12,7 +12,7 @@ pub struct SetPlugin;
 
 impl Plugin for SetPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, set);
+        app.add_systems(FixedUpdate, set.in_set(GameplaySet::Set));
     }
 }
 
@@ -22,7 +22,8 @@ fn set(
     mut player_query: Query<(&ActionState<Action>, &mut HeldObject)>,
     parent_query: Query<Entity, (With<ParentObject>, With<Held>)>,
     children_query: Query<&Children>,
-    transform_query: Query<&GlobalTransform>,
+    rock_query: Query<(&GlobalTransform, &RockParticle)>,
+    blob_query: Query<(), With<Blob>>,
 ) {
     let mut sandbox = sandbox_query.single_mut();
 
@@ -34,23 +35,16 @@ fn set(
             continue;
         };
 
+        let is_blob = blob_query.contains(entity);
         for child in children_query.iter_descendants(entity) {
-            let Ok(position) = transform_query.get(child).map(|x| x.translation()) else {
+            let Ok((transform, rock)) = rock_query.get(child) else {
                 continue;
             };
-            let (grid_x, grid_y) = (
-                (position.x / 8.0).floor() as i32 + sandbox.width() as i32 / 2,
-                (position.y / 8.0).floor() as i32 + sandbox.height() as i32 / 2,
-            );
-
-            if !sandbox.out_of_bounds_i32(grid_x, grid_y) {
-                sandbox.set(
-                    grid_x as usize,
-                    grid_y as usize,
-                    Some(get_particle(
-                        crate::sandbox::particle_types::ParticleTypes::Dirt,
-                    )),
-                );
+
+            if is_blob {
+                spill_particle(&mut sandbox, transform.translation(), rock, Vec2::ZERO);
+            } else {
+                place_rock(&mut sandbox, transform.translation(), rock);
             }
         }
 
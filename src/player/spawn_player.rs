@@ -1,90 +1,159 @@
-use bevy::{
-    input::gamepad::{GamepadButtonChangedEvent, GamepadConnectionEvent},
-    prelude::*,
-};
+use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 use bevy_tnua::controller::TnuaControllerBundle;
 use bevy_tnua_rapier2d::{TnuaRapier2dIOBundle, TnuaRapier2dSensorShape};
-use leafwing_input_manager::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{
+    buoyancy::Buoyancy,
+    damage::{Invulnerable, SPAWNINVULNERABILITY},
+    match_flow::{MatchPhase, MatchScore, PlayerRecord},
+    rules::MatchRules,
+    sandbox::sandbox::Sandbox,
+    schedule::GameplaySet,
+    spawn_points::SpawnPoints,
+    state::GameState,
+};
 
-use super::{Action, PlayerBundle, PlayerHealth, UsedGamepads};
+use super::{
+    bending::{Bender, Element},
+    bindings::{ControlProfile, GrabControl},
+    bots::BotBrain,
+    devices::{InputDevice, JoinRequest, LeaveRequest, PlayerDevice},
+    Action, PlayerBundle, PlayerHealth, PlayerSlot, UsedDevices,
+};
 
 pub struct PlayerConnectionPlugin;
 
 impl Plugin for PlayerConnectionPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (spawn_player, despawn_on_disconnect));
+        app.add_systems(
+            FixedUpdate,
+            (
+                spawn_player.run_if(in_state(MatchPhase::InProgress)),
+                despawn_on_leave,
+            )
+                .chain()
+                .in_set(GameplaySet::Join),
+        )
+        .add_systems(OnExit(GameState::Playing), despawn_players);
     }
 }
 
 fn spawn_player(
     mut commands: Commands,
-    mut gamepad_button_events: EventReader<GamepadButtonChangedEvent>,
-    mut used_gamepads: ResMut<UsedGamepads>,
+    mut join_requests: EventReader<JoinRequest>,
+    mut used_devices: ResMut<UsedDevices>,
+    mut score: ResMut<MatchScore>,
+    rules: Res<MatchRules>,
+    sandbox_query: Query<(&Sandbox, &SpawnPoints)>,
+    player_query: Query<&Transform, With<PlayerHealth>>,
 ) {
-    for event in gamepad_button_events.read() {
-        if used_gamepads.gamepads.contains_key(&event.gamepad) {
+    let Ok((sandbox, spawn_points)) = sandbox_query.get_single() else {
+        return;
+    };
+    let mut players: Vec<Vec2> = player_query
+        .iter()
+        .map(|transform| transform.translation.truncate())
+        .collect();
+
+    for JoinRequest { device, profile } in join_requests.read() {
+        if used_devices.devices.contains_key(device) || !score.can_join(*device) {
             continue;
         }
 
-        println!("Join with gamepad {:?}", event.gamepad);
-        let mut map = InputMap::default()
-            .with_dual_axis(Action::Move, GamepadStick::LEFT)
-            .with_dual_axis(Action::Aim, GamepadStick::RIGHT)
-            .with(Action::Jump, GamepadButtonType::LeftTrigger2)
-            .with(Action::Grab, GamepadButtonType::RightTrigger2)
-            .with(Action::Set, GamepadButtonType::LeftTrigger)
-            .with(Action::Parry, GamepadButtonType::RightTrigger);
-        map.set_gamepad(event.gamepad);
-        let entity = commands
-            .spawn((
-                Name::new("Player"),
-                SpriteBundle {
-                    sprite: Sprite {
-                        color: bevy::color::palettes::basic::BLUE.into(),
-                        custom_size: Some(Vec2::new(30.0, 30.0)),
-                        ..default()
-                    },
-                    transform: Transform::from_translation(Vec3::new(0., 0., 0.1)),
+        println!("Join with {:?}", device);
+        let slot = score.join(*device, *profile, &rules);
+        let PlayerRecord {
+            profile, element, ..
+        } = score.players[slot];
+        let position = spawn_points.pick(sandbox, &players);
+        players.push(position);
+        let entity = spawn_device_player(&mut commands, *device, profile, element, slot, position);
+        used_devices.insert(*device, entity);
+    }
+}
+
+/// The input map is added by `apply_profiles` from the player's profile. Bots have no profile
+/// and press their actions themselves, remote players press the actions their client sends.
+pub fn spawn_device_player(
+    commands: &mut Commands,
+    device: InputDevice,
+    profile: usize,
+    element: Element,
+    slot: usize,
+    position: Vec2,
+) -> Entity {
+    let player = commands
+        .spawn((
+            Name::new("Player"),
+            SpriteBundle {
+                sprite: Sprite {
+                    color: PlayerSlot(slot).color().into(),
+                    custom_size: Some(Vec2::new(30.0, 30.0)),
                     ..default()
                 },
-                InputManagerBundle::<Action>::with_map(map),
-                Collider::ball(15.0),
-                RigidBody::Dynamic,
-                ExternalImpulse::default(),
-                ColliderMassProperties::Mass(20000.0),
-                TnuaControllerBundle::default(),
-                TnuaRapier2dSensorShape(Collider::capsule_x(15.0, 0.0)),
-                TnuaRapier2dIOBundle::default(),
-                CollisionGroups::new(Group::all(), Group::all().difference(Group::GROUP_1)),
-                LockedAxes::ROTATION_LOCKED,
-                ActiveEvents::COLLISION_EVENTS,
-                PlayerBundle {
-                    health: PlayerHealth(50000.0),
+                transform: Transform::from_translation(position.extend(0.1)),
+                ..default()
+            },
+            PlayerDevice(device),
+            Collider::ball(15.0),
+            Buoyancy {
+                radius: 15.0,
+                density: 2.5,
+            },
+            RigidBody::Dynamic,
+            ExternalImpulse::default(),
+            ColliderMassProperties::Mass(20000.0),
+            TnuaControllerBundle::default(),
+            TnuaRapier2dSensorShape(Collider::capsule_x(15.0, 0.0)),
+            TnuaRapier2dIOBundle::default(),
+            CollisionGroups::new(Group::all(), Group::all().difference(Group::GROUP_1)),
+            LockedAxes::ROTATION_LOCKED,
+            ActiveEvents::COLLISION_EVENTS,
+            PlayerBundle {
+                health: PlayerHealth(50000.0),
+                slot: PlayerSlot(slot),
+                bender: Bender {
+                    element,
                     ..default()
                 },
-            ))
-            .id();
+                invulnerable: Invulnerable {
+                    remaining: SPAWNINVULNERABILITY,
+                },
+                ..default()
+            },
+        ))
+        .id();
 
-        used_gamepads.gamepads.insert(event.gamepad, entity);
+    let mut player_commands = commands.entity(player);
+    player_commands.insert((ActionState::<Action>::default(), GrabControl::default()));
+    match device {
+        InputDevice::Bot { difficulty, .. } => {
+            player_commands.insert(BotBrain::new(difficulty));
+        }
+        InputDevice::Remote { .. } => {}
+        _ => {
+            player_commands.insert(ControlProfile(profile));
+        }
     }
+    player
 }
 
-fn despawn_on_disconnect(
+fn despawn_on_leave(
     mut commands: Commands,
-    mut gamepad_connection_events: EventReader<GamepadConnectionEvent>,
-    mut used_gamepads: ResMut<UsedGamepads>,
+    mut leave_requests: EventReader<LeaveRequest>,
+    mut used_devices: ResMut<UsedDevices>,
 ) {
-    for event in gamepad_connection_events.read() {
-        if !event.disconnected() {
-            continue;
+    for LeaveRequest { device } in leave_requests.read() {
+        if let Some(entity) = used_devices.devices.remove(device) {
+            commands.entity(entity).despawn();
         }
+    }
+}
 
-        if let Some((gamepad, entity)) = used_gamepads.gamepads.get_key_value(&event.gamepad) {
-            commands.entity(*entity).despawn();
-
-            let gamepad = *gamepad;
-            used_gamepads.gamepads.remove(&gamepad);
-        }
+fn despawn_players(mut commands: Commands, mut used_devices: ResMut<UsedDevices>) {
+    for (_, entity) in used_devices.devices.drain() {
+        commands.entity(entity).despawn();
     }
 }
@@ -0,0 +1,186 @@
+use bevy::prelude::*;
+use bevy_rapier2d::plugin::RapierContext;
+
+use crate::{
+    damage::{DamageEvent, DamageKind},
+    sandbox::collider::{AcidSensor, FireSensor, WaterSensor},
+    schedule::GameplaySet,
+};
+
+pub struct StatusEffectPlugin;
+
+impl Plugin for StatusEffectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (expose_to_sensors, tick_status_effects)
+                .chain()
+                .in_set(GameplaySet::Status),
+        );
+    }
+}
+
+/// How an effect behaves while a player is exposed to it and after they leave
+pub struct EffectRules {
+    /// Seconds the effect lasts after the last exposure
+    pub duration: f32,
+    pub max_stacks: u32,
+    /// Seconds of continuous exposure needed for another stack
+    pub stack_interval: f32,
+    /// Health lost per second for every stack
+    pub damage_per_stack: f32,
+}
+
+pub const BURNING: EffectRules = EffectRules {
+    duration: 3.0,
+    max_stacks: 3,
+    stack_interval: 1.0,
+    damage_per_stack: 1000.0,
+};
+
+pub const CORRODING: EffectRules = EffectRules {
+    duration: 4.0,
+    max_stacks: 5,
+    stack_interval: 0.5,
+    damage_per_stack: 400.0,
+};
+
+pub const WET: EffectRules = EffectRules {
+    duration: 2.0,
+    max_stacks: 1,
+    stack_interval: f32::INFINITY,
+    damage_per_stack: 0.0,
+};
+
+/// Movement speed while wet
+const WET_SPEED_MULTIPLIER: f32 = 0.6;
+
+/// An effect is active while it has at least one stack
+#[derive(Default, Clone, Copy)]
+pub struct StatusEffect {
+    pub remaining: f32,
+    pub stacks: u32,
+    exposure: f32,
+}
+
+impl StatusEffect {
+    pub fn is_active(&self) -> bool {
+        self.stacks > 0
+    }
+
+    /// Refreshes the duration and adds a stack for every `stack_interval` of exposure
+    fn expose(&mut self, rules: &EffectRules, delta: f32) {
+        if self.is_active() {
+            self.exposure += delta;
+            if self.exposure >= rules.stack_interval {
+                self.exposure -= rules.stack_interval;
+                self.stacks = (self.stacks + 1).min(rules.max_stacks);
+            }
+        } else {
+            self.stacks = 1;
+            self.exposure = 0.0;
+        }
+        self.remaining = rules.duration;
+    }
+
+    fn tick(&mut self, delta: f32) {
+        if !self.is_active() {
+            return;
+        }
+
+        self.remaining -= delta;
+        if self.remaining <= 0.0 {
+            self.clear();
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[derive(Component, Default, Clone)]
+pub struct StatusEffects {
+    pub burning: StatusEffect,
+    pub corroding: StatusEffect,
+    pub wet: StatusEffect,
+}
+
+impl StatusEffects {
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.wet.is_active() {
+            WET_SPEED_MULTIPLIER
+        } else {
+            1.0
+        }
+    }
+}
+
+fn expose_to_sensors(
+    mut query: Query<(Entity, &mut StatusEffects)>,
+    acid_query: Query<(), With<AcidSensor>>,
+    fire_query: Query<(), With<FireSensor>>,
+    water_query: Query<(), With<WaterSensor>>,
+    context: Res<RapierContext>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+
+    for (entity, mut effects) in query.iter_mut() {
+        let (mut in_acid, mut in_fire, mut in_water) = (false, false, false);
+        for (e1, e2, intersecting) in context.intersections_with(entity) {
+            if !intersecting {
+                continue;
+            }
+
+            let other = if e1 == entity { e2 } else { e1 };
+            in_acid |= acid_query.contains(other);
+            in_fire |= fire_query.contains(other);
+            in_water |= water_query.contains(other);
+        }
+
+        if in_water {
+            effects.wet.expose(&WET, delta);
+        }
+        // Water puts out fire and keeps it from catching again until the player dries off
+        if effects.wet.is_active() {
+            effects.burning.clear();
+        } else if in_fire {
+            effects.burning.expose(&BURNING, delta);
+        }
+        if in_acid {
+            effects.corroding.expose(&CORRODING, delta);
+        }
+    }
+}
+
+fn tick_status_effects(
+    mut query: Query<(Entity, &mut StatusEffects)>,
+    mut damage_events: EventWriter<DamageEvent>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+
+    for (entity, mut effects) in query.iter_mut() {
+        for (effect, rules, kind) in [
+            (&effects.burning, &BURNING, DamageKind::Burning),
+            (&effects.corroding, &CORRODING, DamageKind::Corrosion),
+        ] {
+            if !effect.is_active() {
+                continue;
+            }
+
+            damage_events.send(DamageEvent {
+                target: entity,
+                source: None,
+                amount: effect.stacks as f32 * rules.damage_per_stack * delta,
+                knockback: Vec2::ZERO,
+                kind,
+            });
+        }
+
+        effects.burning.tick(delta);
+        effects.corroding.tick(delta);
+        effects.wet.tick(delta);
+    }
+}
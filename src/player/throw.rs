@@ -0,0 +1,133 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::{RapierConfiguration, ReadMassProperties};
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::sandbox::{
+    particle::{MovementType, Particle},
+    raycast::ParticleFilter,
+    sandbox::Sandbox,
+};
+
+use super::{
+    grab::{mass_factor, Held},
+    Action, AimDirection, HeldObject, PlayerSlot, ThrowCharge,
+};
+
+/// Seconds throw has to be held for the fastest throw
+const THROW_CHARGE_TIME: f32 = 0.8;
+/// Speed in pixels per second of a throw that wasn't charged
+const MIN_THROW_SPEED: f32 = 400.0;
+const MAX_THROW_SPEED: f32 = 1600.0;
+/// Bodies up to this mass, about 16 cells, are thrown at full speed
+const LIGHT_THROW_MASS: f32 = 0.06;
+/// Seconds between the points of the previewed arc
+const PREVIEW_STEP: f32 = 1.0 / 60.0;
+/// The preview stops after this many seconds without hitting terrain
+const PREVIEW_DURATION: f32 = 2.0;
+
+pub struct ThrowPlugin;
+
+impl Plugin for ThrowPlugin {
+    fn build(&self, app: &mut App) {
+        // Throws are charged in the grab chain, right before they are let go
+        app.add_systems(Update, preview_throw);
+    }
+}
+
+/// Speed of a throw along the aim, slower for heavier bodies
+pub fn throw_speed(charge: f32, properties: &ReadMassProperties) -> f32 {
+    let speed = MIN_THROW_SPEED.lerp(MAX_THROW_SPEED, charge.clamp(0.0, 1.0));
+    speed * (LIGHT_THROW_MASS.sqrt() / mass_factor(properties)).min(1.0)
+}
+
+pub fn charge_throw(
+    mut query: Query<(&ActionState<Action>, &HeldObject, &mut ThrowCharge)>,
+    time: Res<Time>,
+) {
+    for (action, held, mut charge) in query.iter_mut() {
+        if held.0.is_none() {
+            charge.0 = 0.0;
+        } else if action.pressed(&Action::Throw) {
+            charge.0 = (charge.0 + time.delta_seconds() / THROW_CHARGE_TIME).min(1.0);
+        }
+    }
+}
+
+fn is_terrain(particle: &Particle) -> bool {
+    matches!(
+        particle.movement_type,
+        MovementType::Solid | MovementType::Powder
+    )
+}
+
+/// Draws the arc the held body flies along if the throw was let go now, up to where it hits
+/// terrain
+fn preview_throw(
+    mut gizmos: Gizmos,
+    player_query: Query<(
+        &ActionState<Action>,
+        &AimDirection,
+        &HeldObject,
+        &ThrowCharge,
+        &PlayerSlot,
+    )>,
+    held_query: Query<(&Transform, &ReadMassProperties), With<Held>>,
+    sandbox_query: Query<&Sandbox>,
+    config: Res<RapierConfiguration>,
+) {
+    let Ok(sandbox) = sandbox_query.get_single() else {
+        return;
+    };
+
+    for (action, aim, held, charge, slot) in player_query.iter() {
+        if !action.pressed(&Action::Throw) {
+            continue;
+        }
+        let Some((transform, properties)) = held.0.and_then(|entity| held_query.get(entity).ok())
+        else {
+            continue;
+        };
+
+        let mut position = transform.translation.truncate();
+        let mut velocity = aim.0 * throw_speed(charge.0, properties);
+        let mut points = vec![position];
+        let mut contact = None;
+        for _ in 0..(PREVIEW_DURATION / PREVIEW_STEP) as usize {
+            velocity += config.gravity * PREVIEW_STEP;
+            let from = sandbox.world_to_grid(position);
+            position += velocity * PREVIEW_STEP;
+            points.push(position);
+
+            // Rays between the points so fast throws don't skip over thin terrain
+            let to = sandbox.world_to_grid(position);
+            if let Some(hit) = sandbox.raycast(
+                from,
+                to - from,
+                from.distance(to),
+                ParticleFilter::Custom(is_terrain),
+            ) {
+                contact = Some(hit);
+                break;
+            }
+            let IVec2 { x, y } = sandbox.world_to_cell(position);
+            if sandbox.out_of_bounds_i32(x, y) {
+                break;
+            }
+        }
+
+        let color = slot.color();
+        gizmos.linestrip_2d(points, color);
+        if let Some(hit) = contact {
+            // Marks the hit cell and, in the color of what it lands on, which of its faces the
+            // body lands on
+            let center = sandbox.cell_to_world(hit.cell.x as i32, hit.cell.y as i32);
+            let (r, g, b, a) = hit.particle.color;
+            gizmos.circle_2d(center, 6.0, color);
+            gizmos.line_2d(
+                center,
+                center + hit.normal.as_vec2() * 12.0,
+                Color::srgba_u8(r, g, b, a),
+            );
+        }
+    }
+}
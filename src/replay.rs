@@ -0,0 +1,366 @@
+use std::fs;
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    match_flow::{clear_players, MatchPhase, MatchScore},
+    player::{
+        bindings::GrabControl,
+        components::PlayerSlot,
+        devices::{JoinRequest, LeaveRequest},
+        Action, PlayerInput,
+    },
+    rules::MatchRules,
+    sandbox::{
+        chunk::expand, editor::EditedLayout, particle::Particle, rng::SimulationRng,
+        sandbox::Sandbox, simulation::SimulationControl,
+    },
+    schedule::GameplaySet,
+    state::ResetArena,
+};
+
+/// Replays are recorded to and played from this file in the working directory
+const REPLAY_PATH: &str = "replay.ron";
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Replayer::default())
+            .add_systems(
+                Update,
+                (toggle_recording, start_playback).run_if(in_state(MatchPhase::InProgress)),
+            )
+            .add_systems(OnExit(MatchPhase::InProgress), stop_replay)
+            .add_systems(
+                FixedUpdate,
+                (
+                    (clear_players, begin_replay)
+                        .chain()
+                        .run_if(replay_starting),
+                    play_events.run_if(is_replaying),
+                )
+                    .chain()
+                    .in_set(GameplaySet::Replay),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    play_inputs.run_if(is_replaying).in_set(GameplaySet::Input),
+                    record_step.in_set(GameplaySet::Record),
+                ),
+            );
+    }
+}
+
+/// Everything needed to play a match again from its start: the rules, the seed of the sandbox,
+/// the edited layout of the arena and what happened in every fixed step
+#[derive(Serialize, Deserialize, Default)]
+struct Replay {
+    seed: u64,
+    rules: MatchRules,
+    /// Replays play in the arena they were recorded in, whatever level is open when playing them
+    #[serde(default)]
+    layout: Option<ReplayLayout>,
+    steps: Vec<ReplayStep>,
+}
+
+/// An edited layout with the particles of its chunks run-length encoded, like unloaded chunks
+/// keep them
+#[derive(Serialize, Deserialize)]
+struct ReplayLayout {
+    x_chunks: usize,
+    y_chunks: usize,
+    chunk_width: usize,
+    chunk_height: usize,
+    chunks: Vec<Vec<(u16, Option<Particle>)>>,
+}
+
+impl ReplayLayout {
+    fn new(sandbox: &Sandbox) -> Self {
+        Self {
+            x_chunks: sandbox.x_chunks(),
+            y_chunks: sandbox.y_chunks(),
+            chunk_width: sandbox.chunk_width(),
+            chunk_height: sandbox.chunk_height(),
+            chunks: sandbox
+                .get_all_chunks()
+                .iter()
+                .map(|chunk| chunk.runs())
+                .collect(),
+        }
+    }
+
+    fn sandbox(&self) -> Sandbox {
+        let mut sandbox = Sandbox::new(
+            self.x_chunks,
+            self.y_chunks,
+            self.chunk_width,
+            self.chunk_height,
+        );
+        for (index, runs) in self.chunks.iter().enumerate() {
+            sandbox.replace_chunk(index, expand(runs));
+        }
+        sandbox
+    }
+}
+
+impl Replay {
+    fn load() -> Option<Self> {
+        let contents = match fs::read_to_string(REPLAY_PATH) {
+            Ok(contents) => contents,
+            Err(error) => {
+                println!("Couldn't open {REPLAY_PATH}: {error}");
+                return None;
+            }
+        };
+
+        match ron::from_str(&contents) {
+            Ok(replay) => Some(replay),
+            Err(error) => {
+                println!("Couldn't read {REPLAY_PATH}: {error}");
+                None
+            }
+        }
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string(self)
+            .map_err(|error| error.to_string())
+            .and_then(|contents| {
+                fs::write(REPLAY_PATH, contents).map_err(|error| error.to_string())
+            });
+        match result {
+            Ok(()) => println!("Saved {} steps to {REPLAY_PATH}", self.steps.len()),
+            Err(error) => println!("Couldn't save {REPLAY_PATH}: {error}"),
+        }
+    }
+}
+
+/// What happened in one fixed step
+#[derive(Serialize, Deserialize, Default)]
+struct ReplayStep {
+    /// The arena was put back into its edited layout
+    reset: bool,
+    joins: Vec<JoinRequest>,
+    leaves: Vec<LeaveRequest>,
+    /// Slot and actions of every player
+    inputs: Vec<(usize, PlayerInput)>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum ReplayMode {
+    #[default]
+    Off,
+    Recording,
+    Playing,
+}
+
+/// The replay being recorded or played
+#[derive(Resource, Default)]
+pub struct Replayer {
+    mode: ReplayMode,
+    replay: Replay,
+    /// The arena and the players are reset on the next fixed step, which is the first step of
+    /// the replay
+    starting: bool,
+    /// Index of the step being played
+    step: usize,
+}
+
+pub fn is_replaying(replayer: Res<Replayer>) -> bool {
+    replayer.mode == ReplayMode::Playing
+}
+
+fn replay_starting(replayer: Res<Replayer>) -> bool {
+    replayer.starting
+}
+
+/// Recordings start over with the current rules and a new seed, everyone joins again
+fn toggle_recording(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut replayer: ResMut<Replayer>,
+    rules: Res<MatchRules>,
+    layout: Res<EditedLayout>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F8) {
+        return;
+    }
+
+    match replayer.mode {
+        ReplayMode::Off => {
+            *replayer = Replayer {
+                mode: ReplayMode::Recording,
+                replay: Replay {
+                    seed: rand::random(),
+                    rules: *rules,
+                    layout: layout.get().map(ReplayLayout::new),
+                    steps: Vec::new(),
+                },
+                starting: true,
+                step: 0,
+            };
+            println!("Recording, press F8 again to stop");
+        }
+        ReplayMode::Recording => {
+            replayer.replay.save();
+            replayer.mode = ReplayMode::Off;
+        }
+        ReplayMode::Playing => {}
+    }
+}
+
+fn start_playback(keyboard_input: Res<ButtonInput<KeyCode>>, mut replayer: ResMut<Replayer>) {
+    if !keyboard_input.just_pressed(KeyCode::F9) || replayer.mode != ReplayMode::Off {
+        return;
+    }
+    let Some(replay) = Replay::load() else {
+        return;
+    };
+
+    println!("Playing {} steps from {REPLAY_PATH}", replay.steps.len());
+    *replayer = Replayer {
+        mode: ReplayMode::Playing,
+        replay,
+        starting: true,
+        step: 0,
+    };
+}
+
+/// Recordings end with the match, so they can show how it was decided
+fn stop_replay(mut replayer: ResMut<Replayer>) {
+    match replayer.mode {
+        ReplayMode::Recording => replayer.replay.save(),
+        ReplayMode::Playing => println!("Replay stopped"),
+        ReplayMode::Off => return,
+    }
+    replayer.mode = ReplayMode::Off;
+    replayer.starting = false;
+}
+
+/// Puts the match into the same state for recording and playing. Runs after `clear_players`.
+/// Played replays replace the edited layout, so the arena resets into the recorded one.
+fn begin_replay(
+    mut replayer: ResMut<Replayer>,
+    mut rules: ResMut<MatchRules>,
+    mut control: ResMut<SimulationControl>,
+    mut score: ResMut<MatchScore>,
+    mut rng: ResMut<SimulationRng>,
+    mut layout: ResMut<EditedLayout>,
+    mut events: EventWriter<ResetArena>,
+) {
+    replayer.starting = false;
+    *rng = SimulationRng::seeded(replayer.replay.seed);
+    *rules = replayer.replay.rules;
+    if let Some(recorded) = &replayer.replay.layout {
+        layout.save(&recorded.sandbox());
+    }
+    control.reset_clock();
+    *score = MatchScore::default();
+    events.send(ResetArena);
+}
+
+fn play_events(
+    replayer: Res<Replayer>,
+    mut reset_events: EventWriter<ResetArena>,
+    mut join_requests: EventWriter<JoinRequest>,
+    mut leave_requests: EventWriter<LeaveRequest>,
+) {
+    let Some(step) = replayer.replay.steps.get(replayer.step) else {
+        return;
+    };
+
+    if step.reset {
+        reset_events.send(ResetArena);
+    }
+    join_requests.send_batch(step.joins.iter().copied());
+    leave_requests.send_batch(step.leaves.iter().copied());
+}
+
+/// Replayed players have no input map, so only the replay presses their actions
+fn play_inputs(
+    mut replayer: ResMut<Replayer>,
+    mut player_query: Query<(&PlayerSlot, &mut ActionState<Action>, &mut GrabControl)>,
+) {
+    let Some(step) = replayer.replay.steps.get(replayer.step) else {
+        println!("Replay finished");
+        replayer.mode = ReplayMode::Off;
+        return;
+    };
+
+    for (slot, mut action, mut grab_control) in player_query.iter_mut() {
+        if let Some((_, input)) = step
+            .inputs
+            .iter()
+            .find(|(input_slot, _)| *input_slot == slot.0)
+        {
+            input.apply(&mut action, &mut grab_control);
+        }
+    }
+    replayer.step += 1;
+}
+
+/// Reads the events even while not recording, so old events don't end up in a new recording
+fn record_step(
+    mut replayer: ResMut<Replayer>,
+    player_query: Query<(&PlayerSlot, &ActionState<Action>, &GrabControl)>,
+    mut reset_events: EventReader<ResetArena>,
+    mut join_requests: EventReader<JoinRequest>,
+    mut leave_requests: EventReader<LeaveRequest>,
+) {
+    let reset = reset_events.read().count() > 0;
+    let joins = join_requests.read().copied().collect();
+    let leaves = leave_requests.read().copied().collect();
+    if replayer.mode != ReplayMode::Recording {
+        return;
+    }
+
+    let inputs = player_query
+        .iter()
+        .map(|(slot, action, grab_control)| (slot.0, PlayerInput::read(action, grab_control)))
+        .collect();
+
+    replayer.replay.steps.push(ReplayStep {
+        reset,
+        joins,
+        leaves,
+        inputs,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sandbox::particle_types::{get_particle, ParticleTypes};
+
+    #[test]
+    fn layout_keeps_every_particle() {
+        let mut rng = SimulationRng::seeded(0);
+        let mut sandbox = Sandbox::new(3, 2, 8, 8);
+        for (x, y, material) in [
+            (1, 1, ParticleTypes::Stone),
+            (9, 3, ParticleTypes::Grass),
+            (20, 12, ParticleTypes::Water),
+        ] {
+            sandbox.set(x, y, Some(get_particle(material, &mut rng)));
+        }
+        sandbox.unload_chunk(5);
+
+        // Through the file format, like a replay that was saved and loaded again
+        let layout = ReplayLayout::new(&sandbox);
+        let layout: ReplayLayout = ron::from_str(&ron::ser::to_string(&layout).unwrap()).unwrap();
+        let restored = layout.sandbox();
+
+        assert_eq!(restored.width(), sandbox.width());
+        assert_eq!(restored.height(), sandbox.height());
+        for (chunk, saved) in restored
+            .get_all_chunks()
+            .iter()
+            .zip(sandbox.get_all_chunks())
+        {
+            assert!(chunk.particles() == saved.particles());
+        }
+    }
+}
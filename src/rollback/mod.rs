@@ -0,0 +1,305 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    damage::Invulnerable,
+    islands::PendingIslands,
+    match_flow::MatchScore,
+    player::{
+        bending::Bender,
+        bindings::GrabControl,
+        components::{
+            AimDirection, DamagePercent, ExtraJumps, HeldObject, PlayerHealth, Radius, ThrowCharge,
+        },
+        parry::Parry,
+        status::StatusEffects,
+    },
+    sandbox::{rng::SimulationRng, sandbox::Sandbox, simulation::SimulationControl},
+};
+
+use self::physics::PhysicsSnapshot;
+
+mod physics;
+mod verify;
+
+pub use verify::verify_rollback;
+
+type PlayerData = (
+    &'static AimDirection,
+    &'static Radius,
+    &'static Parry,
+    &'static ExtraJumps,
+    &'static HeldObject,
+    &'static ThrowCharge,
+    &'static PlayerHealth,
+    &'static DamagePercent,
+    &'static StatusEffects,
+    &'static Invulnerable,
+    &'static GrabControl,
+    &'static Bender,
+);
+
+/// Everything a fixed step changes, taken between two steps. Restoring it rolls the match back
+/// to that step. Taking one is cheap, the sandbox shares its chunks with the snapshot and only
+/// copies the chunks that change after it.
+pub struct Snapshot {
+    sandbox: Option<Sandbox>,
+    rng: Option<SimulationRng>,
+    control: Option<SimulationControl>,
+    score: Option<MatchScore>,
+    islands: Option<PendingIslands>,
+    physics: PhysicsSnapshot,
+    bodies: Vec<BodySnapshot>,
+}
+
+/// A rigid body that isn't fixed in place
+struct BodySnapshot {
+    entity: Entity,
+    handle: Option<RapierRigidBodyHandle>,
+    transform: Transform,
+    /// Of the body and its children, which are placed by the transform propagation of the
+    /// next physics step
+    global_transforms: Vec<(Entity, GlobalTransform)>,
+    velocity: Option<Velocity>,
+    player: Option<PlayerSnapshot>,
+}
+
+struct PlayerSnapshot {
+    aim: AimDirection,
+    radius: Radius,
+    parry: Parry,
+    jumps: ExtraJumps,
+    held: HeldObject,
+    throw_charge: ThrowCharge,
+    health: PlayerHealth,
+    percent: DamagePercent,
+    status_effects: StatusEffects,
+    invulnerable: Invulnerable,
+    grab_control: GrabControl,
+    bender: Bender,
+}
+
+impl Snapshot {
+    pub fn save(world: &mut World) -> Self {
+        let sandbox = world.query::<&Sandbox>().get_single(world).ok().cloned();
+
+        let bodies = world
+            .query::<(
+                Entity,
+                &RigidBody,
+                Option<&RapierRigidBodyHandle>,
+                &Transform,
+                Option<&Children>,
+                Option<&Velocity>,
+                Option<PlayerData>,
+            )>()
+            .iter(world)
+            .filter(|(_, body, ..)| **body != RigidBody::Fixed)
+            .map(
+                |(entity, _, handle, transform, children, velocity, player)| BodySnapshot {
+                    entity,
+                    handle: handle.copied(),
+                    transform: *transform,
+                    global_transforms: global_transforms(world, entity, children),
+                    velocity: velocity.copied(),
+                    player: player.map(
+                        |(
+                            aim,
+                            radius,
+                            parry,
+                            jumps,
+                            held,
+                            throw_charge,
+                            health,
+                            percent,
+                            status_effects,
+                            invulnerable,
+                            grab_control,
+                            bender,
+                        )| PlayerSnapshot {
+                            aim: aim.clone(),
+                            radius: radius.clone(),
+                            parry: parry.clone(),
+                            jumps: jumps.clone(),
+                            held: held.clone(),
+                            throw_charge: throw_charge.clone(),
+                            health: health.clone(),
+                            percent: percent.clone(),
+                            status_effects: status_effects.clone(),
+                            invulnerable: invulnerable.clone(),
+                            grab_control: grab_control.clone(),
+                            bender: bender.clone(),
+                        },
+                    ),
+                },
+            )
+            .collect();
+
+        Self {
+            sandbox,
+            rng: world.get_resource::<SimulationRng>().cloned(),
+            control: world.get_resource::<SimulationControl>().cloned(),
+            score: world.get_resource::<MatchScore>().cloned(),
+            islands: world.get_resource::<PendingIslands>().cloned(),
+            physics: PhysicsSnapshot::save(world),
+            bodies,
+        }
+    }
+
+    /// Puts the snapshot back into the world, bodies spawned after the snapshot are despawned.
+    /// Bodies despawned after the snapshot can't be brought back, they are taken out of the
+    /// physics and the rollback isn't exact anymore. Returns how many of them are missing.
+    /// The state of the character controllers isn't saved.
+    pub fn restore(&self, world: &mut World) -> usize {
+        if let Some(sandbox) = &self.sandbox {
+            if let Ok(mut current) = world.query::<&mut Sandbox>().get_single_mut(world) {
+                *current = sandbox.clone();
+            }
+        }
+        if let Some(rng) = &self.rng {
+            world.insert_resource(rng.clone());
+        }
+        if let Some(control) = &self.control {
+            world.insert_resource(control.clone());
+        }
+        if let Some(score) = &self.score {
+            world.insert_resource(score.clone());
+        }
+        if let Some(islands) = &self.islands {
+            world.insert_resource(islands.clone());
+        }
+
+        let spawned_since: Vec<Entity> = world
+            .query::<(Entity, &RigidBody)>()
+            .iter(world)
+            .filter(|(entity, body)| {
+                **body != RigidBody::Fixed
+                    && !self.bodies.iter().any(|saved| saved.entity == *entity)
+            })
+            .map(|(entity, _)| entity)
+            .collect();
+        for entity in spawned_since {
+            world.entity_mut(entity).despawn_recursive();
+        }
+
+        let mut missing = vec![];
+        for body in self.bodies.iter() {
+            let Some(mut entity) = world.get_entity_mut(body.entity) else {
+                missing.push(body);
+                continue;
+            };
+
+            // The physics gets its velocity from the restored physics state. The changed
+            // transform places the children in the next step, until then they keep their saved
+            // global transforms.
+            entity.insert(body.transform);
+            if let (Some(velocity), Some(mut current)) =
+                (body.velocity, entity.get_mut::<Velocity>())
+            {
+                *current.bypass_change_detection() = velocity;
+            }
+            if let Some(player) = &body.player {
+                entity.insert((
+                    player.aim.clone(),
+                    player.radius.clone(),
+                    player.parry.clone(),
+                    player.jumps.clone(),
+                    player.held.clone(),
+                    player.throw_charge.clone(),
+                    player.health.clone(),
+                    player.percent.clone(),
+                    player.status_effects.clone(),
+                    player.invulnerable.clone(),
+                    player.grab_control.clone(),
+                    player.bender.clone(),
+                ));
+            }
+
+            for (entity, global) in body.global_transforms.iter() {
+                if let Some(mut current) = world.get_mut::<GlobalTransform>(*entity) {
+                    *current.bypass_change_detection() = *global;
+                }
+            }
+        }
+
+        let handles: Vec<_> = missing.iter().filter_map(|body| body.handle).collect();
+        self.physics.restore(world, &handles);
+        missing.len()
+    }
+}
+
+fn global_transforms(
+    world: &World,
+    entity: Entity,
+    children: Option<&Children>,
+) -> Vec<(Entity, GlobalTransform)> {
+    std::iter::once(entity)
+        .chain(children.into_iter().flatten().copied())
+        .filter_map(|entity| Some((entity, *world.get::<GlobalTransform>(entity)?)))
+        .collect()
+}
+
+/// Hash of the grid and of every body that isn't fixed in place. Two worlds with the same
+/// checksum play out the same way. Bodies are hashed without their entity, so bodies spawned
+/// again after a rollback count as the same.
+pub fn checksum(world: &mut World) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    if let Ok(sandbox) = world.query::<&Sandbox>().get_single(world) {
+        sandbox.get_all_chunks().hash(&mut hasher);
+    }
+
+    let mut bodies: Vec<u64> = world
+        .query::<(
+            &RigidBody,
+            &Transform,
+            Option<&Velocity>,
+            Option<PlayerData>,
+        )>()
+        .iter(world)
+        .filter(|(body, ..)| **body != RigidBody::Fixed)
+        .map(|(_, transform, velocity, player)| {
+            let mut floats = vec![];
+            floats.extend(transform.translation.to_array());
+            floats.extend(transform.rotation.to_array());
+            if let Some(velocity) = velocity {
+                floats.extend(velocity.linvel.to_array());
+                floats.push(velocity.angvel);
+            }
+            if let Some((
+                aim,
+                radius,
+                parry,
+                jumps,
+                held,
+                throw_charge,
+                health,
+                percent,
+                _,
+                invulnerable,
+                _,
+                bender,
+            )) = player
+            {
+                floats.extend(aim.0.to_array());
+                floats.extend([radius.current, parry.radius(), health.0, percent.0]);
+                floats.extend([invulnerable.remaining, jumps.current as f32]);
+                floats.push(if held.0.is_some() { 1.0 } else { 0.0 });
+                floats.push(throw_charge.0);
+                floats.extend([bender.element as u8 as f32, bender.cooldown]);
+            }
+
+            let mut hasher = DefaultHasher::new();
+            for float in floats {
+                float.to_bits().hash(&mut hasher);
+            }
+            hasher.finish()
+        })
+        .collect();
+    // Query order depends on the entities, sorting the hashes doesn't
+    bodies.sort_unstable();
+    bodies.hash(&mut hasher);
+
+    hasher.finish()
+}
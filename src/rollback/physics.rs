@@ -0,0 +1,176 @@
+use bevy::{ecs::system::RunSystemOnce, prelude::*};
+use bevy_rapier2d::{
+    plugin::systems::writeback_rigid_bodies,
+    prelude::*,
+    rapier::prelude::{
+        CCDSolver, ColliderSet, DefaultBroadPhase, ImpulseJointSet, IntegrationParameters,
+        IslandManager, MultibodyJointSet, NarrowPhase, QueryPipeline, RigidBodySet,
+    },
+};
+
+use crate::sandbox::collider::{ColliderSettings, ColliderStorage};
+
+/// The Rapier side of a `Snapshot`: bodies, colliders, contacts with their warm starting
+/// impulses, sleeping islands and the terrain colliders
+pub struct PhysicsSnapshot {
+    state: Option<PhysicsState>,
+    storage: Option<ColliderStorage>,
+    terrain: Vec<TerrainCollider>,
+}
+
+/// The public fields of `RapierContext`. Replacing the whole context would also replace
+/// bevy_rapier's maps from entities to bodies and colliders, which have to keep matching the
+/// entities that are alive.
+#[derive(Clone)]
+struct PhysicsState {
+    islands: IslandManager,
+    broad_phase: DefaultBroadPhase,
+    narrow_phase: NarrowPhase,
+    bodies: RigidBodySet,
+    colliders: ColliderSet,
+    impulse_joints: ImpulseJointSet,
+    multibody_joints: MultibodyJointSet,
+    ccd_solver: CCDSolver,
+    query_pipeline: QueryPipeline,
+    integration_parameters: IntegrationParameters,
+}
+
+/// Terrain collider entities stay with their region, only their shape changes
+struct TerrainCollider {
+    entity: Entity,
+    shape: Collider,
+    disabled: bool,
+}
+
+impl PhysicsSnapshot {
+    pub fn save(world: &World) -> Self {
+        let state = world
+            .get_resource::<RapierContext>()
+            .map(|context| PhysicsState {
+                islands: context.islands.clone(),
+                broad_phase: context.broad_phase.clone(),
+                narrow_phase: context.narrow_phase.clone(),
+                bodies: context.bodies.clone(),
+                colliders: context.colliders.clone(),
+                impulse_joints: context.impulse_joints.clone(),
+                multibody_joints: context.multibody_joints.clone(),
+                ccd_solver: context.ccd_solver.clone(),
+                query_pipeline: context.query_pipeline.clone(),
+                integration_parameters: context.integration_parameters,
+            });
+
+        let storage = world.get_resource::<ColliderStorage>().cloned();
+        let terrain = storage
+            .iter()
+            .flat_map(|storage| storage.colliders.iter().flatten())
+            .filter_map(|(_, entity)| {
+                let collider = world.get_entity(*entity)?;
+                Some(TerrainCollider {
+                    entity: *entity,
+                    shape: collider.get::<Collider>()?.clone(),
+                    disabled: collider.contains::<ColliderDisabled>(),
+                })
+            })
+            .collect();
+
+        Self {
+            state,
+            storage,
+            terrain,
+        }
+    }
+
+    /// Runs after the bodies were restored. Bodies that were despawned after the snapshot are
+    /// taken out of the physics again, their handles are in `missing`.
+    pub fn restore(&self, world: &mut World, missing: &[RapierRigidBodyHandle]) {
+        self.restore_terrain(world);
+
+        let Some(state) = &self.state else {
+            return;
+        };
+        let Some(mut context) = world.get_resource_mut::<RapierContext>() else {
+            return;
+        };
+        let context = context.bypass_change_detection();
+        let state = state.clone();
+        context.islands = state.islands;
+        context.broad_phase = state.broad_phase;
+        context.narrow_phase = state.narrow_phase;
+        context.bodies = state.bodies;
+        context.colliders = state.colliders;
+        context.impulse_joints = state.impulse_joints;
+        context.multibody_joints = state.multibody_joints;
+        context.ccd_solver = state.ccd_solver;
+        context.query_pipeline = state.query_pipeline;
+        context.integration_parameters = state.integration_parameters;
+
+        for handle in missing {
+            context.bodies.remove(
+                handle.0,
+                &mut context.islands,
+                &mut context.colliders,
+                &mut context.impulse_joints,
+                &mut context.multibody_joints,
+                true,
+            );
+        }
+
+        // Writes the restored bodies back like a physics step does, so bevy_rapier knows that
+        // their transforms came from the physics and doesn't push them into it again
+        world.run_system_once(writeback_rigid_bodies);
+    }
+
+    /// Puts the shapes back on the terrain colliders, which already match the restored physics
+    /// state, so they bypass change detection. Colliders spawned after the snapshot are
+    /// despawned.
+    fn restore_terrain(&self, world: &mut World) {
+        let Some(storage) = &self.storage else {
+            return;
+        };
+
+        let saved = |entity: &Entity| self.terrain.iter().any(|saved| saved.entity == *entity);
+        let spawned_since: Vec<Entity> = world
+            .get_resource::<ColliderStorage>()
+            .iter()
+            .flat_map(|current| current.colliders.iter().flatten())
+            .map(|(_, entity)| *entity)
+            .filter(|entity| !saved(entity))
+            .collect();
+        for entity in spawned_since {
+            world.despawn(entity);
+        }
+
+        let mut storage = storage.clone();
+        let mut despawned = false;
+        for collider in self.terrain.iter() {
+            let Some(mut entity) = world.get_entity_mut(collider.entity) else {
+                despawned = true;
+                continue;
+            };
+            if let Some(mut shape) = entity.get_mut::<Collider>() {
+                *shape.bypass_change_detection() = collider.shape.clone();
+            }
+            match (collider.disabled, entity.contains::<ColliderDisabled>()) {
+                (true, false) => {
+                    entity.insert(ColliderDisabled);
+                }
+                (false, true) => {
+                    entity.remove::<ColliderDisabled>();
+                }
+                _ => {}
+            }
+        }
+
+        // Only resizing the sandbox or changing the collider settings despawns terrain
+        // colliders, their regions are built again from the restored grid
+        if despawned {
+            for region in storage.colliders.iter_mut() {
+                region.retain(|(_, entity)| world.get_entity(*entity).is_some());
+            }
+            if let Some(mut settings) = world.get_resource_mut::<ColliderSettings>() {
+                settings.set_changed();
+            }
+        }
+        world.insert_resource(storage);
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    collections::VecDeque,
+    f32::consts::PI,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use rand::Rng;
+
+use crate::{
+    player::{
+        bending::Bender,
+        bindings::GrabControl,
+        components::{AimDirection, PlayerBundle, PlayerHealth},
+    },
+    sandbox::{
+        collider::{gen_colliders::generate_sandbox_colliders, ColliderSettings, ColliderStorage},
+        particle_types::{get_particle, ParticleTypes},
+        rng::SimulationRng,
+        sandbox::Sandbox,
+        simulation::{
+            advance_clock, simulation_running, tick_due, update_particles, SimulationControl,
+        },
+        SANDBOX_CHUNK_HEIGHT, SANDBOX_CHUNK_WIDTH,
+    },
+    schedule::FIXED_HZ,
+};
+
+use super::{checksum, Snapshot};
+
+const SEED: u64 = 47;
+/// Fixed steps simulated in total
+const STEPS: usize = 1200;
+/// How far every rollback goes back
+const ROLLBACK_STEPS: usize = 8;
+/// Fixed steps between rollbacks
+const ROLLBACK_EVERY: usize = 20;
+/// Seconds between the hops of the test player
+const HOP_COOLDOWN: f32 = 0.75;
+const HOP_SPEED: f32 = 400.0;
+
+/// Runs the falling sand, the terrain colliders and the physics without a window, run with
+/// `--verify-rollback` and by a test with fewer steps. Every `ROLLBACK_EVERY` steps the match is
+/// rolled back `ROLLBACK_STEPS` steps and simulated again, which has to end in the same checksum
+/// as the first time.
+pub fn verify_rollback() -> AppExit {
+    let run = simulate_with_rollbacks(STEPS);
+    for rollback in run.rollbacks.iter() {
+        if rollback.missing != 0 {
+            println!(
+                "Rolling back at step {} left {} despawned bodies missing",
+                rollback.step, rollback.missing
+            );
+            return AppExit::error();
+        }
+        if rollback.resimulated != rollback.expected {
+            println!(
+                "Rolling back {ROLLBACK_STEPS} steps at step {} ended in {:016x} instead of {:016x}",
+                rollback.step, rollback.resimulated, rollback.expected
+            );
+            return AppExit::error();
+        }
+    }
+
+    println!(
+        "Rolled back {ROLLBACK_STEPS} steps {} times over {STEPS} steps, every rollback simulated to the same checksum. Snapshots took {:?} to save and {:?} to restore on average.",
+        run.rollbacks.len(),
+        run.saving / STEPS as u32,
+        run.restoring / (run.rollbacks.len() as u32).max(1),
+    );
+    AppExit::Success
+}
+
+struct RollbackRun {
+    rollbacks: Vec<Rollback>,
+    saving: Duration,
+    restoring: Duration,
+}
+
+struct Rollback {
+    /// Step the rollback went back from
+    step: usize,
+    /// Checksum before rolling back
+    expected: u64,
+    /// Checksum after simulating the rolled back steps again
+    resimulated: u64,
+    /// Bodies the snapshot had that were despawned since
+    missing: usize,
+}
+
+fn simulate_with_rollbacks(steps: usize) -> RollbackRun {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0).in_fixed_schedule(),
+    ))
+    .insert_resource(SimulationControl::default())
+    .insert_resource(ColliderStorage::default())
+    .insert_resource(ColliderSettings::default())
+    .add_systems(
+        FixedUpdate,
+        (
+            hop,
+            advance_clock,
+            update_particles.run_if(simulation_running).run_if(tick_due),
+            generate_sandbox_colliders,
+        )
+            .chain()
+            .before(PhysicsSet::SyncBackend),
+    );
+    app.finish();
+    app.cleanup();
+
+    let world = app.world_mut();
+    world.resource_mut::<RapierConfiguration>().timestep_mode = TimestepMode::Fixed {
+        dt: 1.0 / FIXED_HZ as f32,
+        substeps: 1,
+    };
+    let mut rng = SimulationRng::seeded(SEED);
+    world.spawn(test_level(&mut rng));
+    world.insert_resource(rng);
+    spawn_bodies(world);
+
+    let mut history = VecDeque::with_capacity(ROLLBACK_STEPS + 1);
+    let mut run = RollbackRun {
+        rollbacks: vec![],
+        saving: Duration::ZERO,
+        restoring: Duration::ZERO,
+    };
+    for step in 1..=steps {
+        let started = Instant::now();
+        history.push_back(Snapshot::save(world));
+        run.saving += started.elapsed();
+        if history.len() > ROLLBACK_STEPS {
+            history.pop_front();
+        }
+
+        world.run_schedule(FixedUpdate);
+        if step % ROLLBACK_EVERY != 0 || history.len() < ROLLBACK_STEPS {
+            continue;
+        }
+
+        let expected = checksum(world);
+        let started = Instant::now();
+        let missing = history[0].restore(world);
+        run.restoring += started.elapsed();
+
+        for _ in 0..ROLLBACK_STEPS {
+            world.run_schedule(FixedUpdate);
+        }
+        run.rollbacks.push(Rollback {
+            step,
+            expected,
+            resimulated: checksum(world),
+            missing,
+        });
+    }
+
+    run
+}
+
+/// Rocks dropping into the sand, the water and the fire, and a player hopping around between
+/// them
+fn spawn_bodies(world: &mut World) {
+    for x in [-320.0, -160.0, 0.0, 240.0] {
+        let transform = Transform::from_xyz(x, 0.0, 0.0);
+        world.spawn((
+            transform,
+            GlobalTransform::from(transform),
+            RigidBody::Dynamic,
+            Collider::cuboid(12.0, 8.0),
+            Velocity::zero(),
+        ));
+    }
+
+    let transform = Transform::from_xyz(-40.0, -200.0, 0.0);
+    world.spawn((
+        transform,
+        GlobalTransform::from(transform),
+        RigidBody::Dynamic,
+        Collider::ball(15.0),
+        LockedAxes::ROTATION_LOCKED,
+        Velocity::zero(),
+        PlayerBundle {
+            health: PlayerHealth(100.0),
+            ..default()
+        },
+        GrabControl::default(),
+    ));
+}
+
+/// Stands in for the input of the player, hops into a random direction whenever the cooldown
+/// of its ability ran out
+fn hop(
+    mut query: Query<(&mut Velocity, &mut AimDirection, &mut Bender)>,
+    mut rng: ResMut<SimulationRng>,
+) {
+    for (mut velocity, mut aim, mut bender) in query.iter_mut() {
+        bender.cooldown -= 1.0 / FIXED_HZ as f32;
+        if bender.cooldown > 0.0 {
+            continue;
+        }
+
+        bender.cooldown = HOP_COOLDOWN;
+        aim.0 = Vec2::from_angle(rng.gen_range(0.25..0.75) * PI);
+        velocity.linvel = aim.0 * HOP_SPEED;
+    }
+}
+
+/// A stone basin with sand and water falling into it, fire spreading through wood and lava
+/// cooling in water, so most effects draw random numbers
+fn test_level(rng: &mut SimulationRng) -> Sandbox {
+    let mut sandbox = Sandbox::new(16, 12, SANDBOX_CHUNK_WIDTH, SANDBOX_CHUNK_HEIGHT);
+    let (width, height) = (sandbox.width(), sandbox.height());
+    let mut fill = |xs: std::ops::Range<usize>, ys: std::ops::Range<usize>, particle| {
+        for x in xs {
+            for y in ys.clone() {
+                sandbox.set(x, y, Some(get_particle(particle, rng)));
+            }
+        }
+    };
+
+    fill(0..width, 0..4, ParticleTypes::Stone);
+    fill(0..4, 0..height, ParticleTypes::Stone);
+    fill(width - 4..width, 0..height, ParticleTypes::Stone);
+    fill(10..40, height - 20..height - 4, ParticleTypes::Sand);
+    fill(50..80, height - 16..height - 4, ParticleTypes::Water);
+    fill(90..100, 4..30, ParticleTypes::Wood);
+    fill(90..100, 30..34, ParticleTypes::Lava);
+    fill(104..120, height - 14..height - 10, ParticleTypes::Acid);
+
+    sandbox
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rollbacks_simulate_to_the_same_checksum() {
+        let run = simulate_with_rollbacks(12 * ROLLBACK_EVERY);
+
+        assert_eq!(run.rollbacks.len(), 12);
+        for rollback in run.rollbacks.iter() {
+            assert_eq!(
+                rollback.missing, 0,
+                "bodies missing at step {}",
+                rollback.step
+            );
+            assert_eq!(
+                rollback.resimulated, rollback.expected,
+                "checksum after rolling back at step {}",
+                rollback.step
+            );
+        }
+    }
+}
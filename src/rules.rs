@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{player::rebind::rebind_screen_open, state::GameState};
+
+pub struct RulesPlugin;
+
+impl Plugin for RulesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MatchRules::default())
+            // Rules can only change between matches
+            .add_systems(
+                Update,
+                (select_combat_mode, select_stocks, select_time_limit)
+                    .run_if(in_state(GameState::Editing).and_then(not(rebind_screen_open))),
+            );
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CombatMode {
+    /// Damage drains `PlayerHealth`, players are KO'd when it runs out
+    #[default]
+    Health,
+    /// Damage adds to `DamagePercent`, which makes knockback stronger. Players are only KO'd by
+    /// leaving the blast zones.
+    Percentage,
+}
+
+/// Stock counts to pick from, `None` plays without stocks
+const STOCK_OPTIONS: [Option<u32>; 4] = [Some(3), Some(5), Some(1), None];
+/// Time limits in seconds to pick from, `None` plays without a time limit
+const TIME_LIMIT_OPTIONS: [Option<f32>; 4] = [None, Some(120.0), Some(180.0), Some(300.0)];
+
+/// Rules of the current match
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+pub struct MatchRules {
+    pub combat: CombatMode,
+    /// KOs a player can take before they are out of the match
+    pub stocks: Option<u32>,
+    /// Seconds until the player with the fewest KOs wins
+    pub time_limit: Option<f32>,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self {
+            combat: CombatMode::default(),
+            stocks: STOCK_OPTIONS[0],
+            time_limit: TIME_LIMIT_OPTIONS[0],
+        }
+    }
+}
+
+fn select_combat_mode(keyboard_input: Res<ButtonInput<KeyCode>>, mut rules: ResMut<MatchRules>) {
+    if !keyboard_input.just_pressed(KeyCode::F2) {
+        return;
+    }
+
+    rules.combat = match rules.combat {
+        CombatMode::Health => CombatMode::Percentage,
+        CombatMode::Percentage => CombatMode::Health,
+    };
+    println!("Chose {:?} mode", rules.combat);
+}
+
+fn select_stocks(keyboard_input: Res<ButtonInput<KeyCode>>, mut rules: ResMut<MatchRules>) {
+    if !keyboard_input.just_pressed(KeyCode::F3) {
+        return;
+    }
+
+    rules.stocks = next_option(&STOCK_OPTIONS, rules.stocks);
+    println!("Chose {:?} stocks", rules.stocks);
+}
+
+fn select_time_limit(keyboard_input: Res<ButtonInput<KeyCode>>, mut rules: ResMut<MatchRules>) {
+    if !keyboard_input.just_pressed(KeyCode::F4) {
+        return;
+    }
+
+    rules.time_limit = next_option(&TIME_LIMIT_OPTIONS, rules.time_limit);
+    println!("Chose {:?} second time limit", rules.time_limit);
+}
+
+fn next_option<T: Copy + PartialEq>(options: &[T], current: T) -> T {
+    let index = options
+        .iter()
+        .position(|option| *option == current)
+        .map_or(0, |i| i + 1);
+    options[index % options.len()]
+}
@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 use bevy::prelude::Entity;
 
 use super::particle::Particle;
@@ -10,6 +12,8 @@ pub struct SandboxChunk {
     height: usize,
     pub local_position: (usize, usize),
     particles: Vec<Option<Particle>>,
+    /// Run-length encoded particles while the chunk is unloaded
+    compact: Option<Vec<(u16, Option<Particle>)>>,
     pub colliders: Vec<Entity>,
     strong_ticked: u8,
     weak_ticked: u8,
@@ -27,6 +31,7 @@ impl SandboxChunk {
 
                 (local_x, local_y)
             },
+            compact: None,
             colliders: vec![],
             strong_ticked: MAX_TICKED_BEFORE_SLEEP,
             weak_ticked: MAX_TICKED_BEFORE_SLEEP,
@@ -35,32 +40,91 @@ impl SandboxChunk {
 
     pub fn get(&self, x: usize, y: usize) -> Option<&Particle> {
         let index = self.to_index(x, y);
-        self.particles[index].as_ref()
+        self.particles.get(index)?.as_ref()
     }
 
     pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Particle> {
         let index = self.to_index(x, y);
-        self.particles[index].as_mut()
+        self.particles.get_mut(index)?.as_mut()
     }
 
     pub fn set(&mut self, x: usize, y: usize, particle: Option<Particle>) {
+        if !self.is_loaded() {
+            return;
+        }
+
         let index = self.to_index(x, y);
         self.particles[index] = particle;
 
         self.strong_tick();
     }
 
+    /// Expanded from the runs while the chunk is unloaded
+    pub fn particles(&self) -> Cow<'_, [Option<Particle>]> {
+        match &self.compact {
+            Some(runs) => Cow::Owned(expand(runs)),
+            None => Cow::Borrowed(&self.particles),
+        }
+    }
+
+    /// Run-length encoded particles, like the chunk keeps them while it is unloaded
+    pub fn runs(&self) -> Vec<(u16, Option<Particle>)> {
+        match &self.compact {
+            Some(runs) => runs.clone(),
+            None => compress(&self.particles),
+        }
+    }
+
+    /// Replaces every particle of a loaded chunk, `particles` has to fill the whole chunk
+    pub fn replace(&mut self, particles: Vec<Option<Particle>>) {
+        if !self.is_loaded() || particles.len() != self.width * self.height {
+            return;
+        }
+
+        self.particles = particles;
+        self.strong_tick();
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.compact.is_none()
+    }
+
+    /// Compresses the particles and stops the chunk from being simulated
+    pub fn unload(&mut self) {
+        if !self.is_loaded() {
+            return;
+        }
+
+        self.compact = Some(compress(&self.particles));
+        self.particles = vec![];
+        self.strong_ticked = 0;
+        self.weak_ticked = 0;
+    }
+
+    pub fn load(&mut self) {
+        let Some(runs) = self.compact.take() else {
+            return;
+        };
+
+        self.particles = expand(&runs);
+        self.strong_tick();
+    }
+
     pub fn reset_ticked(&mut self) {
         self.strong_ticked = self.strong_ticked.saturating_sub(1);
         self.weak_ticked = self.weak_ticked.saturating_sub(1);
     }
 
     pub fn strong_tick(&mut self) {
-        self.strong_ticked = MAX_TICKED_BEFORE_SLEEP;
+        if self.is_loaded() {
+            self.strong_ticked = MAX_TICKED_BEFORE_SLEEP;
+        }
     }
 
     pub fn weak_tick(&mut self) {
-        self.weak_ticked = MAX_TICKED_BEFORE_SLEEP;
+        if self.is_loaded() {
+            self.weak_ticked = MAX_TICKED_BEFORE_SLEEP;
+        }
     }
 
     pub fn is_strong_ticked(&self) -> bool {
@@ -73,27 +137,46 @@ impl SandboxChunk {
 
     pub fn mark_updated(&mut self, x: usize, y: usize) {
         let index = self.to_index(x, y);
-        let particle = self.particles[index].as_mut();
+        let particle = self.particles.get_mut(index).and_then(|x| x.as_mut());
         if let Some(particle) = particle {
             particle.updated = true;
         }
     }
 
+    pub fn has_updated(&self) -> bool {
+        self.particles
+            .iter()
+            .flatten()
+            .any(|particle| particle.updated)
+    }
+
     pub fn reset_updated(&mut self) {
         for particle in self.particles.iter_mut().filter_map(|x| x.as_mut()) {
             particle.updated = false;
         }
     }
 
-    pub fn width(&self) -> usize {
-        self.width
+    fn to_index(&self, x: usize, y: usize) -> usize {
+        (y * self.width) + x
     }
+}
 
-    pub fn height(&self) -> usize {
-        self.height
+fn compress(particles: &[Option<Particle>]) -> Vec<(u16, Option<Particle>)> {
+    let mut runs: Vec<(u16, Option<Particle>)> = vec![];
+    for particle in particles {
+        match runs.last_mut() {
+            Some((count, last)) if last == particle && *count < u16::MAX => *count += 1,
+            _ => runs.push((1, *particle)),
+        }
     }
+    runs
+}
 
-    fn to_index(&self, x: usize, y: usize) -> usize {
-        (y * self.width) + x
+/// Particles of the runs of `SandboxChunk::runs`
+pub fn expand(runs: &[(u16, Option<Particle>)]) -> Vec<Option<Particle>> {
+    let mut particles = Vec::with_capacity(runs.iter().map(|(count, _)| *count as usize).sum());
+    for (count, particle) in runs {
+        particles.extend(std::iter::repeat_n(*particle, *count as usize));
     }
+    particles
 }
@@ -0,0 +1,103 @@
+//! Marching squares contour tracing. Only depends on `bevy` math types so the benchmarks can
+//! include it on its own.
+
+use bevy::{
+    math::{IVec2, Vec2},
+    utils::HashMap,
+};
+
+/// Edge directions in counter-clockwise order, so turning left is the next one
+const DIRECTIONS: [IVec2; 4] = [IVec2::X, IVec2::Y, IVec2::NEG_X, IVec2::NEG_Y];
+
+/// Traces the outlines of the filled cells between `low` and `high` (exclusive) into closed
+/// loops. Cells outside of the region count as empty, so outlines are closed at its border.
+///
+/// Every loop is ordered with the filled cells on its left and only has a point where the
+/// outline turns. Cell `(x, y)` spans `x - 0.5..x + 0.5`, so the points are on half cells.
+pub fn trace_contours(
+    low: IVec2,
+    high: IVec2,
+    filled: impl Fn(i32, i32) -> bool,
+) -> Vec<Vec<Vec2>> {
+    let is_filled =
+        |x: i32, y: i32| x >= low.x && x < high.x && y >= low.y && y < high.y && filled(x, y);
+
+    // Outgoing edge directions keyed by the corner they start at. Corner `(x, y)` is the bottom
+    // left corner of cell `(x, y)`. Only corners where two cells touch diagonally have two.
+    let mut edges: HashMap<IVec2, u8> = HashMap::new();
+    let mut add_edge = |corner: IVec2, direction: usize| {
+        *edges.entry(corner).or_default() |= 1 << direction;
+    };
+
+    for x in low.x..high.x {
+        for y in low.y..high.y {
+            if !is_filled(x, y) {
+                continue;
+            }
+
+            if !is_filled(x, y - 1) {
+                add_edge(IVec2::new(x, y), 0);
+            }
+            if !is_filled(x + 1, y) {
+                add_edge(IVec2::new(x + 1, y), 1);
+            }
+            if !is_filled(x, y + 1) {
+                add_edge(IVec2::new(x + 1, y + 1), 2);
+            }
+            if !is_filled(x - 1, y) {
+                add_edge(IVec2::new(x, y + 1), 3);
+            }
+        }
+    }
+
+    let mut loops = vec![];
+    while let Some(&start) = edges.keys().next() {
+        let first_direction = take_edge(&mut edges, start, None);
+        let mut points = vec![start];
+        let mut direction = first_direction;
+        let mut corner = start + DIRECTIONS[direction];
+
+        while corner != start {
+            let next = take_edge(&mut edges, corner, Some(direction));
+            if next != direction {
+                points.push(corner);
+            }
+            direction = next;
+            corner += DIRECTIONS[direction];
+        }
+
+        // The start is only a point of the outline if it turns there
+        if direction == first_direction {
+            points.remove(0);
+        }
+
+        loops.push(
+            points
+                .into_iter()
+                .map(|corner| corner.as_vec2() - Vec2::splat(0.5))
+                .collect(),
+        );
+    }
+
+    loops
+}
+
+/// Removes and returns an edge leaving the corner. Where cells touch diagonally, the edge turning
+/// left is taken so the cells get separate outlines.
+fn take_edge(edges: &mut HashMap<IVec2, u8>, corner: IVec2, incoming: Option<usize>) -> usize {
+    let directions = edges
+        .get_mut(&corner)
+        .expect("outlines of filled cells are always closed");
+
+    let direction = match incoming.map(|incoming| (incoming + 1) % 4) {
+        Some(left) if *directions & (1 << left) != 0 => left,
+        _ => directions.trailing_zeros() as usize,
+    };
+    *directions &= !(1 << direction);
+
+    if *directions == 0 {
+        edges.remove(&corner);
+    }
+
+    direction
+}
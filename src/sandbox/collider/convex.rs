@@ -0,0 +1,264 @@
+//! Splits traced outlines into convex pieces for compound colliders. Only depends on `bevy` math
+//! types so the benchmarks can include it on its own.
+
+use bevy::math::Vec2;
+
+/// Cross products below this count as collinear, points are on half cells
+const EPSILON: f32 = 1e-4;
+
+/// Splits the area inside of the loops into convex polygons. Outlines run counter-clockwise and
+/// the holes in them clockwise, like the loops of `trace_contours`.
+///
+/// Holes are cut open into the outline around them, the outline is ear clipped into triangles
+/// and neighboring triangles are merged for as long as they stay convex. The pieces run
+/// counter-clockwise.
+pub fn convex_pieces(loops: &[Vec<Vec2>]) -> Vec<Vec<Vec2>> {
+    let (mut outlines, holes): (Vec<&[Vec2]>, Vec<&[Vec2]>) = loops
+        .iter()
+        .map(|points| points.as_slice())
+        .filter(|points| points.len() >= 3 && signed_area(points).abs() > EPSILON)
+        .partition(|points| signed_area(points) > 0.0);
+
+    // A hole belongs to the smallest outline around it
+    outlines.sort_by(|a, b| signed_area(a).total_cmp(&signed_area(b)));
+    let mut outline_holes = vec![vec![]; outlines.len()];
+    for hole in holes {
+        // The filled cells are on the left of the hole's edges
+        let direction = (hole[1] - hole[0]).normalize_or_zero();
+        let inside = (hole[0] + hole[1]) / 2.0 + direction.perp() * 0.25;
+        if let Some(outline) = outlines
+            .iter()
+            .position(|outline| contains(outline, inside))
+        {
+            outline_holes[outline].push(hole);
+        }
+    }
+
+    let mut pieces = vec![];
+    for (outline, holes) in outlines.into_iter().zip(outline_holes) {
+        let polygon = bridge_holes(outline, holes);
+        let triangles = ear_clip(&polygon);
+        pieces.extend(
+            merge_convex(&polygon, triangles)
+                .into_iter()
+                .map(|piece| piece.into_iter().map(|i| polygon[i]).collect()),
+        );
+    }
+
+    pieces
+}
+
+/// Positive for counter-clockwise loops
+pub(super) fn signed_area(points: &[Vec2]) -> f32 {
+    points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.perp_dot(*b))
+        .sum::<f32>()
+        / 2.0
+}
+
+fn contains(polygon: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    for (a, b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Joins the holes into the outline with a cut from each hole to a vertex it can see, which turns
+/// the outline into one loop that can be ear clipped
+fn bridge_holes(outline: &[Vec2], mut holes: Vec<&[Vec2]>) -> Vec<Vec2> {
+    let mut polygon = outline.to_vec();
+
+    // Holes furthest to the right are cut open first, like in David Eberly's "Triangulation by
+    // Ear Clipping"
+    let max_x = |hole: &&[Vec2]| hole.iter().map(|point| point.x).fold(f32::MIN, f32::max);
+    holes.sort_by(|a, b| max_x(b).total_cmp(&max_x(a)));
+
+    for (i, hole) in holes.iter().enumerate() {
+        let (start, point) = hole
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.x.total_cmp(&b.x))
+            .map(|(i, point)| (i, *point))
+            .unwrap();
+
+        // The closest vertex whose cut doesn't cross the outline or a hole that isn't cut open yet
+        let mut candidates = (0..polygon.len()).collect::<Vec<_>>();
+        candidates.sort_by(|a, b| {
+            point
+                .distance_squared(polygon[*a])
+                .total_cmp(&point.distance_squared(polygon[*b]))
+        });
+        let edges = loop_edges(&polygon)
+            .chain(holes[i..].iter().flat_map(|hole| loop_edges(hole)))
+            .collect::<Vec<_>>();
+        let target = candidates
+            .iter()
+            .copied()
+            .find(|candidate| {
+                !edges
+                    .iter()
+                    .any(|(a, b)| crosses(point, polygon[*candidate], *a, *b))
+            })
+            .unwrap_or(candidates[0]);
+
+        let mut bridged = Vec::with_capacity(polygon.len() + hole.len() + 2);
+        bridged.extend_from_slice(&polygon[..=target]);
+        bridged.extend_from_slice(&hole[start..]);
+        bridged.extend_from_slice(&hole[..=start]);
+        bridged.extend_from_slice(&polygon[target..]);
+        polygon = bridged;
+    }
+
+    polygon
+}
+
+fn loop_edges(points: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    points
+        .iter()
+        .copied()
+        .zip(points.iter().copied().cycle().skip(1))
+}
+
+/// Whether the segments cross each other, touching at an end point doesn't count
+fn crosses(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    if a == c || a == d || b == c || b == d {
+        return false;
+    }
+
+    let side = |from: Vec2, to: Vec2, point: Vec2| (to - from).perp_dot(point - from);
+    let (c_side, d_side) = (side(a, b, c), side(a, b, d));
+    let (a_side, b_side) = (side(c, d, a), side(c, d, b));
+    c_side * d_side < 0.0 && a_side * b_side < 0.0
+}
+
+/// Triangulates a counter-clockwise loop by cutting off ears, returns indices into the loop.
+/// Loops that cross themselves after simplification still finish, but may overlap.
+fn ear_clip(polygon: &[Vec2]) -> Vec<[usize; 3]> {
+    let mut remaining = (0..polygon.len()).collect::<Vec<_>>();
+    let mut triangles = vec![];
+    let mut i = 0;
+    let mut misses = 0;
+
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let current = i % len;
+        let triangle = [
+            remaining[(current + len - 1) % len],
+            remaining[current],
+            remaining[(current + 1) % len],
+        ];
+
+        // Without any ears left the loop crosses itself, the vertex is cut off anyway
+        if !is_ear(polygon, &remaining, triangle) && misses < len {
+            i = current + 1;
+            misses += 1;
+            continue;
+        }
+
+        if area(polygon, triangle) > EPSILON {
+            triangles.push(triangle);
+        }
+        remaining.remove(current);
+        // The previous vertex may have become an ear
+        i = current + len - 2;
+        misses = 0;
+    }
+
+    let triangle = [remaining[0], remaining[1], remaining[2]];
+    if area(polygon, triangle) > EPSILON {
+        triangles.push(triangle);
+    }
+
+    triangles
+}
+
+/// Twice the signed area of the triangle
+fn area(polygon: &[Vec2], [a, b, c]: [usize; 3]) -> f32 {
+    (polygon[b] - polygon[a]).perp_dot(polygon[c] - polygon[b])
+}
+
+fn is_ear(polygon: &[Vec2], remaining: &[usize], triangle: [usize; 3]) -> bool {
+    if area(polygon, triangle) <= EPSILON {
+        return false;
+    }
+
+    let [a, b, c] = triangle.map(|i| polygon[i]);
+    !remaining.iter().map(|i| polygon[*i]).any(|point| {
+        // Cuts from holes repeat vertices of the triangle
+        point != a
+            && point != b
+            && point != c
+            && (b - a).perp_dot(point - a) >= 0.0
+            && (c - b).perp_dot(point - b) >= 0.0
+            && (a - c).perp_dot(point - c) >= 0.0
+    })
+}
+
+/// Merges triangles sharing an edge as long as the result stays convex, following Hertel and
+/// Mehlhorn
+fn merge_convex(polygon: &[Vec2], triangles: Vec<[usize; 3]>) -> Vec<Vec<usize>> {
+    let mut pieces = triangles
+        .into_iter()
+        .map(|triangle| triangle.to_vec())
+        .collect::<Vec<_>>();
+    let mut merged = true;
+
+    while merged {
+        merged = false;
+        let mut a = 0;
+        while a < pieces.len() {
+            let mut b = a + 1;
+            while b < pieces.len() {
+                match merge(&pieces[a], &pieces[b]).filter(|piece| is_convex(polygon, piece)) {
+                    Some(piece) => {
+                        pieces[a] = piece;
+                        pieces.swap_remove(b);
+                        merged = true;
+                    }
+                    None => b += 1,
+                }
+            }
+            a += 1;
+        }
+    }
+
+    pieces
+}
+
+fn is_convex(polygon: &[Vec2], piece: &[usize]) -> bool {
+    let len = piece.len();
+    (0..len).all(|i| {
+        let [a, b, c] = [piece[i], piece[(i + 1) % len], piece[(i + 2) % len]].map(|i| polygon[i]);
+        (b - a).perp_dot(c - b) >= -EPSILON
+    })
+}
+
+/// Joins two pieces along an edge they share, `None` without a shared edge
+fn merge(first: &[usize], second: &[usize]) -> Option<Vec<usize>> {
+    let (first_len, second_len) = (first.len(), second.len());
+    for i in 0..first_len {
+        let (from, to) = (first[i], first[(i + 1) % first_len]);
+        let Some(j) =
+            (0..second_len).find(|j| second[*j] == to && second[(j + 1) % second_len] == from)
+        else {
+            continue;
+        };
+
+        // Around the first piece from the end of the shared edge back to its start, then around
+        // the second piece without the shared edge
+        let mut piece = (1..=first_len)
+            .map(|k| first[(i + k) % first_len])
+            .collect::<Vec<_>>();
+        piece.extend((2..second_len).map(|k| second[(j + k) % second_len]));
+        return Some(piece);
+    }
+
+    None
+}
@@ -3,198 +3,154 @@ use bevy_rapier2d::prelude::*;
 
 use crate::sandbox::{particle::CollisionType, sandbox::Sandbox};
 
-use super::{utils::*, ColliderStorage, Ground};
+use super::{
+    contour::trace_contours, convex::convex_pieces, simplify::simplify_loop, utils::*, AcidSensor,
+    ColliderSettings, ColliderStorage, FireSensor, Ground, WaterSensor,
+};
 
 pub fn generate_sandbox_colliders(
     mut commands: Commands,
     sandbox: Query<&mut Sandbox>,
+    settings: Res<ColliderSettings>,
     mut storage: ResMut<ColliderStorage>,
 ) {
     let sandbox = sandbox.single();
     let width = sandbox.width();
     let height = sandbox.height();
 
+    let region_width = if settings.merge_chunks {
+        sandbox.x_chunks()
+    } else {
+        1
+    };
+    let x_regions = sandbox.x_chunks().div_ceil(region_width);
+    let regions = x_regions * sandbox.y_chunks();
+    if storage.colliders.len() != regions || settings.is_changed() {
+        for i in 0..storage.colliders.len() {
+            despawn_old_colliders(&mut storage, i, &mut commands);
+        }
+        storage.colliders = vec![vec![]; regions];
+        storage.layouts = vec![vec![]; regions];
+    }
+
     let chunks = sandbox.get_all_chunks();
-    for (i, chunk) in chunks.iter().enumerate() {
-        if !chunk.is_strong_ticked() {
-            continue;
+    if storage.loaded.len() != chunks.len() {
+        storage.loaded = chunks.iter().map(|chunk| chunk.is_loaded()).collect();
+    }
+
+    // A region is rebuilt as a whole when any of its chunks changed, was loaded or unloaded
+    let mut dirty = vec![settings.is_changed(); regions];
+    for (chunk, loaded) in chunks.iter().zip(storage.loaded.iter_mut()) {
+        if chunk.is_strong_ticked() || chunk.is_loaded() != *loaded {
+            let (x, y) = chunk.local_position;
+            dirty[y * x_regions + x / region_width] = true;
+            *loaded = chunk.is_loaded();
         }
-        despawn_old_colliders(&mut storage, i, &mut commands);
+    }
 
-        let low = local_to_world(chunk, Vec2::ZERO);
-        let high = local_to_world(
-            chunk,
-            Vec2::new(chunk.width() as f32, chunk.height() as f32),
+    for i in (0..regions).filter(|i| dirty[*i]) {
+        let region_x = i % x_regions;
+        let region_y = i / x_regions;
+        let low = IVec2::new(
+            (region_x * region_width * sandbox.chunk_width()) as i32,
+            (region_y * sandbox.chunk_height()) as i32,
+        );
+        let high = IVec2::new(
+            (((region_x + 1) * region_width).min(sandbox.x_chunks()) * sandbox.chunk_width())
+                as i32,
+            ((region_y + 1) * sandbox.chunk_height()) as i32,
         );
 
-        let mut colliders = vec![];
         for collision_type in CollisionType::iter() {
             if *collision_type == CollisionType::None {
                 continue;
             }
 
-            let blocks = march_edges(sandbox, low, high, *collision_type);
-
-            let epsilon = match collision_type {
-                CollisionType::Solid => 1.0,
-                _ => 2.0,
-            };
-
-            for block in &blocks {
-                let block = ramer_douglas_peucker(block, epsilon);
-                let block = block
-                    .into_iter()
-                    .map(|pos| {
-                        ((pos - Vec2::new(width as f32 / 2.0, height as f32 / 2.0))
-                            + Vec2::new(0.5, 0.5))
-                            * Vec2::new(8.0, 8.0)
-                    })
-                    .collect();
-
-                let collider = match collision_type {
-                    CollisionType::None => panic!(),
-                    CollisionType::Solid => commands.spawn((
-                        Collider::polyline(block, None),
-                        RigidBody::Fixed,
-                        ContactSkin(3.0),
-                        Friction::coefficient(0.0),
-                        Ground,
-                    )),
-                    CollisionType::Acid => commands.spawn((
-                        Collider::polyline(block, None),
-                        RigidBody::Fixed,
-                        Sensor,
-                        // Acid Status Marker
-                    )),
-                    CollisionType::Fire => commands.spawn((
-                        Collider::polyline(block, None),
-                        Sensor,
-                        // Fire Status Marker
-                    )),
-                    CollisionType::Water => commands.spawn((
-                        Collider::polyline(block, None),
-                        Sensor,
-                        // Water Status Marker
-                    )),
-                }
-                .id();
-                colliders.push(collider);
+            // Tracing and splitting up the outlines costs far more than looking at the cells
+            let layout = hash_collision_type(sandbox, low, high, *collision_type);
+            let layouts = &mut storage.layouts[i];
+            match layouts.iter_mut().find(|(kind, _)| kind == collision_type) {
+                Some((_, hash)) if *hash == layout => continue,
+                Some((_, hash)) => *hash = layout,
+                None => layouts.push((*collision_type, layout)),
             }
-        }
-
-        storage.colliders[i] = Some(colliders);
-    }
-}
-
-// Based on https://github.com/shnewto/bevy_rapier_collider_gen/blob/main/src/edge.rs#L56
-pub fn march_edges(
-    sandbox: &Sandbox,
-    low: Vec2,
-    high: Vec2,
-    collision_type: CollisionType,
-) -> Vec<Vec<Vec2>> {
-    let mut edge_points: Vec<Vec2> = vec![];
 
-    for x in low.x as i32..=high.x as i32 {
-        for y in low.y as i32..=high.y as i32 {
-            if get_at(sandbox, x, y, collision_type) == 0 {
-                continue;
-            }
+            let blocks = trace_contours(low, high, |x, y| {
+                has_collision_type(sandbox, x, y, *collision_type)
+            });
 
-            let neighbors = [
-                get_at(sandbox, x + 1, y, collision_type),
-                get_at(sandbox, x - 1, y, collision_type),
-                get_at(sandbox, x, y + 1, collision_type),
-                get_at(sandbox, x, y - 1, collision_type),
-            ];
+            // Closed loops need a tighter tolerance than open lines or single cells collapse
+            let epsilon = match collision_type {
+                CollisionType::Solid => 0.5,
+                _ => 1.0,
+            };
 
-            let (x, y) = (x as f32, y as f32);
-            match neighbors {
-                // Corners
-                [1, 0, 0, 1] => {
-                    edge_points.push(Vec2::new(x - 0.5, y - 0.5));
-                    edge_points.push(Vec2::new(x - 0.5, y + 0.5));
-                    edge_points.push(Vec2::new(x + 0.5, y + 0.5));
-                }
-                [1, 0, 1, 0] => {
-                    edge_points.push(Vec2::new(x - 0.5, y + 0.5));
-                    edge_points.push(Vec2::new(x - 0.5, y - 0.5));
-                    edge_points.push(Vec2::new(x + 0.5, y - 0.5));
-                }
-                [0, 1, 0, 1] => {
-                    edge_points.push(Vec2::new(x - 0.5, y + 0.5));
-                    edge_points.push(Vec2::new(x + 0.5, y + 0.5));
-                    edge_points.push(Vec2::new(x + 0.5, y - 0.5));
-                }
-                [0, 1, 1, 0] => {
-                    edge_points.push(Vec2::new(x + 0.5, y + 0.5));
-                    edge_points.push(Vec2::new(x + 0.5, y - 0.5));
-                    edge_points.push(Vec2::new(x - 0.5, y - 0.5));
+            let half_size = Vec2::new(width as f32 / 2.0, height as f32 / 2.0);
+            let loops = blocks
+                .iter()
+                .map(|block| simplify_loop(block, epsilon))
+                .filter(|block| block.len() >= 3)
+                .map(|block| {
+                    block
+                        .into_iter()
+                        .map(|pos| (pos - half_size + Vec2::new(0.5, 0.5)) * Vec2::new(8.0, 8.0))
+                        .collect()
+                })
+                .collect::<Vec<_>>();
+
+            // Holes are cut out of the pieces, so sensors only cover the cells they belong to
+            let pieces = convex_pieces(&loops)
+                .into_iter()
+                .filter_map(Collider::convex_polyline)
+                .map(|piece| (Vec2::ZERO, 0.0, piece))
+                .collect::<Vec<_>>();
+
+            let entity = storage.colliders[i]
+                .iter()
+                .find(|(kind, _)| kind == collision_type)
+                .map(|(_, entity)| *entity);
+            match entity {
+                Some(entity) if pieces.is_empty() => {
+                    commands.entity(entity).insert(ColliderDisabled);
                 }
-                // Sides
-                [1, 1, 1, 0] | [0, 0, 1, 0] => {
-                    edge_points.push(Vec2::new(x - 0.5, y - 0.5));
-                    edge_points.push(Vec2::new(x + 0.5, y - 0.5));
+                Some(entity) => {
+                    commands
+                        .entity(entity)
+                        .insert(Collider::compound(pieces))
+                        .remove::<ColliderDisabled>();
                 }
-                [1, 1, 0, 1] | [0, 0, 0, 1] => {
-                    edge_points.push(Vec2::new(x - 0.5, y + 0.5));
-                    edge_points.push(Vec2::new(x + 0.5, y + 0.5));
-                }
-                [1, 0, 1, 1] | [1, 0, 0, 0] => {
-                    edge_points.push(Vec2::new(x - 0.5, y - 0.5));
-                    edge_points.push(Vec2::new(x - 0.5, y + 0.5));
-                }
-                [0, 1, 1, 1] | [0, 1, 0, 0] => {
-                    edge_points.push(Vec2::new(x + 0.5, y - 0.5));
-                    edge_points.push(Vec2::new(x + 0.5, y + 0.5));
-                }
-                // Surrounded
-                [1, 1, 1, 1] => continue,
-                // Others
-                _ => {
-                    edge_points.push(Vec2::new(x + 0.5, y + 0.5));
-                    edge_points.push(Vec2::new(x - 0.5, y + 0.5));
-                    edge_points.push(Vec2::new(x - 0.5, y - 0.5));
-                    edge_points.push(Vec2::new(x + 0.5, y - 0.5));
+                None if pieces.is_empty() => {}
+                None => {
+                    let entity = spawn_terrain_collider(
+                        &mut commands,
+                        Collider::compound(pieces),
+                        *collision_type,
+                    );
+                    storage.colliders[i].push((*collision_type, entity));
                 }
             }
         }
     }
-
-    points_to_drawing_order(&edge_points)
 }
 
-fn points_to_drawing_order(points: &[Vec2]) -> Vec<Vec<Vec2>> {
-    let mut edge_points: Vec<Vec2> = points.to_vec();
-    let mut in_drawing_order: Vec<Vec2> = vec![];
-    let mut groups: Vec<Vec<Vec2>> = vec![];
-    while !edge_points.is_empty() {
-        if in_drawing_order.is_empty() {
-            in_drawing_order.push(edge_points.remove(0));
-        }
-
-        let prev = *in_drawing_order.last().unwrap();
-
-        let neighbor = edge_points
-            .iter()
-            .enumerate()
-            .find(|(_, p)| prev.distance(**p) == 1.0);
-
-        if let Some((i, _)) = neighbor {
-            let next = edge_points.remove(i);
-            in_drawing_order.push(next);
-            continue;
-        }
-
-        if !in_drawing_order.is_empty() {
-            groups.push(in_drawing_order.clone());
-            in_drawing_order.clear()
-        }
-    }
-
-    if !in_drawing_order.is_empty() {
-        groups.push(in_drawing_order.clone());
+/// Spawns the collider of a region's cells with the collision type
+fn spawn_terrain_collider(
+    commands: &mut Commands,
+    shape: Collider,
+    collision_type: CollisionType,
+) -> Entity {
+    match collision_type {
+        CollisionType::None => panic!(),
+        CollisionType::Solid => commands.spawn((
+            shape,
+            RigidBody::Fixed,
+            ContactSkin(3.0),
+            Friction::coefficient(0.0),
+            Ground,
+        )),
+        CollisionType::Acid => commands.spawn((shape, RigidBody::Fixed, Sensor, AcidSensor)),
+        CollisionType::Fire => commands.spawn((shape, Sensor, FireSensor)),
+        CollisionType::Water => commands.spawn((shape, Sensor, WaterSensor)),
     }
-
-    groups
+    .id()
 }
@@ -3,10 +3,14 @@
 use bevy::prelude::*;
 
 use self::gen_colliders::generate_sandbox_colliders;
+use crate::{sandbox::particle::CollisionType, schedule::GameplaySet};
 
-use super::{SANDBOX_X_CHUNKS, SANDBOX_Y_CHUNKS};
-
+pub mod contour;
+pub mod convex;
 pub mod gen_colliders;
+pub mod simplify;
+#[cfg(test)]
+mod tests;
 mod utils;
 
 pub struct SandboxColliderPlugin;
@@ -14,22 +18,55 @@ pub struct SandboxColliderPlugin;
 impl Plugin for SandboxColliderPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(ColliderStorage::default())
-            .add_systems(Update, generate_sandbox_colliders);
+            .insert_resource(ColliderSettings::default())
+            .add_systems(
+                FixedUpdate,
+                generate_sandbox_colliders.in_set(GameplaySet::Terrain),
+            );
     }
 }
 
 #[derive(Component)]
 pub struct Ground;
 
+/// Sensor over acid cells, corrodes players inside of it
+#[derive(Component)]
+pub struct AcidSensor;
+
+/// Sensor over burning cells, sets players inside of it on fire
+#[derive(Component)]
+pub struct FireSensor;
+
+/// Sensor over water cells, extinguishes and slows players inside of it
+#[derive(Component)]
+pub struct WaterSensor;
+
+/// How the terrain is split up into colliders
 #[derive(Resource)]
-pub struct ColliderStorage {
-    pub colliders: Vec<Option<Vec<Entity>>>,
+pub struct ColliderSettings {
+    /// Merges every chunk in a row into one collider region. Seams are only left between
+    /// regions, so players walking across the terrain don't catch on chunk borders.
+    pub merge_chunks: bool,
 }
 
-impl Default for ColliderStorage {
+impl Default for ColliderSettings {
     fn default() -> Self {
-        Self {
-            colliders: vec![None; SANDBOX_X_CHUNKS * SANDBOX_Y_CHUNKS],
-        }
+        Self { merge_chunks: true }
     }
 }
+
+/// Colliders of every region, resized to match the `Sandbox` when it changes size
+#[derive(Resource, Default, Clone)]
+pub struct ColliderStorage {
+    /// One collider for every collision type a region ever had. Rebuilding a region swaps the
+    /// shapes of its colliders and disables the ones without cells, so a rollback can put the
+    /// shapes back on the same entities.
+    pub colliders: Vec<Vec<(CollisionType, Entity)>>,
+    /// Whether every chunk was loaded when the colliders were last built. Unloaded chunks have
+    /// no colliders, so a region is rebuilt when one of its chunks is loaded or unloaded.
+    pub loaded: Vec<bool>,
+    /// Hash of the cells every collision type covered in a region when its collider was last
+    /// built. Types whose cells didn't change keep their collider, so liquids or fire keeping a
+    /// row awake don't rebuild its ground.
+    pub layouts: Vec<Vec<(CollisionType, u64)>>,
+}
@@ -0,0 +1,93 @@
+//! Ramer-Douglas-Peucker simplification of traced outlines. Only depends on `bevy` math types so
+//! the benchmarks can include it on its own.
+
+use bevy::math::Vec2;
+
+pub fn ramer_douglas_peucker(data: &[Vec2], epsilon: f32) -> Vec<Vec2> {
+    let mut max_distance = 0.0;
+    let mut index = 0;
+    let end = data.len() - 1;
+
+    for i in 1..end {
+        let distance = perpendicular_distance(data[i], data[0], data[end]);
+        if distance > max_distance {
+            index = i;
+            max_distance = distance;
+        }
+    }
+
+    let mut results = vec![];
+
+    if max_distance > epsilon {
+        // Both halves keep the point they are split at, the first one drops its copy
+        let mut recursive_results1 = ramer_douglas_peucker(&data[..=index], epsilon);
+        recursive_results1.pop();
+        let mut recursive_results2 = ramer_douglas_peucker(&data[index..], epsilon);
+
+        // Build result
+        results.append(&mut recursive_results1);
+        results.append(&mut recursive_results2)
+    } else {
+        results = vec![data[0], data[end]];
+    }
+
+    results
+}
+
+/// Simplifies a closed loop by splitting it at the point furthest from the start, so both
+/// halves keep their shape instead of collapsing onto the line between the loop's ends
+pub fn simplify_loop(data: &[Vec2], epsilon: f32) -> Vec<Vec2> {
+    if data.len() < 4 {
+        return data.to_vec();
+    }
+
+    let (split, _) = data
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| data[0].distance(**a).total_cmp(&data[0].distance(**b)))
+        .unwrap();
+    if split == 0 {
+        return vec![data[0]];
+    }
+
+    let mut second_half = data[split..].to_vec();
+    second_half.push(data[0]);
+
+    let mut results = ramer_douglas_peucker(&data[..=split], epsilon);
+    results.pop();
+    results.append(&mut ramer_douglas_peucker(&second_half, epsilon));
+    // The loop closes back onto the first point
+    results.pop();
+
+    results
+}
+
+// CC0 Tim Sheerman-Chase, 2016
+// https://gist.github.com/TimSC/0813573d77734bcb6f2cd2cf6cc7aa51
+pub fn perpendicular_distance(point: Vec2, line_start: Vec2, line_end: Vec2) -> f32 {
+    let mut dx = line_end.x - line_start.x;
+    let mut dy = line_end.y - line_start.y;
+
+    // Normalise
+    let magnitude = (dx.powf(2.0) + dy.powf(2.0)).powf(0.5);
+    if magnitude > 0.0 {
+        dx /= magnitude;
+        dy /= magnitude;
+    }
+
+    let pvx = point.x - line_start.x;
+    let pvy = point.y - line_start.y;
+
+    // Get dot product (project pv onto normalized direction)
+    let pvdot = dx * pvx + dy * pvy;
+
+    // Scale line direction vector
+    let dsx = pvdot * dx;
+    let dsy = pvdot * dy;
+
+    // Subtract this from pv
+    let ax = pvx - dsx;
+    let ay = pvy - dsy;
+
+    (ax.powf(2.0) + ay.powf(2.0)).powf(0.5)
+}
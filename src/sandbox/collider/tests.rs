@@ -0,0 +1,143 @@
+//! Tests of the collider helpers that the benchmarks include on their own, kept out of their
+//! files so the benchmarks don't build them
+
+use bevy::math::{IVec2, Vec2};
+
+use super::{
+    contour::trace_contours,
+    convex::{convex_pieces, signed_area},
+    simplify::{ramer_douglas_peucker, simplify_loop},
+};
+
+/// A square from `0, 0` to `4, 4` with a point on every half cell of its outline
+fn dense_square() -> Vec<Vec2> {
+    let corners = [
+        Vec2::new(0.0, 0.0),
+        Vec2::new(4.0, 0.0),
+        Vec2::new(4.0, 4.0),
+        Vec2::new(0.0, 4.0),
+    ];
+    (0..4)
+        .flat_map(|i| {
+            let (from, to) = (corners[i], corners[(i + 1) % 4]);
+            (0..8).map(move |step| from.lerp(to, step as f32 / 8.0))
+        })
+        .collect()
+}
+
+#[test]
+fn simplified_square_keeps_corners() {
+    for epsilon in [0.5, 1.0] {
+        let simplified = simplify_loop(&dense_square(), epsilon);
+
+        assert_eq!(
+            simplified,
+            vec![
+                Vec2::new(0.0, 0.0),
+                Vec2::new(4.0, 0.0),
+                Vec2::new(4.0, 4.0),
+                Vec2::new(0.0, 4.0),
+            ]
+        );
+    }
+}
+
+#[test]
+fn keeps_corner_before_split() {
+    // Split at the corner at index 2, the corner right before it has to stay
+    let steps = vec![
+        Vec2::new(0.0, 0.0),
+        Vec2::new(1.0, 0.0),
+        Vec2::new(1.0, 3.0),
+        Vec2::new(4.0, 3.0),
+        Vec2::new(4.0, 4.0),
+    ];
+
+    assert_eq!(ramer_douglas_peucker(&steps, 0.1), steps);
+}
+
+fn square(low: Vec2, high: Vec2) -> Vec<Vec2> {
+    vec![
+        low,
+        Vec2::new(high.x, low.y),
+        high,
+        Vec2::new(low.x, high.y),
+    ]
+}
+
+fn total_area(pieces: &[Vec<Vec2>]) -> f32 {
+    pieces.iter().map(|piece| signed_area(piece)).sum()
+}
+
+#[test]
+fn square_is_one_piece() {
+    let pieces = convex_pieces(&[square(Vec2::ZERO, Vec2::splat(4.0))]);
+
+    assert_eq!(pieces.len(), 1);
+    assert_eq!(pieces[0].len(), 4);
+    assert_eq!(total_area(&pieces), 16.0);
+}
+
+#[test]
+fn concave_outline_is_split() {
+    let l_shape = vec![
+        Vec2::new(0.0, 0.0),
+        Vec2::new(4.0, 0.0),
+        Vec2::new(4.0, 2.0),
+        Vec2::new(2.0, 2.0),
+        Vec2::new(2.0, 4.0),
+        Vec2::new(0.0, 4.0),
+    ];
+    let pieces = convex_pieces(&[l_shape]);
+
+    assert_eq!(pieces.len(), 2);
+    assert_eq!(total_area(&pieces), 12.0);
+    for piece in &pieces {
+        assert!(signed_area(piece) > 0.0);
+    }
+}
+
+#[test]
+fn holes_are_cut_out() {
+    let mut hole = square(Vec2::splat(2.0), Vec2::splat(4.0));
+    hole.reverse();
+    let pieces = convex_pieces(&[square(Vec2::ZERO, Vec2::splat(6.0)), hole]);
+
+    assert_eq!(total_area(&pieces), 32.0);
+    for piece in &pieces {
+        for point in piece {
+            assert!(!(point.x > 2.0 && point.x < 4.0 && point.y > 2.0 && point.y < 4.0));
+        }
+    }
+}
+
+#[test]
+fn separate_outlines_keep_their_holes() {
+    let mut hole = square(Vec2::splat(11.0), Vec2::splat(13.0));
+    hole.reverse();
+    let pieces = convex_pieces(&[
+        square(Vec2::ZERO, Vec2::splat(4.0)),
+        square(Vec2::splat(10.0), Vec2::splat(14.0)),
+        hole,
+    ]);
+
+    assert_eq!(total_area(&pieces), 16.0 + 12.0);
+}
+
+#[test]
+fn traced_cells_are_covered() {
+    // Blobs with holes and cells touching diagonally
+    let filled = |x: i32, y: i32| (x * 7 + y * 13) % 5 != 0 && (x * x + y) % 7 != 3;
+    let size = IVec2::splat(24);
+    let loops = trace_contours(IVec2::ZERO, size, filled);
+    let cells = (0..size.x)
+        .flat_map(|x| (0..size.y).map(move |y| (x, y)))
+        .filter(|(x, y)| filled(*x, *y))
+        .count();
+
+    let pieces = convex_pieces(&loops);
+    assert!((total_area(&pieces) - cells as f32).abs() < 1e-3);
+    for piece in &pieces {
+        assert!(signed_area(piece) > 0.0);
+    }
+}
@@ -0,0 +1,84 @@
+use bevy::prelude::*;
+
+use crate::{
+    player::rebind::rebind_screen_open,
+    schedule::GameplaySet,
+    state::{GameState, ResetArena},
+};
+
+use super::{history::SandboxHistory, sandbox::Sandbox, simulation::SimulationControl};
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EditedLayout::default())
+            .add_systems(
+                OnEnter(GameState::Playing),
+                (save_layout, resume_simulation),
+            )
+            .add_systems(FixedUpdate, restore_layout.in_set(GameplaySet::Reset))
+            .add_systems(
+                Update,
+                simulation_controls
+                    .run_if(in_state(GameState::Editing).and_then(not(rebind_screen_open))),
+            );
+    }
+}
+
+/// Copy of the grid as it was edited, used to reset the arena after a match
+#[derive(Resource, Default)]
+pub struct EditedLayout(Option<Sandbox>);
+
+impl EditedLayout {
+    pub fn save(&mut self, sandbox: &Sandbox) {
+        self.0 = Some(sandbox.clone());
+    }
+
+    pub fn get(&self) -> Option<&Sandbox> {
+        self.0.as_ref()
+    }
+}
+
+fn save_layout(sandbox_query: Query<&Sandbox>, mut layout: ResMut<EditedLayout>) {
+    let Ok(sandbox) = sandbox_query.get_single() else {
+        return;
+    };
+
+    layout.save(sandbox);
+}
+
+fn resume_simulation(mut control: ResMut<SimulationControl>) {
+    control.paused = false;
+}
+
+fn restore_layout(
+    mut sandbox_query: Query<&mut Sandbox>,
+    layout: Res<EditedLayout>,
+    mut history: ResMut<SandboxHistory>,
+    mut events: EventReader<ResetArena>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    let (Ok(mut sandbox), Some(saved)) = (sandbox_query.get_single_mut(), &layout.0) else {
+        return;
+    };
+
+    *sandbox = saved.clone();
+    sandbox.wake_all_chunks();
+    history.clear();
+}
+
+fn simulation_controls(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut control: ResMut<SimulationControl>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyP) {
+        control.paused = !control.paused;
+        println!("Simulation paused: {}", control.paused);
+    }
+    if keyboard_input.just_pressed(KeyCode::Period) && control.paused {
+        control.step();
+    }
+}
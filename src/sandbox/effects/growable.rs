@@ -1,25 +1,25 @@
 use rand::prelude::*;
 
-use crate::sandbox::{particle_types::get_particle, sandbox::Sandbox};
+use crate::sandbox::{particle_types::get_particle, rng::SimulationRng, sandbox::Sandbox};
 
-pub fn tick_growable(x: usize, y: usize, sandbox: &mut Sandbox) {
+pub fn tick_growable(x: usize, y: usize, sandbox: &mut Sandbox, rng: &mut SimulationRng) {
     sandbox.get_chunk_mut(x, y).weak_tick();
 
-    if try_spread(x, y, sandbox) {
+    if try_spread(x, y, sandbox, rng) {
         return;
     }
 
-    try_upwards_growth(x, y, sandbox);
+    try_upwards_growth(x, y, sandbox, rng);
 }
 
-fn try_spread(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+fn try_spread(x: usize, y: usize, sandbox: &mut Sandbox, rng: &mut SimulationRng) -> bool {
     let grow_as = match sandbox
         .get(x, y)
         .expect("Simulation shouldn't have let it get this far")
         .growable
     {
         Some(growable) => {
-            if !thread_rng().gen_bool(growable.spread_chance as f64 / 100.0) {
+            if !rng.gen_bool(growable.spread_chance as f64 / 100.0) {
                 return false;
             }
 
@@ -34,7 +34,7 @@ fn try_spread(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
         (x, y.overflowing_sub(1).0),
         (x, y + 1),
     ];
-    search_directions.shuffle(&mut thread_rng());
+    search_directions.shuffle(rng);
 
     for (neighbor_x, neighbor_y) in search_directions {
         if let Some(particle) = sandbox.checked_get(neighbor_x, neighbor_y) {
@@ -42,7 +42,7 @@ fn try_spread(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
                 continue;
             }
 
-            let mut new_particle = get_particle(grow_as);
+            let mut new_particle = get_particle(grow_as, rng);
             new_particle.updated = true;
             sandbox.set(neighbor_x, neighbor_y, Some(new_particle));
             return true;
@@ -52,15 +52,14 @@ fn try_spread(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
     false
 }
 
-fn try_upwards_growth(x: usize, y: usize, sandbox: &mut Sandbox) {
+fn try_upwards_growth(x: usize, y: usize, sandbox: &mut Sandbox, rng: &mut SimulationRng) {
     let growable = match sandbox
         .get(x, y)
         .expect("Simulation shouldn't have let it get this far")
         .growable
     {
         Some(growable) => {
-            if !thread_rng().gen_bool(growable.spread_chance as f64 / 100.0) || !growable.can_sprout
-            {
+            if !rng.gen_bool(growable.spread_chance as f64 / 100.0) || !growable.can_sprout {
                 return;
             }
 
@@ -75,7 +74,7 @@ fn try_upwards_growth(x: usize, y: usize, sandbox: &mut Sandbox) {
         (x, y + 1),
         (x, y.overflowing_sub(1).0),
     ];
-    search_directions.shuffle(&mut thread_rng());
+    search_directions.shuffle(rng);
 
     for (neighbor_x, neighbor_y) in search_directions {
         if sandbox.checked_get(neighbor_x, neighbor_y).is_some()
@@ -84,7 +83,7 @@ fn try_upwards_growth(x: usize, y: usize, sandbox: &mut Sandbox) {
             continue;
         }
 
-        let mut new_particle = get_particle(growable.grow_as);
+        let mut new_particle = get_particle(growable.grow_as, rng);
         new_particle.updated = true;
         sandbox.set(neighbor_x, neighbor_y, Some(new_particle));
     }
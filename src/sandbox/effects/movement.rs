@@ -1,8 +1,8 @@
 use bevy::prelude::default;
 use rand::*;
 
-use crate::sandbox::particle::*;
 use crate::sandbox::sandbox::Sandbox;
+use crate::sandbox::{particle::*, rng::SimulationRng};
 use crate::vector::*;
 
 #[derive(Default)]
@@ -16,10 +16,10 @@ struct StepData {
     swap: bool,
 }
 
-pub fn tick_movement(x: usize, y: usize, sandbox: &mut Sandbox) {
+pub fn tick_movement(x: usize, y: usize, sandbox: &mut Sandbox, rng: &mut SimulationRng) {
     apply_gravity(x, y, sandbox);
 
-    let step_data = get_step_data(x as i32, y as i32, sandbox);
+    let step_data = get_step_data(x as i32, y as i32, sandbox, rng);
 
     if step_data.swap {
         let current_particle = sandbox.get(x, y).unwrap();
@@ -68,7 +68,7 @@ fn apply_gravity(x: usize, y: usize, sandbox: &mut Sandbox) {
     particle.velocity.zero_out();
 }
 
-fn get_step_data(x: i32, y: i32, sandbox: &Sandbox) -> StepData {
+fn get_step_data(x: i32, y: i32, sandbox: &Sandbox, rng: &mut SimulationRng) -> StepData {
     let particle = sandbox
         .get(x as usize, y as usize)
         .expect("Simulation should have skipped this particle");
@@ -79,7 +79,7 @@ fn get_step_data(x: i32, y: i32, sandbox: &Sandbox) -> StepData {
         MovementType::Solid => return StepData::default(),
     };
 
-    let clockwise_prioity = thread_rng().gen_bool(0.5);
+    let clockwise_prioity = rng.gen_bool(0.5);
     let mut movement_rotations = match clockwise_prioity {
         true => vec![0, 1, 2, 3, 4],
         false => vec![0, 2, 1, 4, 2],
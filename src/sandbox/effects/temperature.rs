@@ -1,19 +1,24 @@
 use bevy::prelude::Vec2;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
-use crate::sandbox::{particle::*, particle_types::*, sandbox::Sandbox};
+use crate::sandbox::{particle::*, particle_types::*, rng::SimulationRng, sandbox::Sandbox};
 
 /// Returns true if the current particle was removed from the simulation during the tick
-pub fn tick_temperature(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+pub fn tick_temperature(
+    x: usize,
+    y: usize,
+    sandbox: &mut Sandbox,
+    rng: &mut SimulationRng,
+) -> bool {
     temperature_change_neighbors(x, y, sandbox);
 
-    if tick_self(x, y, sandbox) {
+    if tick_self(x, y, sandbox, rng) {
         return true;
     }
 
     try_ignite_burnable(x, y, sandbox);
     try_extinquish_burning(x, y, sandbox);
-    spark_if_ignited(x, y, sandbox);
+    spark_if_ignited(x, y, sandbox, rng);
 
     false
 }
@@ -59,7 +64,7 @@ fn temperature_change_neighbors(x: usize, y: usize, sandbox: &mut Sandbox) {
     }
 }
 
-fn tick_self(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+fn tick_self(x: usize, y: usize, sandbox: &mut Sandbox, rng: &mut SimulationRng) -> bool {
     let temperature = match sandbox
         .get(x, y)
         .expect("Simulation shouldn't have let it get this far")
@@ -75,14 +80,16 @@ fn tick_self(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
         || (temperature.critical_on_cool && temperature.current_temperature >= 100)
     {
         if temperature.explosion_radius > 0 {
-            explode(x, y, temperature.explosion_radius, sandbox);
+            explode(x, y, temperature.explosion_radius, sandbox, rng);
             return true;
         }
 
         deplete_critical(health);
 
         if health.amount <= 0 {
-            let replacement = temperature.change_on_critical.map(get_particle);
+            let replacement = temperature
+                .change_on_critical
+                .map(|replacement| get_particle(replacement, rng));
 
             sandbox.set(x, y, replacement);
             return true;
@@ -128,7 +135,7 @@ fn try_extinquish_burning(x: usize, y: usize, sandbox: &mut Sandbox) {
     }
 }
 
-fn spark_if_ignited(x: usize, y: usize, sandbox: &mut Sandbox) {
+fn spark_if_ignited(x: usize, y: usize, sandbox: &mut Sandbox, rng: &mut SimulationRng) {
     match sandbox
         .get_mut(x, y)
         .expect("Simulation shouldn't have let it get this far")
@@ -151,10 +158,10 @@ fn spark_if_ignited(x: usize, y: usize, sandbox: &mut Sandbox) {
         if sandbox.checked_get(neighbor_x, neighbor_y).is_none()
             && !sandbox.out_of_bounds_usize(neighbor_x, neighbor_y)
         {
-            let new_particle = if thread_rng().gen_ratio(1, 3) {
-                get_particle(ParticleTypes::Spark)
+            let new_particle = if rng.gen_ratio(1, 3) {
+                get_particle(ParticleTypes::Spark, rng)
             } else {
-                get_particle(ParticleTypes::Smoke)
+                get_particle(ParticleTypes::Smoke, rng)
             };
 
             sandbox.set(neighbor_x, neighbor_y, Some(new_particle));
@@ -162,7 +169,15 @@ fn spark_if_ignited(x: usize, y: usize, sandbox: &mut Sandbox) {
     }
 }
 
-fn explode(current_x: usize, current_y: usize, radius: i32, sandbox: &mut Sandbox) {
+fn explode(
+    current_x: usize,
+    current_y: usize,
+    radius: i32,
+    sandbox: &mut Sandbox,
+    rng: &mut SimulationRng,
+) {
+    sandbox.record_explosion(current_x, current_y, radius);
+
     let low_x = current_x as i32 - radius;
     let high_x = current_x as i32 + radius;
     let low_y = current_y as i32 - radius;
@@ -175,6 +190,10 @@ fn explode(current_x: usize, current_y: usize, radius: i32, sandbox: &mut Sandbo
             }
 
             if let Some(particle) = sandbox.get_mut(x as usize, y as usize) {
+                if particle.unbreakable {
+                    continue;
+                }
+
                 if x < low_x || x > high_x || y < low_y || y > high_y {
                     let force = (Vec2::new(x as f32, y as f32)
                         - Vec2::new(current_x as f32, current_y as f32))
@@ -188,7 +207,7 @@ fn explode(current_x: usize, current_y: usize, radius: i32, sandbox: &mut Sandbo
                 sandbox.set(
                     x as usize,
                     y as usize,
-                    Some(get_particle(ParticleTypes::Spark)),
+                    Some(get_particle(ParticleTypes::Spark, rng)),
                 );
             }
         }
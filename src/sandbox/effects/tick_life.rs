@@ -1,7 +1,7 @@
-use crate::sandbox::{particle_types::get_particle, sandbox::Sandbox};
+use crate::sandbox::{particle_types::get_particle, rng::SimulationRng, sandbox::Sandbox};
 
 /// Returns true if the current particle was removed from the simulation during the tick
-pub fn tick_life(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
+pub fn tick_life(x: usize, y: usize, sandbox: &mut Sandbox, rng: &mut SimulationRng) -> bool {
     let replacement = match sandbox
         .get(x, y)
         .expect("Simulation shouldn't have let it get this far")
@@ -18,7 +18,7 @@ pub fn tick_life(x: usize, y: usize, sandbox: &mut Sandbox) -> bool {
     health.amount -= 1;
 
     if health.amount <= 0 {
-        let replacement = replacement.map(get_particle);
+        let replacement = replacement.map(|replacement| get_particle(replacement, rng));
 
         sandbox.set(x, y, replacement);
         return true;
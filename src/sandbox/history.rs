@@ -0,0 +1,313 @@
+use std::collections::VecDeque;
+use std::mem::size_of;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{player::rebind::rebind_screen_open, state::GameState};
+
+use super::{particle::Particle, sandbox::Sandbox};
+
+const DEFAULT_MEMORY_BUDGET: usize = 16 * 1024 * 1024;
+
+pub struct SandboxHistoryPlugin;
+
+impl Plugin for SandboxHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SandboxHistory::default()).add_systems(
+            Update,
+            undo_redo.run_if(in_state(GameState::Editing).and_then(not(rebind_screen_open))),
+        );
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct CellChange {
+    pub x: usize,
+    pub y: usize,
+    pub before: Option<Particle>,
+    pub after: Option<Particle>,
+}
+
+/// All the cells changed by a single brush stroke or editor operation
+#[derive(Default)]
+pub struct Stroke {
+    changes: Vec<CellChange>,
+}
+
+impl Stroke {
+    fn memory_size(&self) -> usize {
+        size_of::<Self>() + self.changes.len() * size_of::<CellChange>()
+    }
+}
+
+/// Edit history of the `Sandbox`. Editing tools should write through [`SandboxHistory::set`]
+/// instead of [`Sandbox::set`] so their changes can be undone. Anything else that rewrites the
+/// grid while editing, like loading the level or resetting the arena, has to
+/// [`SandboxHistory::clear`] it, or undoing would put stale cells over the newer ones.
+#[derive(Resource)]
+pub struct SandboxHistory {
+    undo_stack: VecDeque<Stroke>,
+    redo_stack: Vec<Stroke>,
+    current: Option<HashMap<(usize, usize), CellChange>>,
+    memory_budget: usize,
+    memory_used: usize,
+}
+
+impl Default for SandboxHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMORY_BUDGET)
+    }
+}
+
+impl SandboxHistory {
+    pub fn new(memory_budget: usize) -> Self {
+        Self {
+            undo_stack: VecDeque::new(),
+            redo_stack: vec![],
+            current: None,
+            memory_budget,
+            memory_used: 0,
+        }
+    }
+
+    /// Starts grouping every following edit into one stroke until [`SandboxHistory::end_stroke`]
+    pub fn begin_stroke(&mut self) {
+        if self.current.is_none() {
+            self.current = Some(HashMap::new());
+        }
+    }
+
+    pub fn end_stroke(&mut self) {
+        let Some(current) = self.current.take() else {
+            return;
+        };
+
+        let changes: Vec<CellChange> = current
+            .into_values()
+            .filter(|change| change.before != change.after)
+            .collect();
+        self.push_stroke(Stroke { changes });
+    }
+
+    /// Sets the particle and records the change. Edits made outside of a stroke are recorded as
+    /// their own stroke.
+    pub fn set(&mut self, sandbox: &mut Sandbox, x: usize, y: usize, particle: Option<Particle>) {
+        if sandbox.out_of_bounds_usize(x, y) {
+            return;
+        }
+
+        let before = sandbox.get(x, y).copied();
+        sandbox.set(x, y, particle);
+
+        match &mut self.current {
+            Some(current) => {
+                current
+                    .entry((x, y))
+                    .and_modify(|change| change.after = particle)
+                    .or_insert(CellChange {
+                        x,
+                        y,
+                        before,
+                        after: particle,
+                    });
+            }
+            None => self.push_stroke(Stroke {
+                changes: vec![CellChange {
+                    x,
+                    y,
+                    before,
+                    after: particle,
+                }],
+            }),
+        }
+    }
+
+    /// Forgets every stroke, including the one being drawn
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.current = None;
+        self.memory_used = 0;
+    }
+
+    /// Returns true if there was a stroke to undo
+    pub fn undo(&mut self, sandbox: &mut Sandbox) -> bool {
+        self.end_stroke();
+
+        let Some(stroke) = self.undo_stack.pop_back() else {
+            return false;
+        };
+
+        for change in stroke.changes.iter().rev() {
+            sandbox.set(change.x, change.y, change.before);
+        }
+        self.redo_stack.push(stroke);
+
+        true
+    }
+
+    /// Returns true if there was a stroke to redo
+    pub fn redo(&mut self, sandbox: &mut Sandbox) -> bool {
+        self.end_stroke();
+
+        let Some(stroke) = self.redo_stack.pop() else {
+            return false;
+        };
+
+        for change in stroke.changes.iter() {
+            sandbox.set(change.x, change.y, change.after);
+        }
+        self.undo_stack.push_back(stroke);
+
+        true
+    }
+
+    fn push_stroke(&mut self, stroke: Stroke) {
+        if stroke.changes.is_empty() {
+            return;
+        }
+
+        for stroke in self.redo_stack.drain(..) {
+            self.memory_used -= stroke.memory_size();
+        }
+
+        self.memory_used += stroke.memory_size();
+        self.undo_stack.push_back(stroke);
+
+        // Forget the oldest strokes first, but always keep the newest one
+        while self.memory_used > self.memory_budget && self.undo_stack.len() > 1 {
+            let oldest = self.undo_stack.pop_front().unwrap();
+            self.memory_used -= oldest.memory_size();
+        }
+    }
+}
+
+fn undo_redo(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut history: ResMut<SandboxHistory>,
+    mut sandbox_query: Query<&mut Sandbox>,
+) {
+    if !keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    let Ok(mut sandbox) = sandbox_query.get_single_mut() else {
+        return;
+    };
+
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if keyboard_input.just_pressed(KeyCode::KeyZ) && !shift {
+        history.undo(&mut sandbox);
+    } else if keyboard_input.just_pressed(KeyCode::KeyY)
+        || (keyboard_input.just_pressed(KeyCode::KeyZ) && shift)
+    {
+        history.redo(&mut sandbox);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sandbox::{
+        particle::Particle,
+        particle_types::{get_particle, ParticleTypes},
+        rng::SimulationRng,
+        SANDBOX_CHUNK_HEIGHT, SANDBOX_CHUNK_WIDTH,
+    };
+
+    fn sandbox() -> Sandbox {
+        Sandbox::new(2, 2, SANDBOX_CHUNK_WIDTH, SANDBOX_CHUNK_HEIGHT)
+    }
+
+    fn particle(material: ParticleTypes) -> Particle {
+        get_particle(material, &mut SimulationRng::seeded(0))
+    }
+
+    fn material(sandbox: &Sandbox, x: usize, y: usize) -> Option<ParticleTypes> {
+        sandbox.get(x, y).map(|particle| particle.material)
+    }
+
+    #[test]
+    fn undo_and_redo_strokes_in_order() {
+        let mut sandbox = sandbox();
+        let mut history = SandboxHistory::default();
+        let sand = Some(particle(ParticleTypes::Sand));
+        let stone = Some(particle(ParticleTypes::Stone));
+
+        history.begin_stroke();
+        history.set(&mut sandbox, 1, 1, sand);
+        history.set(&mut sandbox, 2, 1, sand);
+        history.end_stroke();
+        history.set(&mut sandbox, 1, 1, stone);
+
+        assert!(history.undo(&mut sandbox));
+        assert_eq!(material(&sandbox, 1, 1), Some(ParticleTypes::Sand));
+        assert!(history.undo(&mut sandbox));
+        assert_eq!(material(&sandbox, 1, 1), None);
+        assert_eq!(material(&sandbox, 2, 1), None);
+        assert!(!history.undo(&mut sandbox));
+
+        assert!(history.redo(&mut sandbox));
+        assert_eq!(material(&sandbox, 1, 1), Some(ParticleTypes::Sand));
+        assert_eq!(material(&sandbox, 2, 1), Some(ParticleTypes::Sand));
+        assert!(history.redo(&mut sandbox));
+        assert_eq!(material(&sandbox, 1, 1), Some(ParticleTypes::Stone));
+        assert!(!history.redo(&mut sandbox));
+    }
+
+    #[test]
+    fn new_edits_drop_the_redo_stack() {
+        let mut sandbox = sandbox();
+        let mut history = SandboxHistory::default();
+
+        history.set(&mut sandbox, 1, 1, Some(particle(ParticleTypes::Sand)));
+        history.undo(&mut sandbox);
+        history.set(&mut sandbox, 2, 2, Some(particle(ParticleTypes::Water)));
+
+        assert!(!history.redo(&mut sandbox));
+        assert_eq!(material(&sandbox, 1, 1), None);
+    }
+
+    #[test]
+    fn evicts_the_oldest_strokes_over_the_memory_budget() {
+        let mut sandbox = sandbox();
+        let stroke_size = size_of::<Stroke>() + size_of::<CellChange>();
+        let mut history = SandboxHistory::new(stroke_size * 2);
+
+        for x in 0..3 {
+            history.set(&mut sandbox, x, 0, Some(particle(ParticleTypes::Sand)));
+        }
+        assert_eq!(history.memory_used, stroke_size * 2);
+
+        assert!(history.undo(&mut sandbox));
+        assert!(history.undo(&mut sandbox));
+        assert!(!history.undo(&mut sandbox));
+        // The first stroke was forgotten, so its cell stays
+        assert_eq!(material(&sandbox, 0, 0), Some(ParticleTypes::Sand));
+        assert_eq!(material(&sandbox, 1, 0), None);
+    }
+
+    #[test]
+    fn keeps_the_newest_stroke_over_the_memory_budget() {
+        let mut sandbox = sandbox();
+        let mut history = SandboxHistory::new(0);
+
+        history.set(&mut sandbox, 0, 0, Some(particle(ParticleTypes::Sand)));
+        history.set(&mut sandbox, 1, 0, Some(particle(ParticleTypes::Sand)));
+
+        assert!(history.undo(&mut sandbox));
+        assert!(!history.undo(&mut sandbox));
+        assert_eq!(material(&sandbox, 0, 0), Some(ParticleTypes::Sand));
+    }
+
+    #[test]
+    fn clear_forgets_every_stroke() {
+        let mut sandbox = sandbox();
+        let mut history = SandboxHistory::default();
+
+        history.set(&mut sandbox, 0, 0, Some(particle(ParticleTypes::Sand)));
+        history.clear();
+
+        assert!(!history.undo(&mut sandbox));
+        assert_eq!(history.memory_used, 0);
+    }
+}
@@ -1,28 +1,42 @@
 use bevy::{
     prelude::*,
     render::{render_asset::RenderAssetUsages, render_resource::*, texture::ImageSampler},
-    time::common_conditions::on_timer,
 };
 use std::f32::consts::PI;
-use std::time::Duration;
 
 use self::{
-    collider::SandboxColliderPlugin, particle_placer::ParticlePlacerPlugin,
-    render::render_particles, sandbox::Sandbox, simulation::update_particles,
+    collider::SandboxColliderPlugin,
+    editor::EditorPlugin,
+    history::SandboxHistoryPlugin,
+    particle_placer::ParticlePlacerPlugin,
+    render::render_particles,
+    rng::SimulationRng,
+    sandbox::Sandbox,
+    simulation::{
+        advance_clock, simulation_running, tick_due, update_particles, SimulationControl,
+    },
+    streaming::ChunkStreamingPlugin,
 };
+use crate::schedule::GameplaySet;
 
-mod chunk;
+pub mod chunk;
 pub mod collider;
+pub mod editor;
 mod effects;
+pub mod history;
 pub mod particle;
 mod particle_placer;
 pub mod particle_types;
-mod render;
+pub mod raycast;
+pub mod render;
+pub mod rng;
 pub mod sandbox;
-mod simulation;
+pub mod simulation;
+pub mod streaming;
 
-const SANDBOX_CHUNK_WIDTH: usize = 8;
-const SANDBOX_CHUNK_HEIGHT: usize = 8;
+pub const SANDBOX_CHUNK_WIDTH: usize = 8;
+pub const SANDBOX_CHUNK_HEIGHT: usize = 8;
+// Default size, levels larger than this resize the sandbox when they are loaded
 const SANDBOX_X_CHUNKS: usize = 30;
 const SANDBOX_Y_CHUNKS: usize = 17;
 
@@ -31,13 +45,26 @@ pub struct SandboxPlugin;
 impl Plugin for SandboxPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(ParticlePlacerPlugin)
+            .add_plugins(SandboxHistoryPlugin)
             .add_plugins(SandboxColliderPlugin)
+            .add_plugins(EditorPlugin)
+            .add_plugins(ChunkStreamingPlugin)
+            .insert_resource(SimulationControl::default())
+            .insert_resource(SimulationRng::default())
             .add_systems(Startup, setup)
             .add_systems(
-                Update,
-                (update_particles, render_particles)
+                FixedUpdate,
+                (
+                    advance_clock,
+                    (
+                        update_particles.run_if(simulation_running),
+                        render_particles,
+                    )
+                        .chain()
+                        .distributive_run_if(tick_due),
+                )
                     .chain()
-                    .distributive_run_if(on_timer(Duration::from_secs_f32(1.0 / 24.0))),
+                    .in_set(GameplaySet::Simulation),
             );
     }
 }
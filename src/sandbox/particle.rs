@@ -2,11 +2,13 @@ use std::cmp::Ordering;
 use std::slice::Iter;
 
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use super::particle_types::ParticleTypes;
+use super::{particle_types::ParticleTypes, rng::SimulationRng};
 
-#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Particle {
+    pub material: ParticleTypes,
     pub health: ParticleHealth,
     pub velocity: Velocity,
     pub color: (u8, u8, u8, u8),
@@ -22,9 +24,11 @@ pub struct Particle {
     pub affected_by_gravity: bool,
     pub updated: bool,
     pub growable_on: bool,
+    /// Can't be grabbed, dug out or blown up
+    pub unbreakable: bool,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ParticleHealth {
     pub amount: i32,
     pub corrodable: bool,
@@ -45,12 +49,12 @@ impl Default for ParticleHealth {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct TickLife {
     pub replace_on_death: Option<ParticleTypes>,
 }
 
-#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Velocity {
     pub x: i32,
     pub y: i32,
@@ -70,13 +74,13 @@ impl Velocity {
     }
 }
 
-#[derive(Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Density(pub u32);
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Acidity(pub i32);
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Temperature {
     pub current_temperature: i32,
     pub starting_temperature: i32,
@@ -108,10 +112,10 @@ impl Temperature {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct TemperatureChanger(pub i32);
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Burnable {
     pub burn_temperature: i32,
     pub burn_ticks: i32,
@@ -120,7 +124,7 @@ pub struct Burnable {
     pub burning: bool,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Growable {
     pub energy: u32,
     pub spread_chance: u32,
@@ -130,18 +134,24 @@ pub struct Growable {
 }
 
 impl Growable {
-    pub fn new(energy: u32, spread_chance: u32, up_chance: u32, grow_as: ParticleTypes) -> Self {
+    pub fn new(
+        energy: u32,
+        spread_chance: u32,
+        up_chance: u32,
+        grow_as: ParticleTypes,
+        rng: &mut SimulationRng,
+    ) -> Self {
         Self {
             energy,
             spread_chance,
             grow_as,
             up_chance,
-            can_sprout: thread_rng().gen_bool(up_chance as f64 / 100.0),
+            can_sprout: rng.gen_bool(up_chance as f64 / 100.0),
         }
     }
 }
 
-#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum MovementType {
     Solid,
     #[default]
@@ -150,7 +160,7 @@ pub enum MovementType {
     Gas,
 }
 
-#[derive(Clone, Copy, PartialEq, Default, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Default, Eq, Hash, Serialize, Deserialize)]
 pub enum CollisionType {
     #[default]
     None,
@@ -1,7 +1,11 @@
 use bevy::prelude::*;
 
+use crate::{player::rebind::rebind_screen_open, state::GameState};
+
 use super::{
+    history::SandboxHistory,
     particle_types::{get_particle, ParticleTypes},
+    rng::SimulationRng,
     sandbox::Sandbox,
 };
 
@@ -12,7 +16,12 @@ impl Plugin for ParticlePlacerPlugin {
         app.insert_resource(SelectedParticle {
             particle_type: ParticleTypes::Sand,
         })
-        .add_systems(Update, place_particles);
+        .add_systems(
+            Update,
+            (select_particle, place_particles)
+                .chain()
+                .run_if(in_state(GameState::Editing).and_then(not(rebind_screen_open))),
+        );
     }
 }
 
@@ -21,20 +30,34 @@ pub struct SelectedParticle {
     particle_type: ParticleTypes,
 }
 
+fn select_particle(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut selected: ResMut<SelectedParticle>,
+) {
+    if let Some(particle_type) = set_particle_type(keyboard_input) {
+        selected.particle_type = particle_type;
+    }
+}
+
 pub fn place_particles(
     mut sandbox_query: Query<&mut Sandbox>,
     query_window: Query<&Window>,
     query_camera: Query<(&Camera, &GlobalTransform)>,
     mouse_button_input: Res<ButtonInput<MouseButton>>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut selected: ResMut<SelectedParticle>,
+    selected: Res<SelectedParticle>,
+    mut history: ResMut<SandboxHistory>,
+    mut rng: ResMut<SimulationRng>,
 ) {
     let (camera, camera_transform) = query_camera.single();
     let window: &Window = query_window.get_single().unwrap();
     let mut sandbox = sandbox_query.single_mut();
 
-    if let Some(particle_type) = set_particle_type(keyboard_input) {
-        selected.particle_type = particle_type;
+    // Every press of a mouse button is one undoable stroke
+    if mouse_button_input.any_just_pressed([MouseButton::Left, MouseButton::Right]) {
+        history.begin_stroke();
+    }
+    if !mouse_button_input.any_pressed([MouseButton::Left, MouseButton::Right]) {
+        history.end_stroke();
     }
 
     if let Some(world_position) = window
@@ -55,11 +78,16 @@ pub fn place_particles(
                 let y = y.saturating_add_signed(y_offset);
 
                 if mouse_button_input.pressed(MouseButton::Left) && sandbox.get(x, y).is_none() {
-                    sandbox.set(x, y, Some(get_particle(selected.particle_type)));
+                    history.set(
+                        &mut sandbox,
+                        x,
+                        y,
+                        Some(get_particle(selected.particle_type, &mut rng)),
+                    );
                 } else if mouse_button_input.pressed(MouseButton::Right)
                     && sandbox.get(x, y).is_some()
                 {
-                    sandbox.set(x, y, None);
+                    history.set(&mut sandbox, x, y, None);
                 }
             }
         }
@@ -115,6 +143,14 @@ fn set_particle_type(keyboard_input: Res<ButtonInput<KeyCode>>) -> Option<Partic
         println!("Chose Grass");
         return Some(ParticleTypes::Grass);
     }
+    if keyboard_input.just_pressed(KeyCode::KeyB) {
+        println!("Chose Bedrock");
+        return Some(ParticleTypes::Bedrock);
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyI) {
+        println!("Chose Ice");
+        return Some(ParticleTypes::Ice);
+    }
 
     None
 }
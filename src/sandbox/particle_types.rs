@@ -1,9 +1,11 @@
-use super::particle::*;
+use super::{particle::*, rng::SimulationRng};
 use bevy::utils::default;
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ParticleTypes {
+    #[default]
     Sand,
     Water,
     Stone,
@@ -21,10 +23,12 @@ pub enum ParticleTypes {
     Dirt,
     Grass,
     Igneous,
+    Bedrock,
+    Ice,
 }
 
-pub fn get_particle(particle_type: ParticleTypes) -> Particle {
-    match particle_type {
+pub fn get_particle(particle_type: ParticleTypes, rng: &mut SimulationRng) -> Particle {
+    let particle = match particle_type {
         ParticleTypes::Sand => Particle {
             color: (218, 203, 128, 255),
             density: Density(u32::MAX),
@@ -68,7 +72,7 @@ pub fn get_particle(particle_type: ParticleTypes) -> Particle {
             ..default()
         },
         ParticleTypes::Steam => {
-            let tick_health = thread_rng().gen_range(100..120);
+            let tick_health = rng.gen_range(100..120);
             Particle {
                 health: ParticleHealth::new(tick_health, false),
                 color: (240, 233, 201, 255),
@@ -124,7 +128,7 @@ pub fn get_particle(particle_type: ParticleTypes) -> Particle {
             ..default()
         },
         ParticleTypes::Spark => {
-            let tick_health = thread_rng().gen_range(5..10);
+            let tick_health = rng.gen_range(5..10);
             Particle {
                 health: ParticleHealth::new(tick_health, false),
                 color: (204, 146, 94, 255),
@@ -140,7 +144,7 @@ pub fn get_particle(particle_type: ParticleTypes) -> Particle {
             }
         }
         ParticleTypes::Smoke => {
-            let tick_health = thread_rng().gen_range(40..55);
+            let tick_health = rng.gen_range(40..55);
             Particle {
                 health: ParticleHealth::new(tick_health, false),
                 color: (36, 22, 41, 255),
@@ -240,7 +244,7 @@ pub fn get_particle(particle_type: ParticleTypes) -> Particle {
                 cooled_color: (125, 110, 110, 255),
                 burning: false,
             }),
-            growable: Some(Growable::new(2, 50, 25, ParticleTypes::Grass)),
+            growable: Some(Growable::new(2, 50, 25, ParticleTypes::Grass, rng)),
             affected_by_gravity: true,
             ..default()
         },
@@ -252,5 +256,37 @@ pub fn get_particle(particle_type: ParticleTypes) -> Particle {
             affected_by_gravity: true,
             ..default()
         },
+        ParticleTypes::Bedrock => Particle {
+            health: ParticleHealth::new(1, false),
+            color: (46, 42, 48, 255),
+            movement_type: MovementType::Solid,
+            density: Density(u32::MAX),
+            collision_type: CollisionType::Solid,
+            unbreakable: true,
+            ..default()
+        },
+        // Melts back into water when heated, cools what it touches like water does
+        ParticleTypes::Ice => Particle {
+            color: (190, 225, 240, 255),
+            movement_type: MovementType::Solid,
+            density: Density(u32::MAX),
+            temperature: Some(Temperature::new(
+                20,
+                false,
+                true,
+                false,
+                Some(ParticleTypes::Water),
+                0,
+            )),
+            temperature_changer: Some(TemperatureChanger(5)),
+            collision_type: CollisionType::Solid,
+            affected_by_gravity: true,
+            ..default()
+        },
+    };
+
+    Particle {
+        material: particle_type,
+        ..particle
     }
 }
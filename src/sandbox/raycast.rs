@@ -0,0 +1,294 @@
+use bevy::prelude::{IVec2, UVec2, Vec2};
+
+use super::{
+    particle::{CollisionType, Particle},
+    particle_types::ParticleTypes,
+    sandbox::Sandbox,
+};
+
+/// Decides which particles are hit by queries
+#[derive(Clone, Copy, Default)]
+pub enum ParticleFilter {
+    #[default]
+    Any,
+    CollisionType(CollisionType),
+    Material(ParticleTypes),
+    Custom(fn(&Particle) -> bool),
+}
+
+impl ParticleFilter {
+    pub fn matches(&self, particle: &Particle) -> bool {
+        match self {
+            ParticleFilter::Any => true,
+            ParticleFilter::CollisionType(collision_type) => {
+                particle.collision_type == *collision_type
+            }
+            ParticleFilter::Material(material) => particle.material == *material,
+            ParticleFilter::Custom(predicate) => predicate(particle),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct RaycastHit {
+    pub cell: UVec2,
+    pub particle: Particle,
+    /// Normal of the cell face the ray entered through. Zero when the ray started inside the cell.
+    pub normal: IVec2,
+    /// Distance from the origin to where the ray entered the cell, in cells
+    pub distance: f32,
+}
+
+impl Sandbox {
+    /// Walks every cell the ray passes through and returns the first particle matching the filter.
+    /// Positions and distances are in grid coordinates, see [`Sandbox::world_to_grid`]. The ray
+    /// stops once it leaves the grid, so `max_distance` can be infinite.
+    // Based on "A Fast Voxel Traversal Algorithm for Ray Tracing" by Amanatides & Woo
+    pub fn raycast(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        max_distance: f32,
+        filter: ParticleFilter,
+    ) -> Option<RaycastHit> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec2::ZERO {
+            return None;
+        }
+
+        let step = IVec2::new(axis_step(direction.x), axis_step(direction.y));
+        let delta = Vec2::new(1.0 / direction.x.abs(), 1.0 / direction.y.abs());
+
+        let mut cell = origin.floor().as_ivec2();
+        let mut next_boundary = Vec2::new(
+            first_boundary(origin.x, cell.x, step.x, delta.x),
+            first_boundary(origin.y, cell.y, step.y, delta.y),
+        );
+        let mut normal = IVec2::ZERO;
+        let mut distance = 0.0;
+
+        // Past the grid's diagonal from the closest point of the grid there is nothing to hit
+        let size = Vec2::new(self.width() as f32, self.height() as f32);
+        let max_distance =
+            max_distance.min(origin.distance(origin.clamp(Vec2::ZERO, size)) + size.length());
+
+        while distance <= max_distance {
+            if self.leaving_grid(cell, step) {
+                break;
+            }
+            if !self.out_of_bounds_i32(cell.x, cell.y) {
+                if let Some(particle) = self.get(cell.x as usize, cell.y as usize) {
+                    if filter.matches(particle) {
+                        return Some(RaycastHit {
+                            cell: cell.as_uvec2(),
+                            particle: *particle,
+                            normal,
+                            distance,
+                        });
+                    }
+                }
+            }
+
+            if next_boundary.x < next_boundary.y {
+                cell.x += step.x;
+                distance = next_boundary.x;
+                next_boundary.x += delta.x;
+                normal = IVec2::new(-step.x, 0);
+            } else {
+                cell.y += step.y;
+                distance = next_boundary.y;
+                next_boundary.y += delta.y;
+                normal = IVec2::new(0, -step.y);
+            }
+        }
+
+        None
+    }
+
+    /// Whether the cell is outside of the grid and stepping further away from it
+    fn leaving_grid(&self, cell: IVec2, step: IVec2) -> bool {
+        let (width, height) = (self.width() as i32, self.height() as i32);
+        (cell.x < 0 && step.x <= 0)
+            || (cell.x >= width && step.x >= 0)
+            || (cell.y < 0 && step.y <= 0)
+            || (cell.y >= height && step.y >= 0)
+    }
+
+    /// Returns true if no particle matching the filter is between the two grid positions
+    pub fn line_of_sight(&self, from: Vec2, to: Vec2, filter: ParticleFilter) -> bool {
+        self.raycast(from, to - from, from.distance(to), filter)
+            .is_none()
+    }
+
+    /// Collects every particle matching the filter whose cell center is inside the circle
+    pub fn particles_in_circle(
+        &self,
+        center: Vec2,
+        radius: f32,
+        filter: ParticleFilter,
+    ) -> Vec<(UVec2, &Particle)> {
+        let low = (center - radius).floor().as_ivec2();
+        let high = (center + radius).ceil().as_ivec2();
+
+        self.particles_in_rect(low, high, filter)
+            .into_iter()
+            .filter(|(cell, _)| (cell.as_vec2() + 0.5).distance_squared(center) <= radius * radius)
+            .collect()
+    }
+
+    pub fn count_in_circle(&self, center: Vec2, radius: f32, filter: ParticleFilter) -> usize {
+        self.particles_in_circle(center, radius, filter).len()
+    }
+
+    /// Collects every particle matching the filter in the cells from `low` to `high` inclusive
+    pub fn particles_in_rect(
+        &self,
+        low: IVec2,
+        high: IVec2,
+        filter: ParticleFilter,
+    ) -> Vec<(UVec2, &Particle)> {
+        let low = low.max(IVec2::ZERO);
+        let high = high.min(IVec2::new(
+            self.width() as i32 - 1,
+            self.height() as i32 - 1,
+        ));
+
+        let mut particles = vec![];
+        for x in low.x..=high.x {
+            for y in low.y..=high.y {
+                if self.out_of_bounds_i32(x, y) {
+                    continue;
+                }
+                if let Some(particle) = self.get(x as usize, y as usize) {
+                    if filter.matches(particle) {
+                        particles.push((UVec2::new(x as u32, y as u32), particle));
+                    }
+                }
+            }
+        }
+
+        particles
+    }
+
+    pub fn count_in_rect(&self, low: IVec2, high: IVec2, filter: ParticleFilter) -> usize {
+        self.particles_in_rect(low, high, filter).len()
+    }
+}
+
+fn axis_step(direction: f32) -> i32 {
+    if direction > 0.0 {
+        1
+    } else if direction < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+/// Distance along the ray to the first cell boundary on one axis
+fn first_boundary(origin: f32, cell: i32, step: i32, delta: f32) -> f32 {
+    match step {
+        1 => (cell as f32 + 1.0 - origin) * delta,
+        -1 => (origin - cell as f32) * delta,
+        _ => f32::INFINITY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sandbox::{particle_types::get_particle, rng::SimulationRng};
+
+    fn sandbox_with_stone(cells: &[(usize, usize)]) -> Sandbox {
+        let mut sandbox = Sandbox::new(2, 2, 8, 8);
+        let mut rng = SimulationRng::seeded(0);
+        for (x, y) in cells {
+            sandbox.set(*x, *y, Some(get_particle(ParticleTypes::Stone, &mut rng)));
+        }
+        sandbox
+    }
+
+    #[test]
+    fn hits_first_matching_cell() {
+        let sandbox = sandbox_with_stone(&[(10, 4), (12, 4)]);
+
+        let hit = sandbox
+            .raycast(
+                Vec2::new(2.5, 4.5),
+                Vec2::X,
+                f32::INFINITY,
+                ParticleFilter::Any,
+            )
+            .unwrap();
+        assert_eq!(hit.cell, UVec2::new(10, 4));
+        assert_eq!(hit.particle.material, ParticleTypes::Stone);
+        assert_eq!(hit.normal, IVec2::new(-1, 0));
+        assert_eq!(hit.distance, 7.5);
+    }
+
+    #[test]
+    fn stops_at_max_distance() {
+        let sandbox = sandbox_with_stone(&[(10, 4)]);
+
+        let hit = sandbox.raycast(Vec2::new(2.5, 4.5), Vec2::X, 5.0, ParticleFilter::Any);
+        assert!(hit.is_none());
+    }
+
+    #[test]
+    fn leaves_grid_without_max_distance() {
+        let sandbox = sandbox_with_stone(&[]);
+
+        for direction in [Vec2::X, Vec2::NEG_Y, Vec2::new(-1.0, 3.0)] {
+            let hit = sandbox.raycast(
+                Vec2::new(8.0, 8.0),
+                direction,
+                f32::INFINITY,
+                ParticleFilter::Any,
+            );
+            assert!(hit.is_none());
+        }
+
+        // Starts outside of the grid and moves away from it
+        let hit = sandbox.raycast(
+            Vec2::new(-4.0, 8.0),
+            Vec2::NEG_X,
+            f32::INFINITY,
+            ParticleFilter::Any,
+        );
+        assert!(hit.is_none());
+    }
+
+    #[test]
+    fn enters_grid_from_outside() {
+        let sandbox = sandbox_with_stone(&[(0, 4)]);
+
+        let hit = sandbox
+            .raycast(
+                Vec2::new(-20.5, 4.5),
+                Vec2::X,
+                f32::INFINITY,
+                ParticleFilter::Any,
+            )
+            .unwrap();
+        assert_eq!(hit.cell, UVec2::new(0, 4));
+        assert_eq!(hit.distance, 20.5);
+    }
+
+    #[test]
+    fn filters_particles() {
+        let sandbox = sandbox_with_stone(&[(6, 4)]);
+        let water = ParticleFilter::Material(ParticleTypes::Water);
+
+        assert!(!sandbox.line_of_sight(
+            Vec2::new(2.5, 4.5),
+            Vec2::new(12.5, 4.5),
+            ParticleFilter::Any
+        ));
+        assert!(sandbox.line_of_sight(Vec2::new(2.5, 4.5), Vec2::new(12.5, 4.5), water));
+        assert_eq!(
+            sandbox.count_in_circle(Vec2::new(6.5, 4.5), 2.0, ParticleFilter::Any),
+            1
+        );
+        assert_eq!(sandbox.count_in_circle(Vec2::new(6.5, 4.5), 2.0, water), 0);
+    }
+}
@@ -1,4 +1,4 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, render::render_resource::Extent3d};
 
 use super::sandbox::Sandbox;
 
@@ -8,32 +8,50 @@ const BACKGROUND_COLOR: (u8, u8, u8, u8) = (0, 0, 0, 0);
 // Based on https://github.com/grunnt/falling-rust/blob/master/src/render.rs
 pub fn render_particles(
     mut images: ResMut<Assets<Image>>,
-    mut sandbox: Query<(&mut Sandbox, &Handle<Image>)>,
+    sandbox: Query<(&Sandbox, &Handle<Image>)>,
 ) {
-    let (mut sandbox, image_handle) = sandbox
-        .get_single_mut()
+    let (sandbox, image_handle) = sandbox
+        .get_single()
         .expect("Sandbox should be created by this point");
 
     let image = images.get_mut(image_handle).unwrap();
-    for y in 0..sandbox.height() {
-        for x in 0..sandbox.width() {
-            if !sandbox.get_chunk(x, y).is_strong_ticked() {
-                continue;
-            }
+    // Replays can reset the arena into a sandbox of a different size
+    let size = Extent3d {
+        width: sandbox.width() as u32,
+        height: sandbox.height() as u32,
+        depth_or_array_layers: 1,
+    };
+    if image.texture_descriptor.size != size {
+        image.resize(size);
+    }
+    let (chunk_width, chunk_height) = (sandbox.chunk_width(), sandbox.chunk_height());
+    for chunk_index in 0..sandbox.get_all_chunks().len() {
+        // Only awake chunks can change, so unloaded chunks keep their last drawn pixels
+        let chunk = &sandbox.get_all_chunks()[chunk_index];
+        if !chunk.is_strong_ticked() {
+            continue;
+        }
+        let (low_x, low_y) = (
+            chunk.local_position.0 * chunk_width,
+            chunk.local_position.1 * chunk_height,
+        );
 
-            let particle = sandbox.get_mut(x, y);
-            let color = match particle {
-                Some(particle) => particle.color,
-                None => BACKGROUND_COLOR,
-            };
+        for y in low_y..low_y + chunk_height {
+            for x in low_x..low_x + chunk_width {
+                let particle = sandbox.get(x, y);
+                let color = match particle {
+                    Some(particle) => particle.color,
+                    None => BACKGROUND_COLOR,
+                };
 
-            let bytes_per_pixel = 4;
-            let index = (x + y * sandbox.width()) * bytes_per_pixel;
+                let bytes_per_pixel = 4;
+                let index = (x + y * sandbox.width()) * bytes_per_pixel;
 
-            image.data[index] = color.0;
-            image.data[index + 1] = color.1;
-            image.data[index + 2] = color.2;
-            image.data[index + 3] = color.3;
+                image.data[index] = color.0;
+                image.data[index + 1] = color.1;
+                image.data[index + 2] = color.2;
+                image.data[index + 3] = color.3;
+            }
         }
     }
 }
@@ -0,0 +1,38 @@
+use bevy::prelude::Resource;
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+/// Everything random in the simulation draws from this generator, so a match plays out the same
+/// way again when it starts from the same seed. Seeds itself randomly until a replay or match
+/// replaces it with [`SimulationRng::seeded`].
+#[derive(Resource, Clone)]
+pub struct SimulationRng(StdRng);
+
+impl SimulationRng {
+    pub fn seeded(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl Default for SimulationRng {
+    fn default() -> Self {
+        Self(StdRng::from_entropy())
+    }
+}
+
+impl RngCore for SimulationRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
@@ -1,23 +1,34 @@
-use bevy::prelude::Component;
+use std::sync::Arc;
 
-use super::{chunk::SandboxChunk, particle::Particle};
+use bevy::prelude::{Component, IVec2, Vec2};
 
-#[derive(Component)]
+use super::{
+    chunk::SandboxChunk,
+    particle::{MovementType, Particle},
+};
+
+#[derive(Component, Clone)]
 pub struct Sandbox {
     x_chunks: usize,
-    _y_chunks: usize,
+    y_chunks: usize,
     chunk_width: usize,
     chunk_height: usize,
     total_width: usize,
     total_height: usize,
-    chunks: Vec<SandboxChunk>,
+    /// Clones of the sandbox share their chunks until one of them changes a chunk, so
+    /// snapshots only copy the chunks that change after them
+    chunks: Vec<Arc<SandboxChunk>>,
+    /// Cells that stopped holding a solid particle since the last `take_removed_solids`
+    removed_solids: Vec<(usize, usize)>,
+    /// Cells and radii of explosions since the last `take_explosions`
+    explosions: Vec<(usize, usize, i32)>,
 }
 
 impl Sandbox {
     pub fn new(x_chunks: usize, y_chunks: usize, chunk_width: usize, chunk_height: usize) -> Self {
         Self {
             x_chunks,
-            _y_chunks: y_chunks,
+            y_chunks,
             chunk_width,
             chunk_height,
             total_width: x_chunks * chunk_width,
@@ -25,10 +36,17 @@ impl Sandbox {
             chunks: {
                 let mut chunks = Vec::with_capacity(x_chunks * y_chunks);
                 for i in 0..chunks.capacity() {
-                    chunks.push(SandboxChunk::new(chunk_width, chunk_height, i, x_chunks));
+                    chunks.push(Arc::new(SandboxChunk::new(
+                        chunk_width,
+                        chunk_height,
+                        i,
+                        x_chunks,
+                    )));
                 }
                 chunks
             },
+            removed_solids: vec![],
+            explosions: vec![],
         }
     }
 
@@ -58,7 +76,8 @@ impl Sandbox {
     pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Particle> {
         let index = self.to_index(x, y);
         self.chunks
-            .get_mut(index)?
+            .get_mut(index)
+            .map(Arc::make_mut)?
             .get_mut(x % self.chunk_width, y % self.chunk_height)
     }
 
@@ -76,11 +95,28 @@ impl Sandbox {
             return;
         }
 
-        self.chunks[index].set(x % self.chunk_width, y % self.chunk_height, particle);
+        let (local_x, local_y) = (x % self.chunk_width, y % self.chunk_height);
+        let was_solid = is_solid(self.chunks[index].get(local_x, local_y));
+        self.chunk_mut(index).set(local_x, local_y, particle);
+        if was_solid && !is_solid(particle.as_ref()) {
+            self.removed_solids.push((x, y));
+        }
 
         self.strong_tick_neighbors(x, y);
     }
 
+    pub fn take_removed_solids(&mut self) -> Vec<(usize, usize)> {
+        std::mem::take(&mut self.removed_solids)
+    }
+
+    pub fn record_explosion(&mut self, x: usize, y: usize, radius: i32) {
+        self.explosions.push((x, y, radius));
+    }
+
+    pub fn take_explosions(&mut self) -> Vec<(usize, usize, i32)> {
+        std::mem::take(&mut self.explosions)
+    }
+
     pub fn swap(&mut self, x1: usize, y1: usize, x2: usize, y2: usize) {
         let index1 = self.to_index(x1, y1);
         let index2 = self.to_index(x2, y2);
@@ -92,8 +128,11 @@ impl Sandbox {
             .get(x2 % self.chunk_width, y2 % self.chunk_height)
             .copied();
 
-        self.chunks[index1].set(x1 % self.chunk_width, y1 % self.chunk_height, particle2);
-        self.chunks[index2].set(x2 % self.chunk_width, y2 % self.chunk_height, particle1);
+        let (chunk_width, chunk_height) = (self.chunk_width, self.chunk_height);
+        self.chunk_mut(index1)
+            .set(x1 % chunk_width, y1 % chunk_height, particle2);
+        self.chunk_mut(index2)
+            .set(x2 % chunk_width, y2 % chunk_height, particle1);
 
         self.strong_tick_neighbors(x1, y1);
         self.strong_tick_neighbors(x2, y2);
@@ -106,13 +145,41 @@ impl Sandbox {
 
     pub fn get_chunk_mut(&mut self, x: usize, y: usize) -> &mut SandboxChunk {
         let index = self.to_index(x, y);
-        &mut self.chunks[index]
+        self.chunk_mut(index)
     }
 
-    pub fn get_all_chunks(&self) -> &[SandboxChunk] {
+    pub fn get_all_chunks(&self) -> &[Arc<SandboxChunk>] {
         &self.chunks
     }
 
+    /// Copies the chunk first while a clone of the sandbox still shares it
+    fn chunk_mut(&mut self, index: usize) -> &mut SandboxChunk {
+        Arc::make_mut(&mut self.chunks[index])
+    }
+
+    pub fn replace_chunk(&mut self, index: usize, particles: Vec<Option<Particle>>) {
+        if index < self.chunks.len() {
+            self.chunk_mut(index).replace(particles);
+        }
+    }
+
+    pub fn load_chunk(&mut self, index: usize) {
+        if !self.chunks[index].is_loaded() {
+            self.chunk_mut(index).load();
+        }
+    }
+
+    pub fn unload_chunk(&mut self, index: usize) {
+        if self.chunks[index].is_loaded() {
+            self.chunk_mut(index).unload();
+        }
+    }
+
+    /// Replaces the grid with an empty one of a different size
+    pub fn resize(&mut self, x_chunks: usize, y_chunks: usize) {
+        *self = Sandbox::new(x_chunks, y_chunks, self.chunk_width, self.chunk_height);
+    }
+
     fn strong_tick_neighbors(&mut self, x: usize, y: usize) {
         let search_directions = [
             (x.overflowing_sub(1).0, y),
@@ -136,18 +203,32 @@ impl Sandbox {
 
     pub fn mark_updated(&mut self, x: usize, y: usize) {
         let index = self.to_index(x, y);
-        self.chunks[index].mark_updated(x % self.chunk_width, y % self.chunk_height);
+        let (chunk_width, chunk_height) = (self.chunk_width, self.chunk_height);
+        self.chunk_mut(index)
+            .mark_updated(x % chunk_width, y % chunk_height);
     }
 
+    /// Only touches chunks with updated particles, so the rest stay shared with snapshots
     pub fn reset_updated(&mut self) {
         for chunk in self.chunks.iter_mut() {
-            chunk.reset_updated();
+            if chunk.has_updated() {
+                Arc::make_mut(chunk).reset_updated();
+            }
         }
     }
 
+    /// Only touches awake chunks, so sleeping chunks stay shared with snapshots
     pub fn reset_ticked_chunks(&mut self) {
         for chunk in self.chunks.iter_mut() {
-            chunk.reset_ticked();
+            if chunk.is_strong_ticked() || chunk.is_weak_ticked() {
+                Arc::make_mut(chunk).reset_ticked();
+            }
+        }
+    }
+
+    pub fn wake_all_chunks(&mut self) {
+        for chunk in self.chunks.iter_mut() {
+            Arc::make_mut(chunk).strong_tick();
         }
     }
 
@@ -178,19 +259,71 @@ impl Sandbox {
         self.total_width
     }
 
+    pub fn x_chunks(&self) -> usize {
+        self.x_chunks
+    }
+
+    pub fn y_chunks(&self) -> usize {
+        self.y_chunks
+    }
+
+    pub fn chunk_width(&self) -> usize {
+        self.chunk_width
+    }
+
+    pub fn chunk_height(&self) -> usize {
+        self.chunk_height
+    }
+
     pub fn height(&self) -> usize {
         self.total_height
     }
 
+    /// Converts a world position into continuous grid coordinates, where cell `(x, y)` covers
+    /// `x..x + 1` and `y..y + 1`
+    pub fn world_to_grid(&self, position: Vec2) -> Vec2 {
+        position / 8.0
+            + Vec2::new(
+                (self.total_width / 2) as f32,
+                (self.total_height / 2) as f32,
+            )
+    }
+
+    pub fn world_to_cell(&self, position: Vec2) -> IVec2 {
+        self.world_to_grid(position).floor().as_ivec2()
+    }
+
+    /// Returns the world position of the center of a cell
+    pub fn cell_to_world(&self, x: i32, y: i32) -> Vec2 {
+        (Vec2::new(x as f32, y as f32) + 0.5
+            - Vec2::new(
+                (self.total_width / 2) as f32,
+                (self.total_height / 2) as f32,
+            ))
+            * 8.0
+    }
+
+    /// Cells inside unloaded chunks count as out of bounds
     pub fn out_of_bounds_i32(&self, x: i32, y: i32) -> bool {
-        x < 0 || x >= self.total_width as i32 || y < 0 || y >= self.total_height as i32
+        x < 0
+            || x >= self.total_width as i32
+            || y < 0
+            || y >= self.total_height as i32
+            || !self.chunks[self.to_index(x as usize, y as usize)].is_loaded()
     }
 
+    /// Cells inside unloaded chunks count as out of bounds
     pub fn out_of_bounds_usize(&self, x: usize, y: usize) -> bool {
-        x >= self.total_width || y >= self.total_height
+        x >= self.total_width
+            || y >= self.total_height
+            || !self.chunks[self.to_index(x, y)].is_loaded()
     }
 
     fn to_index(&self, x: usize, y: usize) -> usize {
         ((y / self.chunk_height) * self.x_chunks) + x / self.chunk_width
     }
 }
+
+pub fn is_solid(particle: Option<&Particle>) -> bool {
+    particle.is_some_and(|particle| particle.movement_type == MovementType::Solid)
+}
@@ -5,30 +5,82 @@ use super::effects::growable::tick_growable;
 use super::effects::movement::tick_movement;
 use super::effects::temperature::tick_temperature;
 use super::effects::tick_life::tick_life;
+use super::rng::SimulationRng;
 use super::sandbox::*;
+use crate::schedule::FIXED_HZ;
 
-pub fn update_particles(mut sandbox_query: Query<&mut Sandbox>) {
+/// Sandbox ticks per second
+const TICKS_PER_SECOND: u64 = 24;
+
+#[derive(Resource, Default, Clone)]
+pub struct SimulationControl {
+    pub paused: bool,
+    pending_steps: u32,
+    /// Fixed steps since the clock was reset, the sandbox ticks on some of them
+    fixed_steps: u64,
+    tick_due: bool,
+}
+
+impl SimulationControl {
+    /// Runs a single tick of the simulation while it is paused
+    pub fn step(&mut self) {
+        self.pending_steps += 1;
+    }
+
+    /// Ticks the sandbox on the next fixed step and on the same steps after it every time, so
+    /// replays tick in step with the recording
+    pub fn reset_clock(&mut self) {
+        self.fixed_steps = 0;
+    }
+}
+
+/// Counts the sandbox ticks in fixed steps instead of time, so they never drift from the steps
+pub fn advance_clock(mut control: ResMut<SimulationControl>) {
+    control.tick_due =
+        control.fixed_steps * TICKS_PER_SECOND % u64::from(FIXED_HZ) < TICKS_PER_SECOND;
+    control.fixed_steps += 1;
+}
+
+pub fn tick_due(control: Res<SimulationControl>) -> bool {
+    control.tick_due
+}
+
+pub fn simulation_running(control: Res<SimulationControl>) -> bool {
+    !control.paused || control.pending_steps > 0
+}
+
+pub fn update_particles(
+    mut sandbox_query: Query<&mut Sandbox>,
+    mut control: ResMut<SimulationControl>,
+    mut rng: ResMut<SimulationRng>,
+) {
     let mut sandbox = sandbox_query
         .get_single_mut()
         .expect("There should be a Sandbox at this point");
+    control.pending_steps = control.pending_steps.saturating_sub(1);
 
     sandbox.reset_ticked_chunks();
 
+    let chunk_height = sandbox.chunk_height();
     for x in 0..sandbox.width() {
-        for y in 0..sandbox.height() {
-            let current_chunk = sandbox.get_chunk(x, y);
+        for chunk_y in 0..sandbox.y_chunks() {
+            // Sleeping and unloaded chunks are skipped as a whole
+            let low_y = chunk_y * chunk_height;
+            let current_chunk = sandbox.get_chunk(x, low_y);
             if !current_chunk.is_weak_ticked() && !current_chunk.is_strong_ticked() {
                 continue;
             }
 
-            step_particle(x, y, &mut sandbox);
+            for y in low_y..low_y + chunk_height {
+                step_particle(x, y, &mut sandbox, &mut rng);
+            }
         }
     }
 
     sandbox.reset_updated();
 }
 
-fn step_particle(x: usize, y: usize, sandbox: &mut Sandbox) {
+fn step_particle(x: usize, y: usize, sandbox: &mut Sandbox, rng: &mut SimulationRng) {
     match sandbox.get(x, y) {
         Some(particle) => {
             if particle.updated {
@@ -45,13 +97,13 @@ fn step_particle(x: usize, y: usize, sandbox: &mut Sandbox) {
     if tick_acidity(x, y, sandbox) {
         return;
     }
-    if tick_temperature(x, y, sandbox) {
+    if tick_temperature(x, y, sandbox, rng) {
         return;
     }
-    if tick_life(x, y, sandbox) {
+    if tick_life(x, y, sandbox, rng) {
         return;
     }
 
-    tick_growable(x, y, sandbox);
-    tick_movement(x, y, sandbox);
+    tick_growable(x, y, sandbox, rng);
+    tick_movement(x, y, sandbox, rng);
 }
@@ -0,0 +1,84 @@
+use bevy::prelude::*;
+
+use super::sandbox::Sandbox;
+use crate::schedule::GameplaySet;
+
+pub struct ChunkStreamingPlugin;
+
+impl Plugin for ChunkStreamingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(StreamingSettings::default())
+            .add_systems(FixedUpdate, stream_chunks.in_set(GameplaySet::Streaming));
+    }
+}
+
+/// Keeps the chunks around the entity loaded
+#[derive(Component, Default)]
+pub struct ChunkLoader;
+
+/// Distances are in world units. Chunks between the two distances keep their current state so
+/// they don't flicker between loaded and unloaded.
+#[derive(Resource)]
+pub struct StreamingSettings {
+    pub load_distance: f32,
+    pub unload_distance: f32,
+}
+
+impl Default for StreamingSettings {
+    fn default() -> Self {
+        Self {
+            load_distance: 1600.0,
+            unload_distance: 2000.0,
+        }
+    }
+}
+
+fn stream_chunks(
+    mut sandbox_query: Query<&mut Sandbox>,
+    loader_query: Query<&GlobalTransform, With<ChunkLoader>>,
+    settings: Res<StreamingSettings>,
+) {
+    let Ok(mut sandbox) = sandbox_query.get_single_mut() else {
+        return;
+    };
+
+    let loaders: Vec<Vec2> = loader_query
+        .iter()
+        .map(|transform| transform.translation().truncate())
+        .collect();
+
+    // Nothing is streamed while there is nobody in the world, e.g. while editing
+    if loaders.is_empty() {
+        for i in 0..sandbox.get_all_chunks().len() {
+            if !sandbox.get_all_chunks()[i].is_loaded() {
+                sandbox.load_chunk(i);
+            }
+        }
+        return;
+    }
+
+    let (chunk_width, chunk_height) = (sandbox.chunk_width() as i32, sandbox.chunk_height() as i32);
+
+    for i in 0..sandbox.get_all_chunks().len() {
+        let chunk = &sandbox.get_all_chunks()[i];
+        let low = IVec2::new(
+            chunk.local_position.0 as i32 * chunk_width,
+            chunk.local_position.1 as i32 * chunk_height,
+        );
+        let high = low + IVec2::new(chunk_width - 1, chunk_height - 1);
+        // Halfway between the centers of the chunk's corner cells
+        let center =
+            (sandbox.cell_to_world(low.x, low.y) + sandbox.cell_to_world(high.x, high.y)) / 2.0;
+
+        let distance = loaders
+            .iter()
+            .map(|loader| loader.distance(center))
+            .fold(f32::MAX, f32::min);
+
+        if distance <= settings.load_distance && !chunk.is_loaded() {
+            sandbox.load_chunk(i);
+        } else if distance >= settings.unload_distance && chunk.is_loaded() {
+            sandbox.unload_chunk(i);
+        }
+    }
+}
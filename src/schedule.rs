@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::net::is_client;
+
+/// Gameplay steps per second, the sandbox ticks on some of them
+pub const FIXED_HZ: u32 = 60;
+
+pub struct SchedulePlugin;
+
+impl Plugin for SchedulePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Time::<Fixed>::from_hz(FIXED_HZ as f64))
+            .configure_sets(
+                FixedUpdate,
+                (
+                    GameplaySet::Replay,
+                    GameplaySet::Reset,
+                    GameplaySet::Input,
+                    GameplaySet::Join,
+                    GameplaySet::Record,
+                    GameplaySet::Movement,
+                    GameplaySet::Grab,
+                    GameplaySet::Bending,
+                    GameplaySet::Set,
+                    GameplaySet::Parry,
+                    GameplaySet::Status,
+                    GameplaySet::Damage,
+                    GameplaySet::Match,
+                    GameplaySet::Streaming,
+                    GameplaySet::Simulation,
+                    GameplaySet::Islands,
+                    GameplaySet::Terrain,
+                )
+                    .chain()
+                    .before(PhysicsSet::SyncBackend)
+                    .run_if(not(is_client)),
+            )
+            .add_systems(Startup, fix_physics_timestep);
+    }
+}
+
+/// Everything that changes the match runs in `FixedUpdate` in this order, so the same inputs
+/// always play out the same way, no matter the frame rate. Network clients run none of it, they
+/// show the match of the server.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameplaySet {
+    /// Starts replays and plays back their resets, joins and leaves
+    Replay,
+    /// Puts the arena back into its edited layout
+    Reset,
+    /// Bots, the mouse and replays press actions
+    Input,
+    /// Players join and leave
+    Join,
+    /// Replays record the actions, joins and leaves of the step
+    Record,
+    /// Walking, jumping and aiming
+    Movement,
+    /// Grabbing, throwing and placing blocks back
+    Grab,
+    /// Switching elements and their abilities
+    Bending,
+    Set,
+    Parry,
+    Status,
+    /// Damage, knockback and knockouts
+    Damage,
+    /// Respawns, the match clock and the winner
+    Match,
+    Streaming,
+    /// Falling sand
+    Simulation,
+    Islands,
+    /// Terrain colliders and buoyancy
+    Terrain,
+}
+
+/// Physics steps once per gameplay step
+fn fix_physics_timestep(mut config: ResMut<RapierConfiguration>) {
+    config.timestep_mode = TimestepMode::Fixed {
+        dt: 1.0 / FIXED_HZ as f32,
+        substeps: 1,
+    };
+}
@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+
+use crate::sandbox::{
+    particle::{MovementType, Particle},
+    raycast::ParticleFilter,
+    sandbox::Sandbox,
+};
+
+/// Level pixels with this color mark a spawn point instead of dirt
+pub const SPAWN_POINT_COLOR: (u8, u8, u8, u8) = (255, 0, 255, 255);
+/// Cells around a spawn point that have to be free, enough to fit a player
+const SPAWN_CLEARANCE: i32 = 2;
+/// Spawn points closer than this to a player are taken
+const OCCUPIED_DISTANCE: f32 = 60.0;
+
+/// Cells where players can spawn, declared by the level. Lives on the sandbox entity.
+#[derive(Component, Default)]
+pub struct SpawnPoints(pub Vec<IVec2>);
+
+impl SpawnPoints {
+    /// Picks the free spawn point farthest away from the given players. Points that are taken
+    /// or buried are only used when nothing else is left, and buried points move up until they
+    /// are clear.
+    pub fn pick(&self, sandbox: &Sandbox, players: &[Vec2]) -> Vec2 {
+        // Levels without spawn points spawn in the middle of the arena
+        let fallback = [sandbox.world_to_cell(Vec2::ZERO)];
+        let points = if self.0.is_empty() {
+            &fallback[..]
+        } else {
+            &self.0[..]
+        };
+
+        let distance_to_players = |cell: IVec2| {
+            let position = sandbox.cell_to_world(cell.x, cell.y);
+            players
+                .iter()
+                .map(|player| player.distance(position))
+                .fold(f32::INFINITY, f32::min)
+        };
+
+        let best = points
+            .iter()
+            .map(|cell| {
+                let distance = distance_to_players(*cell);
+                let free = is_clear(sandbox, *cell) && distance >= OCCUPIED_DISTANCE;
+                (*cell, free, distance)
+            })
+            .max_by(|(_, a_free, a_distance), (_, b_free, b_distance)| {
+                a_free.cmp(b_free).then(a_distance.total_cmp(b_distance))
+            })
+            .map(|(cell, ..)| cell)
+            .unwrap_or(fallback[0]);
+
+        let cell = dig_out(sandbox, best);
+        sandbox.cell_to_world(cell.x, cell.y)
+    }
+}
+
+/// Gases don't block a player from spawning
+fn is_blocking(particle: &Particle) -> bool {
+    particle.movement_type != MovementType::Gas
+}
+
+fn is_clear(sandbox: &Sandbox, cell: IVec2) -> bool {
+    sandbox.count_in_rect(
+        cell - SPAWN_CLEARANCE,
+        cell + SPAWN_CLEARANCE,
+        ParticleFilter::Custom(is_blocking),
+    ) == 0
+}
+
+/// Moves a buried spawn point up to the first clear spot above it
+fn dig_out(sandbox: &Sandbox, cell: IVec2) -> IVec2 {
+    (cell.y..sandbox.height() as i32)
+        .map(|y| IVec2::new(cell.x, y))
+        .find(|cell| is_clear(sandbox, *cell))
+        .unwrap_or(cell)
+}
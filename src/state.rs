@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+
+use crate::{net::is_client, player::rebind::rebind_screen_open};
+
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<GameState>()
+            .enable_state_scoped_entities::<GameState>()
+            .add_event::<ResetArena>()
+            .add_systems(
+                Update,
+                (
+                    // Tab can be bound on the rebind screen
+                    toggle_editor.run_if(not(rebind_screen_open)),
+                    request_reset.run_if(in_state(GameState::Playing)),
+                )
+                    // The arena of a client is the one of the server
+                    .run_if(not(is_client)),
+            )
+            .add_systems(OnExit(GameState::Playing), reset_on_exit);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
+pub enum GameState {
+    Editing,
+    #[default]
+    Playing,
+}
+
+/// Puts the arena back into the layout it had when the match started
+#[derive(Event)]
+pub struct ResetArena;
+
+fn toggle_editor(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Tab) {
+        return;
+    }
+
+    match state.get() {
+        GameState::Editing => next_state.set(GameState::Playing),
+        GameState::Playing => next_state.set(GameState::Editing),
+    }
+}
+
+fn request_reset(keyboard_input: Res<ButtonInput<KeyCode>>, mut events: EventWriter<ResetArena>) {
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        events.send(ResetArena);
+    }
+}
+
+fn reset_on_exit(mut events: EventWriter<ResetArena>) {
+    events.send(ResetArena);
+}